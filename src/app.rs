@@ -1,14 +1,22 @@
 use egui::{Color32, NumExt, Pos2, Rect, RichText, ScrollArea, Slider, Stroke, TextStyle, Vec2};
+use fnv::FnvHasher;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::hash::Hasher;
+use std::panic::AssertUnwindSafe;
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::Instant;
+use url::Url;
 
 use crate::data::{
-    DataSource, EntryID, EntryInfo, Field, SlotMetaTile, SlotTile, TileID, UtilPoint,
+    format_task_details, merge_small_items, sanitize_items, snap_to_tiles, DataSource,
+    DataSourceError, EntryID, EntryInfo, EntryKind, Field, Item, ItemUID, SlotMetaTile, SlotTile,
+    TileID, TimeOrigin, UtilPoint,
 };
+use crate::http::client::parse_urls;
 use crate::search::{SelectedItem, SelectedState};
-use crate::timestamp::Interval;
+use crate::stats::{compute_stats, IntervalStats};
+use crate::timestamp::{AxisFormatter, DisplayConfig, Interval, Timestamp, TimestampParseError};
 
 /// Overview:
 ///   ProfApp -> Context, Window *
@@ -42,11 +50,17 @@ use crate::timestamp::Interval;
 
 const MAX_SELECTED_ITEMS: u64 = 1000;
 const MAX_SEARCHED_ITEMS: u64 = 100000;
+// Oldest views are forgotten first
+const MAX_ZOOM_LEVELS: usize = 100;
 
 struct Summary {
     entry_id: EntryID,
     color: Color32,
     utilization: Vec<UtilPoint>,
+    // Whether utilization covers the view (it may not while fetching)
+    loaded: bool,
+    // What the tiles of utilization cover, see snap_to_tiles
+    fetched_interval: Option<Interval>,
     last_view_interval: Option<Interval>,
 }
 
@@ -54,17 +68,26 @@ struct Slot {
     entry_id: EntryID,
     short_name: String,
     long_name: String,
+    kind: EntryKind,
     expanded: bool,
     max_rows: u64,
     tiles: Vec<SlotTile>,
     tile_metas: BTreeMap<TileID, SlotMetaTile>,
+    // Whether tiles cover the view (they may not while fetching)
+    loaded: bool,
+    // What tiles (and tile_metas) were clipped to
+    fetched_interval: Option<Interval>,
     last_view_interval: Option<Interval>,
+    // Whether tiles contain merged items, which can't be selected
+    merged: bool,
 }
 
 struct Panel<S: Entry> {
     entry_id: EntryID,
     short_name: String,
     long_name: String,
+    // The long name, followed by the kind of the slots, if they share one
+    hover_text: String,
     expanded: bool,
 
     summary: Option<Summary>,
@@ -78,8 +101,37 @@ struct Config {
 
     // This is just for the local profile
     interval: Interval,
+    // What timestamp 0 means
+    time_origin: TimeOrigin,
 
     data_source: Box<dyn DataSource>,
+
+    // Most recent failed tile fetches, oldest first
+    errors: Vec<DataSourceError>,
+
+    // Sources that don't fetch in the background (see DeferredDataSource)
+    // fetch on the UI thread, so stop fetching for the rest of the frame once
+    // this passes. Entries that miss out try again next frame.
+    #[cfg(not(target_arch = "wasm32"))]
+    fetch_deadline: Option<Instant>,
+
+    // Slot under the mouse this frame, for keyboard navigation
+    hovered_slot: Option<EntryID>,
+
+    // Screen rects of the slots drawn this frame, for region selection
+    slot_rects: Vec<(EntryID, Rect)>,
+
+    // Entries drawn without data this frame because their tiles are still
+    // being fetched, and the interval those tiles will cover
+    loading: BTreeMap<EntryID, Interval>,
+
+    // View interval as of the last frame, to tell when fetches queued for it
+    // are no longer wanted
+    view_interval: Option<Interval>,
+
+    // Whether data_source repaints once a fetch completes, so that entries
+    // waiting on one needn't repaint every frame. None until asked.
+    repaints_on_fetch: Option<bool>,
 }
 
 struct Window {
@@ -87,6 +139,13 @@ struct Window {
     index: u64,
     kinds: Vec<String>,
     config: Config,
+    // Set when drawing the window panicked, e.g., on a malformed tile
+    panic_message: Option<String>,
+    // Statistics of the expanded slots over the pinned measurement, which
+    // are only recomputed when either changes
+    stats: Option<(Interval, Vec<EntryID>, IntervalStats)>,
+    // Slots and time range picked with Ctrl+drag, kept until cleared
+    region: Option<(Vec<EntryID>, Interval, IntervalStats)>,
 }
 
 #[derive(Default, Deserialize, Serialize)]
@@ -96,6 +155,49 @@ struct ZoomState {
     zoom_count: u32, // factor out
 }
 
+#[derive(Deserialize, Serialize)]
+struct NavConfig {
+    // Fraction of the view interval to pan by
+    pan_step: f32,
+    // Factor to zoom in/out by
+    zoom_factor: f32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+enum ColorScheme {
+    // The colors chosen by the data source
+    #[default]
+    Default,
+    // Okabe-Ito palette, distinguishable with color vision deficiency
+    ColorblindSafe,
+    Grayscale,
+}
+
+// How tall rows are, relative to a line of body text
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+enum Density {
+    #[default]
+    Normal,
+    Compact,
+    Dense,
+}
+
+impl Density {
+    fn scale(self) -> f32 {
+        match self {
+            Density::Normal => 1.0,
+            Density::Compact => 0.6,
+            Density::Dense => 0.35,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Bookmark {
+    name: String,
+    interval: Interval,
+}
+
 #[derive(Default, Deserialize, Serialize)]
 struct Context {
     row_height: f32,
@@ -114,6 +216,16 @@ struct Context {
 
     drag_origin: Option<Pos2>,
 
+    #[serde(skip)]
+    overview_drag_origin: Option<Pos2>,
+
+    // Ruler: shift-click sets the anchor, a second shift-click pins the
+    // measurement
+    #[serde(skip)]
+    measure_anchor: Option<Timestamp>,
+    #[serde(skip)]
+    measurement: Option<Interval>,
+
     // Hack: We need to track the screenspace rect where slot/summary
     // data gets drawn. This gets used rendering the cursor, but we
     // only know it when we render slots. So stash it here.
@@ -126,9 +238,75 @@ struct Context {
 
     toggle_dark_mode: bool,
 
+    display_config: DisplayConfig,
+
+    #[serde(default)]
+    axis_formatter: AxisFormatter,
+
+    // Label the axis with wall-clock time, for profiles that record it
+    #[serde(default)]
+    wall_clock_axis: bool,
+
+    #[serde(default)]
+    nav_config: NavConfig,
+
+    #[serde(default)]
+    color_scheme: ColorScheme,
+
+    #[serde(default)]
+    density: Density,
+
+    #[serde(default)]
+    bookmarks: Vec<Bookmark>,
+
+    #[serde(skip)]
+    bookmark_name_buffer: String,
+
+    // Keep the end of the profile(s) at the right edge of the view while
+    // they grow. Panning away turns this off.
+    #[serde(skip)]
+    follow_live: bool,
+    // The view as of the last time it followed the end, and when (in
+    // seconds of egui time) the data sources were last polled
+    #[serde(skip)]
+    live_view: Option<Interval>,
+    #[serde(skip)]
+    last_live_poll: f64,
+
+    // Whether the current drag selects a region (Ctrl+drag) rather than
+    // zooming
+    #[serde(skip)]
+    region_drag: bool,
+
+    // Where a task was right-clicked, and its details, while its menu is open
+    #[serde(skip)]
+    task_menu: Option<(Pos2, String)>,
+
+    // Servers connected to, most recent first, each a comma-separated list
+    // of URLs. The first is the current one, if connected.
+    #[serde(default)]
+    recent_servers: Vec<String>,
+    #[serde(skip)]
+    connected: bool,
+    #[serde(skip)]
+    server_buffer: String,
+    #[serde(skip)]
+    server_error: Option<String>,
+
     debug: bool,
 }
 
+/// What to show on startup, e.g., from a shared link. Encoded as a URL query
+/// string like "start=100ns&stop=200ns&entry=0/1/3", optionally with the
+/// servers to connect to, as in "urls=http://a:8080,http://b:8080".
+#[derive(Debug, Clone, Default)]
+pub struct ViewState {
+    pub interval: Option<Interval>,
+    pub entry_id: Option<EntryID>,
+    // Unvalidated, see http::client::parse_urls
+    pub urls: Option<String>,
+}
+
 #[derive(Default, Deserialize, Serialize)]
 #[serde(default)] // deserialize missing fields as default value
 struct ProfApp {
@@ -143,6 +321,87 @@ struct ProfApp {
     #[cfg(not(target_arch = "wasm32"))]
     #[serde(skip)]
     last_update: Option<Instant>,
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    screenshot_requested: bool,
+
+    // Where the last screenshot was saved, or why it wasn't
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    screenshot_result: Option<Result<std::path::PathBuf, String>>,
+}
+
+const OKABE_ITO: [Color32; 7] = [
+    Color32::from_rgb(0xE6, 0x9F, 0x00), // orange
+    Color32::from_rgb(0x56, 0xB4, 0xE9), // sky blue
+    Color32::from_rgb(0x00, 0x9E, 0x73), // bluish green
+    Color32::from_rgb(0xF0, 0xE4, 0x42), // yellow
+    Color32::from_rgb(0x00, 0x72, 0xB2), // blue
+    Color32::from_rgb(0xD5, 0x5E, 0x00), // vermillion
+    Color32::from_rgb(0xCC, 0x79, 0xA7), // reddish purple
+];
+
+// The OKABE_ITO entry for a color chosen by the data source, which depends
+// only on the color, so that it's the same whatever order colors are seen in
+// (and across runs). Note: distinct colors may share an entry.
+fn okabe_ito(color: Color32) -> Color32 {
+    // FNV rather than the std hasher, which is not guaranteed to be stable
+    // across builds
+    let mut hasher = FnvHasher::default();
+    hasher.write(&color.to_array());
+    OKABE_ITO[(hasher.finish() % OKABE_ITO.len() as u64) as usize]
+}
+
+// Recolor a color chosen by the data source. Data sources use the color to
+// distinguish kinds of items, so the same color always maps to the same
+// result.
+fn color_for(color: Color32, scheme: ColorScheme) -> Color32 {
+    let [r, g, b, _] = color.to_array();
+    match scheme {
+        ColorScheme::Default => color,
+        ColorScheme::ColorblindSafe => okabe_ito(color),
+        ColorScheme::Grayscale => {
+            let luma = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+            Color32::from_gray(luma.round() as u8)
+        }
+    }
+}
+
+// Hatching over an entry whose tiles haven't been fetched yet, so that it
+// doesn't look empty
+fn draw_loading(ui: &egui::Ui, rect: Rect) {
+    const SPACING: f32 = 8.0;
+    let color = ui.visuals().weak_text_color();
+    let painter = ui.painter().with_clip_rect(rect.intersect(ui.clip_rect()));
+    let stroke = Stroke::new(1.0, color.linear_multiply(0.3));
+    let mut x = rect.left() - rect.height();
+    while x < rect.right() {
+        painter.line_segment(
+            [
+                Pos2::new(x, rect.bottom()),
+                Pos2::new(x + rect.height(), rect.top()),
+            ],
+            stroke,
+        );
+        x += SPACING;
+    }
+    painter.text(
+        rect.center(),
+        egui::Align2::CENTER_CENTER,
+        "Loading…",
+        TextStyle::Small.resolve(ui.style()),
+        color,
+    );
+}
+
+// Vertical extent of the items in row index (counting from the top) of a
+// slot with rows row_height tall, relative to the top of the slot. Items
+// leave a gap of 5% of the row above and below. Hovering is tested against
+// the same extent.
+fn entry_y_range(index: u64, row_height: f32) -> (f32, f32) {
+    let top = index as f32 * row_height;
+    (top + 0.05 * row_height, top + 0.95 * row_height)
 }
 
 trait Entry {
@@ -206,19 +465,57 @@ trait Entry {
     fn toggle_expanded(&mut self);
 }
 
+// Whether the tiles fetched for last_view still cover view, i.e., the view
+// only panned within them. Zooming changes which tiles are needed.
+fn still_covered(
+    fetched: Option<Interval>,
+    last_view: Option<Interval>,
+    view: Interval,
+    bounds: Interval,
+) -> bool {
+    let panned = last_view.map_or(false, |last| last.duration_ns() == view.duration_ns());
+    let needed = bounds.intersection(view);
+    panned
+        && match (fetched, needed) {
+            (Some(fetched), Some(needed)) => {
+                fetched.start <= needed.start && needed.stop <= fetched.stop
+            }
+            _ => false,
+        }
+}
+
 impl Summary {
-    fn clear(&mut self) {
-        self.utilization.clear();
+    // Refetch, but keep showing utilization until that's done
+    fn invalidate(&mut self) {
+        self.loaded = false;
+        self.fetched_interval = None;
     }
 
-    fn inflate(&mut self, config: &mut Config, cx: &Context) {
-        let interval = config.interval.intersection(cx.view_interval);
-        let tiles = config.data_source.request_tiles(&self.entry_id, interval);
-        for tile_id in tiles {
-            let tile = config
-                .data_source
-                .fetch_summary_tile(&self.entry_id, tile_id);
-            self.utilization.extend(tile.utilization);
+    // pixels is the width of the plot. Leaves loaded unset while the tiles
+    // are still being fetched.
+    fn inflate(&mut self, config: &mut Config, cx: &Context, pixels: f32) {
+        let interval = match config.interval.intersection(cx.view_interval) {
+            Some(interval) => interval,
+            None => {
+                self.loaded = true;
+                return;
+            }
+        };
+        // No tile needs more than one point per pixel
+        config
+            .data_source
+            .set_summary_samples(pixels.ceil() as usize);
+        if let Some(tiles) = config
+            .data_source
+            .poll_summary_tiles(&self.entry_id, interval)
+        {
+            let tile_ids = tiles.iter().map(|tile| tile.tile_id).collect();
+            self.fetched_interval = Some(snap_to_tiles(interval, &[tile_ids], 0));
+            self.utilization.clear();
+            for tile in tiles {
+                self.utilization.extend(tile.utilization);
+            }
+            self.loaded = true;
         }
     }
 }
@@ -230,6 +527,8 @@ impl Entry for Summary {
                 entry_id,
                 color: *color,
                 utilization: Vec::new(),
+                loaded: false,
+                fetched_interval: None,
                 last_view_interval: None,
             }
         } else {
@@ -260,23 +559,45 @@ impl Entry for Summary {
         const TOOLTIP_RADIUS: f32 = 4.0;
         let response = ui.allocate_rect(rect, egui::Sense::hover());
         let hover_pos = response.hover_pos(); // where is the mouse hovering?
+        let covered = still_covered(
+            self.fetched_interval,
+            self.last_view_interval,
+            cx.view_interval,
+            config.interval,
+        );
         if self
             .last_view_interval
             .map_or(true, |i| i != cx.view_interval)
+            && !covered
         {
-            self.clear();
+            self.invalidate();
         }
         self.last_view_interval = Some(cx.view_interval);
-        if self.utilization.is_empty() {
-            self.inflate(config, cx);
+        if !self.loaded {
+            if !config.fetch_budget_exhausted() {
+                let pixels = rect.width() * ui.ctx().pixels_per_point();
+                self.inflate(config, cx, pixels);
+            }
+            if !self.loaded {
+                config.repaint_when_fetched(ui.ctx());
+                config
+                    .loading
+                    .insert(self.entry_id.clone(), cx.view_interval);
+            }
         }
 
         let style = ui.style();
         let visuals = style.interact_selectable(&response, false);
         ui.painter()
             .rect(rect, 0.0, visuals.bg_fill, visuals.bg_stroke);
+        if !self.loaded && self.utilization.is_empty() && config.is_loading(&self.entry_id) {
+            draw_loading(ui, rect);
+        }
 
-        let stroke = Stroke::new(visuals.bg_stroke.width, self.color);
+        let stroke = Stroke::new(
+            visuals.bg_stroke.width,
+            color_for(self.color, cx.color_scheme),
+        );
 
         // Conversions to and from screen space coordinates
         let util_to_screen = |util: &UtilPoint| {
@@ -373,23 +694,183 @@ impl Slot {
     fn clear(&mut self) {
         self.tiles.clear();
         self.tile_metas.clear();
+        self.merged = false;
+        self.invalidate();
     }
 
-    fn inflate(&mut self, config: &mut Config, cx: &Context) {
-        let interval = config.interval.intersection(cx.view_interval);
-        let tiles = config.data_source.request_tiles(&self.entry_id, interval);
-        for tile_id in tiles {
-            let tile = config.data_source.fetch_slot_tile(&self.entry_id, tile_id);
+    // Refetch, but keep showing tiles until that's done
+    fn invalidate(&mut self) {
+        self.loaded = false;
+    }
+
+    // Just the part of the view, and a bit beyond so that panning a little
+    // needn't refetch, is fetched
+    fn fetch_interval(config: &Config, cx: &Context) -> Option<Interval> {
+        let margin = Timestamp(cx.view_interval.duration_ns() / 4);
+        let wanted = Interval::new(
+            cx.view_interval.start - margin,
+            cx.view_interval.stop + margin,
+        );
+        config.interval.intersection(wanted)
+    }
+
+    // Items narrower than this are merged, unless we're about to jump to a
+    // selected item (which might be merged away)
+    fn min_item_ns(cx: &Context) -> i64 {
+        match cx.slot_rect {
+            Some(rect) if cx.selected_state.selected.is_none() && rect.width() > 0.0 => {
+                (cx.view_interval.duration_ns() as f32 / rect.width()) as i64
+            }
+            _ => 0,
+        }
+    }
+
+    // Sanitizes and merges the items of tile, which needs metadata (from
+    // fetch_meta) if there's anything to fix. Returns None if fetch_meta
+    // does.
+    fn prepare_tile(
+        mut tile: SlotTile,
+        min_ns: i64,
+        mut fetch_meta: impl FnMut(TileID) -> Option<SlotMetaTile>,
+    ) -> Option<(SlotTile, Option<SlotMetaTile>, bool)> {
+        let tile_id = tile.tile_id;
+        let mut meta_tile = None;
+        let mut merged = false;
+
+        let has_inverted = tile
+            .items
+            .iter()
+            .flatten()
+            .any(|item| item.interval.start > item.interval.stop);
+        if has_inverted {
+            // Items are matched up with their metadata by position, so
+            // the metadata of dropped items must go too
+            let mut metas = fetch_meta(tile_id)?;
+            for (row, meta_row) in tile.items.iter_mut().zip(metas.items.iter_mut()) {
+                *row = sanitize_items(std::mem::take(row));
+                let mut kept = row.iter().map(|item| item.item_uid).peekable();
+                meta_row.retain(|meta| kept.next_if_eq(&meta.item_uid).is_some());
+            }
+            meta_tile = Some(metas);
+        }
+
+        let has_small = tile
+            .items
+            .iter()
+            .flatten()
+            .any(|item| item.interval.duration_ns() < min_ns);
+        if has_small {
+            // Merged items need merged metadata, so fetch it now
+            let mut metas = match meta_tile.take() {
+                Some(metas) => metas,
+                None => fetch_meta(tile_id)?,
+            };
+            for (row, meta_row) in tile.items.iter_mut().zip(metas.items.iter_mut()) {
+                let (items, metas) = merge_small_items(row, meta_row, min_ns);
+                *row = items;
+                *meta_row = metas;
+            }
+            meta_tile = Some(metas);
+            merged = true;
+        }
+
+        Some((tile, meta_tile, merged))
+    }
+
+    // Replaces the tiles with those fetched clipped to interval
+    fn add_tiles(
+        &mut self,
+        interval: Interval,
+        tiles: Vec<(SlotTile, Option<SlotMetaTile>, bool)>,
+    ) {
+        self.fetched_interval = Some(interval);
+        self.tiles.clear();
+        self.tile_metas.clear();
+        self.merged = false;
+        for (tile, meta_tile, merged) in tiles {
+            if let Some(meta_tile) = meta_tile {
+                self.tile_metas.insert(tile.tile_id, meta_tile);
+            }
+            self.merged |= merged;
             self.tiles.push(tile);
         }
+        self.loaded = true;
+    }
+
+    // Leaves loaded unset while the tiles are still being fetched, in which
+    // case nothing is added until they all are
+    fn inflate(&mut self, config: &mut Config, cx: &Context) {
+        let interval = match Self::fetch_interval(config, cx) {
+            Some(interval) => interval,
+            None => {
+                self.loaded = true;
+                return;
+            }
+        };
+        let min_ns = Self::min_item_ns(cx);
+
+        let tiles = match config.data_source.poll_slot_tiles(&self.entry_id, interval) {
+            Some(tiles) => tiles,
+            None => return,
+        };
+        let mut prepared = Vec::new();
+        let mut ready = true;
+        for tile in tiles {
+            // Keep going when a tile isn't ready, so that the fetches of the
+            // rest get started too
+            let data_source = &mut config.data_source;
+            match Self::prepare_tile(tile, min_ns, |tile_id| {
+                data_source.poll_slot_meta_tile(&self.entry_id, tile_id, interval)
+            }) {
+                Some(tile) => prepared.push(tile),
+                None => ready = false,
+            }
+        }
+        if ready {
+            self.add_tiles(interval, prepared);
+        }
+    }
+
+    // Like inflate, but waits for the tiles
+    fn inflate_blocking(&mut self, config: &mut Config, cx: &Context) {
+        let interval = match Self::fetch_interval(config, cx) {
+            Some(interval) => interval,
+            None => {
+                self.loaded = true;
+                return;
+            }
+        };
+        let min_ns = Self::min_item_ns(cx);
+
+        let mut prepared = Vec::new();
+        for tile_id in config.data_source.request_tiles(&self.entry_id, interval) {
+            let tile =
+                config
+                    .data_source
+                    .fetch_clipped_slot_tile(&self.entry_id, tile_id, interval);
+            let data_source = &mut config.data_source;
+            let tile = Self::prepare_tile(tile, min_ns, |tile_id| {
+                Some(data_source.fetch_clipped_slot_meta_tile(&self.entry_id, tile_id, interval))
+            });
+            prepared.extend(tile);
+        }
+        self.add_tiles(interval, prepared);
     }
 
-    fn fetch_meta_tile(&mut self, tile_id: TileID, config: &mut Config) -> &mut SlotMetaTile {
-        self.tile_metas.entry(tile_id).or_insert_with(|| {
-            config
+    // Returns None while the tile is still being fetched
+    fn fetch_meta_tile(
+        &mut self,
+        tile_id: TileID,
+        config: &mut Config,
+    ) -> Option<&mut SlotMetaTile> {
+        if !self.tile_metas.contains_key(&tile_id) {
+            let clip = self.fetched_interval?;
+            let tile = config
                 .data_source
-                .fetch_slot_meta_tile(&self.entry_id, tile_id)
-        })
+                .poll_slot_meta_tile(&self.entry_id, tile_id, clip)?;
+            self.tile_metas.insert(tile_id, tile);
+        }
+        self.tile_metas.get_mut(&tile_id)
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -424,8 +905,9 @@ impl Slot {
             // entire row if we don't need it
 
             // Compute bounds for the whole row
-            let row_min = rect.lerp(Vec2::new(0.0, (irow as f32 + 0.05) / rows as f32));
-            let row_max = rect.lerp(Vec2::new(1.0, (irow as f32 + 0.95) / rows as f32));
+            let (row_top, row_bottom) = entry_y_range(irow, rect.height() / rows as f32);
+            let row_min = Pos2::new(rect.min.x, rect.min.y + row_top);
+            let row_max = Pos2::new(rect.max.x, rect.min.y + row_bottom);
 
             // Cull if out of bounds
             // Note: need to shift by rect.min to get to viewport space
@@ -452,10 +934,11 @@ impl Slot {
                 // is the BEGINNING of the interval.stop nanosecond.
                 let start = cx.view_interval.unlerp(item.interval.start).at_least(0.0);
                 let stop = cx.view_interval.unlerp(item.interval.stop).at_most(1.0);
-                let min = rect.lerp(Vec2::new(start, (irow as f32 + 0.05) / rows as f32));
-                let max = rect.lerp(Vec2::new(stop, (irow as f32 + 0.95) / rows as f32));
+                let min = Pos2::new(rect.lerp(Vec2::new(start, 0.0)).x, row_min.y);
+                let max = Pos2::new(rect.lerp(Vec2::new(stop, 0.0)).x, row_max.y);
 
                 let item_rect = Rect::from_min_max(min, max);
+                let item_color = color_for(item.color, cx.color_scheme);
 
                 if cx.selected_state.selected.is_some()
                     && cx.selected_state.selected.as_ref().unwrap().item_uid == item.item_uid
@@ -468,7 +951,7 @@ impl Slot {
 
                 if row_hover && hover_pos.map_or(false, |h| item_rect.contains(h)) {
                     hover_pos = None;
-                    interact_item = Some((row, item_idx, item_rect, tile_id));
+                    interact_item = Some((row, item_idx, item_rect, tile_id, item.interval));
 
                     let index = if cx
                         .selected_state
@@ -483,7 +966,7 @@ impl Slot {
                     };
                     if index.is_some() {
                         if clicked {
-                            ui.painter().rect(item_rect, 0.0, item.color, Stroke::NONE);
+                            ui.painter().rect(item_rect, 0.0, item_color, Stroke::NONE);
                             cx.selected_state
                                 .highlighted_items
                                 .get_mut(&self.entry_id)
@@ -493,7 +976,7 @@ impl Slot {
                             ui.painter().rect(
                                 item_rect,
                                 0.0,
-                                item.color,
+                                item_color,
                                 Stroke::new(2.0, Color32::WHITE),
                             );
                         }
@@ -501,11 +984,19 @@ impl Slot {
                         let selected_item = SelectedItem {
                             entry_id: self.entry_id.clone(),
                             tile_id,
-                            meta: config
-                                .data_source
-                                .fetch_slot_meta_tile(&self.entry_id.clone(), tile_id)
-                                .items[row][item_idx]
-                                .clone(), // inefficient, but necessary to pick a single item's metadata
+                            // Merged tiles always have their metadata cached
+                            meta: match self.tile_metas.get(&tile_id) {
+                                Some(meta_tile) => meta_tile.items[row][item_idx].clone(),
+                                None => config
+                                    .data_source
+                                    .fetch_clipped_slot_meta_tile(
+                                        &self.entry_id.clone(),
+                                        tile_id,
+                                        self.fetched_interval.unwrap(),
+                                    )
+                                    .items[row][item_idx]
+                                    .clone(), // inefficient, but necessary to pick a single item's metadata
+                            },
                             row,
                             item_uid: item.item_uid,
                             index: item_idx,
@@ -514,11 +1005,11 @@ impl Slot {
                         ui.painter().rect(
                             item_rect,
                             0.0,
-                            item.color,
+                            item_color,
                             Stroke::new(2.0, Color32::WHITE),
                         );
                     } else {
-                        ui.painter().rect(item_rect, 0.0, item.color, Stroke::NONE);
+                        ui.painter().rect(item_rect, 0.0, item_color, Stroke::NONE);
                     }
                 } else if cx
                     .selected_state
@@ -532,21 +1023,40 @@ impl Slot {
                         ui.painter().rect(
                             item_rect,
                             0.0,
-                            item.color,
+                            item_color,
                             Stroke::new(2.0, Color32::WHITE),
                         );
                     } else {
-                        ui.painter().rect(item_rect, 0.0, item.color, Stroke::NONE);
+                        ui.painter().rect(item_rect, 0.0, item_color, Stroke::NONE);
                     }
                 } else {
-                    ui.painter().rect(item_rect, 0.0, item.color, Stroke::NONE);
+                    ui.painter().rect(item_rect, 0.0, item_color, Stroke::NONE);
                 }
             }
         }
 
-        if let Some((row, item_idx, item_rect, tile_id)) = interact_item {
-            let tile_meta = self.fetch_meta_tile(tile_id, config);
+        if let Some((row, item_idx, item_rect, tile_id, interval)) = interact_item {
+            let entry_id = self.entry_id.clone();
+            let tile_meta = match self.fetch_meta_tile(tile_id, config) {
+                Some(tile_meta) => tile_meta,
+                None => {
+                    config.repaint_when_fetched(ui.ctx());
+                    ui.show_tooltip_ui("task_tooltip", &item_rect, |ui| {
+                        ui.label("Loading…");
+                    });
+                    return hover_pos;
+                }
+            };
             let item_meta = &tile_meta.items[row][item_idx];
+            if let Some(pos) = ui.input(|i| {
+                i.pointer
+                    .secondary_clicked()
+                    .then(|| i.pointer.interact_pos())
+                    .flatten()
+            }) {
+                let details = format_task_details(item_meta, interval, &entry_id);
+                cx.task_menu = Some((pos, details));
+            }
             ui.show_tooltip_ui("task_tooltip", &item_rect, |ui| {
                 ui.label(&item_meta.title);
                 if cx.debug {
@@ -564,7 +1074,7 @@ impl Slot {
                             ui.label(format!("{name}: {value}"));
                         }
                         Field::Interval(value) => {
-                            ui.label(format!("{name}: {value}"));
+                            ui.label(format!("{name}: {}", value.format(&cx.display_config)));
                         }
                         Field::Empty => {
                             ui.label(name);
@@ -584,17 +1094,22 @@ impl Entry for Slot {
             short_name,
             long_name,
             max_rows,
+            kind,
         } = info
         {
             Self {
                 entry_id,
                 short_name: short_name.to_owned(),
                 long_name: long_name.to_owned(),
+                kind: *kind,
                 expanded: true,
                 max_rows: *max_rows,
                 tiles: Vec::new(),
                 tile_metas: BTreeMap::new(),
+                loaded: false,
+                fetched_interval: None,
                 last_view_interval: None,
+                merged: false,
             }
         } else {
             unreachable!()
@@ -622,6 +1137,10 @@ impl Entry for Slot {
         cx.slot_rect = Some(rect); // Save slot rect for use later
 
         let response = ui.allocate_rect(rect, egui::Sense::hover());
+        if response.hovered() {
+            config.hovered_slot = Some(self.entry_id.clone());
+        }
+        config.slot_rects.push((self.entry_id.clone(), rect));
 
         let mut clicked = false;
 
@@ -635,24 +1154,52 @@ impl Entry for Slot {
         if cx.selected_state.selected.is_some()
             && cx.selected_state.selected.clone().unwrap().entry_id == self.entry_id
         {
-            self.expanded = true
+            self.expanded = true;
+            // The selected item might have been merged away
+            if self.merged {
+                self.clear();
+            }
         }
         if self.expanded {
+            let covered = still_covered(
+                self.fetched_interval,
+                self.last_view_interval,
+                cx.view_interval,
+                config.interval,
+            );
             if self
                 .last_view_interval
                 .map_or(true, |i| i != cx.view_interval)
+                && !covered
             {
-                self.clear();
+                self.invalidate();
             }
             self.last_view_interval = Some(cx.view_interval);
-            if self.tiles.is_empty() {
-                self.inflate(config, cx);
+            if !self.loaded {
+                if !config.fetch_budget_exhausted() {
+                    self.inflate(config, cx);
+                }
+                if !self.loaded {
+                    config.repaint_when_fetched(ui.ctx());
+                    config
+                        .loading
+                        .insert(self.entry_id.clone(), cx.view_interval);
+                }
             }
 
             let style = ui.style();
             let visuals = style.interact_selectable(&response, false);
-            ui.painter()
-                .rect(rect, 0.0, visuals.bg_fill, visuals.bg_stroke);
+            // Shade memories and channels so they stand apart from processors
+            let fill = match self.kind {
+                _ if response.hovered() => visuals.bg_fill,
+                EntryKind::Memory => style.visuals.faint_bg_color,
+                EntryKind::Channel => style.visuals.extreme_bg_color,
+                EntryKind::Processor | EntryKind::Other => visuals.bg_fill,
+            };
+            ui.painter().rect(rect, 0.0, fill, visuals.bg_stroke);
+            if !self.loaded && self.tiles.is_empty() && config.is_loading(&self.entry_id) {
+                draw_loading(ui, rect);
+            }
 
             let rows = self.rows();
             for tile_index in 0..self.tiles.len() {
@@ -751,10 +1298,17 @@ impl<S: Entry> Entry for Panel<S> {
                 .enumerate()
                 .map(|(i, s)| S::new(s, entry_id.child(i as u64)))
                 .collect();
+            let hover_text = match info.kind() {
+                Some(kind) if kind != EntryKind::Other => {
+                    format!("{long_name} ({})", kind.plural_name())
+                }
+                _ => long_name.to_owned(),
+            };
             Self {
                 entry_id,
                 short_name: short_name.to_owned(),
                 long_name: long_name.to_owned(),
+                hover_text,
                 expanded,
                 summary,
                 slots,
@@ -771,7 +1325,7 @@ impl<S: Entry> Entry for Panel<S> {
         &self.short_name
     }
     fn hover_text(&self) -> &str {
-        &self.long_name
+        &self.hover_text
     }
 
     fn content(
@@ -854,7 +1408,108 @@ impl Config {
             min_node: 0,
             max_node,
             interval: data_source.interval(),
+            time_origin: data_source.time_origin(),
             data_source,
+            errors: Vec::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            fetch_deadline: None,
+            hovered_slot: None,
+            slot_rects: Vec::new(),
+            loading: BTreeMap::new(),
+            view_interval: None,
+            repaints_on_fetch: None,
+        }
+    }
+
+    // Statistics of the items in entries (all slots) overlapping interval
+    fn slot_stats(&mut self, entries: &[EntryID], interval: Interval) -> IntervalStats {
+        let data_source = &mut self.data_source;
+        let mut items = Vec::new();
+        for entry_id in entries {
+            // Items cut at tile boundaries are joined back together so
+            // that they're counted once, at their full duration
+            let mut slot_items: BTreeMap<ItemUID, Item> = BTreeMap::new();
+            for tile_id in data_source.request_tiles(entry_id, interval) {
+                let tile = data_source.fetch_slot_tile(entry_id, tile_id);
+                for item in tile.items.into_iter().flatten() {
+                    slot_items
+                        .entry(item.item_uid)
+                        .and_modify(|i| i.interval = i.interval.union(item.interval))
+                        .or_insert(item);
+                }
+            }
+            items.extend(slot_items.into_values());
+        }
+        compute_stats(&items, interval)
+    }
+
+    fn start_frame(&mut self) {
+        self.hovered_slot = None;
+        self.slot_rects.clear();
+        self.loading.clear();
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            const FETCH_BUDGET: std::time::Duration = std::time::Duration::from_millis(10);
+            self.fetch_deadline = Some(Instant::now() + FETCH_BUDGET);
+        }
+    }
+
+    // Fetches for a view the user has left would otherwise hold up those for
+    // the current view
+    fn set_view_interval(&mut self, view_interval: Interval) {
+        if self.view_interval != Some(view_interval) {
+            self.view_interval = Some(view_interval);
+            self.data_source.cancel_queued();
+        }
+    }
+
+    fn watch_fetches(&mut self, ctx: &egui::Context) {
+        if self.repaints_on_fetch.is_none() {
+            let ctx = ctx.clone();
+            let repaint = Box::new(move || ctx.request_repaint());
+            self.repaints_on_fetch = Some(self.data_source.notify_on_fetch(repaint));
+        }
+    }
+
+    // For entries drawn while their tiles are still being fetched
+    fn repaint_when_fetched(&self, ctx: &egui::Context) {
+        // Nothing gets fetched for entries the budget ran out for, so there
+        // is nothing to wait on
+        if self.repaints_on_fetch != Some(true) || self.fetch_budget_exhausted() {
+            ctx.request_repaint();
+        }
+    }
+
+    fn is_loading(&self, entry_id: &EntryID) -> bool {
+        self.loading.contains_key(entry_id)
+    }
+
+    fn fetch_budget_exhausted(&self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.fetch_deadline
+                .map_or(false, |deadline| Instant::now() >= deadline)
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            false
+        }
+    }
+
+    fn poll_errors(&mut self) {
+        const MAX_ERRORS: usize = 100;
+        self.errors.extend(self.data_source.get_errors());
+        if self.errors.len() > MAX_ERRORS {
+            self.errors.drain(..self.errors.len() - MAX_ERRORS);
+        }
+    }
+}
+
+impl Default for NavConfig {
+    fn default() -> Self {
+        Self {
+            pan_step: 0.1,
+            zoom_factor: 2.0,
         }
     }
 }
@@ -868,15 +1523,68 @@ impl Window {
             index,
             kinds: config.data_source.fetch_info().kinds(),
             config,
+            panic_message: None,
+            stats: None,
+            region: None,
+        }
+    }
+
+    // Entries still waiting for tiles as of the last frame, and the interval
+    // those tiles cover
+    pub fn loading_regions(&self) -> Vec<(EntryID, Interval)> {
+        self.config
+            .loading
+            .iter()
+            .map(|(entry_id, interval)| (entry_id.clone(), *interval))
+            .collect()
+    }
+
+    // Like content, but a panic (e.g., in the data source) only takes down
+    // this window. Note: on the web panics abort, so this can't help there.
+    fn guarded_content(&mut self, ui: &mut egui::Ui, cx: &mut Context) {
+        if let Some(message) = &self.panic_message {
+            ui.colored_label(
+                ui.visuals().error_fg_color,
+                format!("Profile {} stopped: {message}", self.index),
+            );
+            if ui.button("Retry").clicked() {
+                self.panic_message = None;
+            }
+            return;
+        }
+
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| self.content(ui, cx)));
+        if let Err(payload) = result {
+            let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                message.to_string()
+            } else if let Some(message) = payload.downcast_ref::<String>() {
+                message.clone()
+            } else {
+                "unknown panic".to_owned()
+            };
+            self.panic_message = Some(message);
         }
     }
 
     fn content(&mut self, ui: &mut egui::Ui, cx: &mut Context) {
+        self.config.start_frame();
+        self.config.set_view_interval(cx.view_interval);
+        self.config.watch_fetches(ui.ctx());
+        self.config.poll_errors();
+
         ui.horizontal(|ui| {
             ui.heading(format!("Profile {}", self.index));
-            ui.label(cx.view_interval.to_string())
+            ui.label(cx.view_interval.format(&cx.display_config));
+            ui.weak(format!("0 = {}", self.config.time_origin));
         });
 
+        // E.g., a profile of a single point in time: there is nothing to
+        // draw, and nothing to pan or zoom to
+        if self.config.interval.duration_ns() == 0 {
+            ui.centered_and_justified(|ui| ui.label("Empty profile"));
+            return;
+        }
+
         ScrollArea::vertical()
             .auto_shrink([false; 2])
             .show_viewport(ui, |ui, viewport| {
@@ -957,21 +1665,16 @@ impl Window {
         {
             match Interval::convert_str_to_timestamp(&cx.view_interval_start_buffer) {
                 Ok(start) => {
-                    // validate timestamp
-                    if start > cx.view_interval.stop {
-                        cx.view_interval_start_buffer = "Start must be before stop".to_string();
-                        return;
-                    }
-                    if start > cx.total_interval.stop {
-                        cx.view_interval_start_buffer =
-                            "Start must be before end of trace".to_string();
+                    let interval = Interval::new(start, cx.view_interval.stop);
+                    if let Err(e) = interval.validate_against(cx.total_interval) {
+                        cx.view_interval_start_buffer = e.to_string();
                         return;
                     }
                     cx.view_interval.start = start;
                     ProfApp::zoom(cx, cx.view_interval);
                 }
                 Err(e) => {
-                    if e.to_string() == "no value" {
+                    if e == TimestampParseError::NoValue {
                         cx.view_interval_start_buffer = cx.view_interval.start.to_string();
                         return;
                     }
@@ -984,9 +1687,9 @@ impl Window {
         {
             match Interval::convert_str_to_timestamp(&cx.view_interval_stop_buffer) {
                 Ok(stop) => {
-                    // validate timestamp
-                    if stop < cx.view_interval.start {
-                        cx.view_interval_stop_buffer = "Stop must be after start".to_string();
+                    let interval = Interval::new(cx.view_interval.start, stop);
+                    if let Err(e) = interval.validate_against(cx.total_interval) {
+                        cx.view_interval_stop_buffer = e.to_string();
                         return;
                     }
 
@@ -995,7 +1698,7 @@ impl Window {
                     ProfApp::zoom(cx, cx.view_interval);
                 }
                 Err(e) => {
-                    if e.to_string() == "no value" {
+                    if e == TimestampParseError::NoValue {
                         cx.view_interval_stop_buffer = cx.view_interval.stop.to_string();
                         return;
                     }
@@ -1008,6 +1711,29 @@ impl Window {
     fn controls(&mut self, ui: &mut egui::Ui, cx: &mut Context) {
         const WIDGET_PADDING: f32 = 8.0;
         ui.heading(format!("Profile {}: Controls", self.index));
+        if let Some(error) = self.config.data_source.last_error() {
+            ui.colored_label(ui.visuals().error_fg_color, format!("Error: {error}"));
+        }
+        let loading = self.loading_regions();
+        if !loading.is_empty() {
+            ui.weak(format!("Loading… ({} pending)", loading.len()));
+        }
+        if !self.config.errors.is_empty() {
+            let errors = &mut self.config.errors;
+            ui.collapsing(format!("{} failed tile fetches", errors.len()), |ui| {
+                for error in errors.iter().rev() {
+                    ui.label(format!(
+                        "{} {}: {}",
+                        error.entry_id,
+                        error.tile_id.0.format(&cx.display_config),
+                        error.message
+                    ));
+                }
+                if ui.button("Clear").clicked() {
+                    errors.clear();
+                }
+            });
+        }
         ui.add_space(WIDGET_PADDING);
         self.node_selection(ui, cx);
         ui.add_space(WIDGET_PADDING);
@@ -1015,44 +1741,224 @@ impl Window {
         ui.add_space(WIDGET_PADDING);
         self.modify_interval(ui, cx);
         ui.add_space(WIDGET_PADDING);
+        if cx.measurement.is_some() {
+            self.selection_stats(ui, cx);
+            ui.add_space(WIDGET_PADDING);
+        }
+        if self.region.is_some() {
+            self.region_stats(ui, cx);
+            ui.add_space(WIDGET_PADDING);
+        }
+        ui.horizontal(|ui| {
+            let zoom_state = &cx.zoom_state;
+            let can_undo = zoom_state.index > 0;
+            let can_redo = zoom_state.index + 1 < zoom_state.levels.len();
+            if ui
+                .add_enabled(can_undo, egui::Button::new("Back"))
+                .clicked()
+            {
+                ProfApp::undo_zoom(cx);
+            }
+            if ui
+                .add_enabled(can_redo, egui::Button::new("Forward"))
+                .clicked()
+            {
+                ProfApp::redo_zoom(cx);
+            }
+        });
         if ui.button("Reset Zoom Level").clicked() {
-            ProfApp::zoom(cx, cx.total_interval);
+            ProfApp::zoom_to_fit(cx);
+        }
+        if ui.button("Copy Link to View").clicked() {
+            let query = ProfApp::view_state(cx).to_query();
+            ui.output_mut(|o| o.copied_text = query);
         }
     }
-}
-
-impl ProfApp {
-    /// Called once before the first frame.
-    pub fn new(
-        cc: &eframe::CreationContext<'_>,
-        data_source: Box<dyn DataSource>,
-        extra_source: Option<Box<dyn DataSource>>,
-    ) -> Self {
-        // This is also where you can customized the look at feel of egui using
-        // `cc.egui_ctx.set_visuals` and `cc.egui_ctx.set_fonts`.
 
-        // Load previous app state (if any).
-        // Note that you must enable the `persistence` feature for this to work.
-        let mut result: Self = if let Some(storage) = cc.storage {
-            eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default()
-        } else {
-            Default::default()
-        };
+    fn selection_stats(&mut self, ui: &mut egui::Ui, cx: &Context) {
+        let measurement = cx.measurement.unwrap();
+        let mut entries = Vec::new();
+        for node in &self.panel.slots {
+            if !node.expanded
+                || !Panel::<Panel<Slot>>::is_slot_visible(&node.entry_id, &self.config)
+            {
+                continue;
+            }
+            for kind in node.slots.iter().filter(|kind| kind.expanded) {
+                for slot in kind.slots.iter().filter(|slot| slot.expanded) {
+                    entries.push(slot.entry_id.clone());
+                }
+            }
+        }
 
-        result.windows.clear();
-        result.windows.push(Window::new(data_source, 0));
-        let window = result.windows.last().unwrap();
-        result.cx.total_interval = window.config.interval;
-        result.extra_source = extra_source;
-        Self::zoom(&mut result.cx, window.config.interval);
+        let stale = self.stats.as_ref().map_or(true, |(interval, ids, _)| {
+            *interval != measurement || *ids != entries
+        });
+        if stale {
+            let stats = self.config.slot_stats(&entries, measurement);
+            self.stats = Some((measurement, entries, stats));
+        }
 
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            result.last_update = Some(Instant::now());
+        let (_, entries, stats) = self.stats.as_ref().unwrap();
+        ui.subheading("Selection", cx);
+        ui.label(format!(
+            "{} tasks in {} expanded slots",
+            stats.count,
+            entries.len()
+        ));
+        if stats.count > 0 {
+            ui.label(format!("Busy: {}", stats.busy));
+            ui.label(format!("Mean: {}", stats.mean));
+            ui.label(format!("p50: {}  p95: {}", stats.p50, stats.p95));
+            ui.label(format!("Max: {}", stats.max));
         }
+    }
 
-        let theme = if result.cx.toggle_dark_mode {
-            egui::Visuals::dark()
+    fn selection_region(&self) -> Option<(Vec<EntryID>, Interval)> {
+        self.region
+            .as_ref()
+            .map(|(entries, interval, _)| (entries.clone(), *interval))
+    }
+
+    // Replaces the region with the slots under rect (in screen space) over
+    // the times it spans, or clears it if rect misses every slot
+    fn select_region(&mut self, rect: Rect, timeline: Rect, view: Interval) {
+        self.region = ProfApp::region_of(&self.config.slot_rects, rect, timeline, view).map(
+            |(entries, interval)| {
+                let stats = self.config.slot_stats(&entries, interval);
+                (entries, interval, stats)
+            },
+        );
+    }
+
+    fn region_stats(&mut self, ui: &mut egui::Ui, cx: &Context) {
+        let (entries, interval, stats) = self.region.as_ref().unwrap();
+        ui.subheading("Region", cx);
+        ui.label(interval.format(&cx.display_config));
+        ui.label(format!("{} tasks in {} slots", stats.count, entries.len()));
+        if stats.count > 0 {
+            ui.label(format!("Busy: {}", stats.busy));
+            ui.label(format!("Mean: {}", stats.mean));
+            ui.label(format!("p50: {}  p95: {}", stats.p50, stats.p95));
+            ui.label(format!("Max: {}", stats.max));
+        }
+        if ui.button("Clear Region").clicked() {
+            self.region = None;
+        }
+    }
+
+    // Expand every panel on the way to entry_id so that it is visible
+    fn expand_to(&mut self, entry_id: &EntryID) {
+        let node = entry_id
+            .slot_index(0)
+            .and_then(|i| self.panel.slots.get_mut(i as usize));
+        if let Some(node) = node {
+            node.expanded = true;
+            let kind = entry_id
+                .slot_index(1)
+                .and_then(|i| node.slots.get_mut(i as usize));
+            if let Some(kind) = kind {
+                kind.expanded = true;
+                let slot = entry_id
+                    .slot_index(2)
+                    .and_then(|i| kind.slots.get_mut(i as usize));
+                if let Some(slot) = slot {
+                    slot.expanded = true;
+                }
+            }
+        }
+    }
+}
+
+impl ViewState {
+    // Unknown keys and values that fail to parse are ignored
+    pub fn from_query(query: &str) -> Self {
+        let mut start = None;
+        let mut stop = None;
+        let mut entry_id = None;
+        let mut urls = None;
+        for pair in query.trim_start_matches('?').split('&') {
+            let (key, value) = match pair.split_once('=') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            match key {
+                "start" => start = Interval::convert_str_to_timestamp(value).ok(),
+                "stop" => stop = Interval::convert_str_to_timestamp(value).ok(),
+                "entry" => entry_id = value.parse().ok(),
+                "urls" => urls = Some(value.to_owned()),
+                _ => {}
+            }
+        }
+        Self {
+            interval: start
+                .zip(stop)
+                .map(|(start, stop)| Interval::new(start, stop)),
+            entry_id,
+            urls,
+        }
+    }
+
+    pub fn to_query(&self) -> String {
+        let mut pairs = Vec::new();
+        if let Some(interval) = self.interval {
+            // Exact, and free of characters that would need escaping
+            pairs.push(format!("start={}ns", interval.start.0));
+            pairs.push(format!("stop={}ns", interval.stop.0));
+        }
+        if let Some(entry_id) = &self.entry_id {
+            pairs.push(format!("entry={entry_id}"));
+        }
+        if let Some(urls) = &self.urls {
+            pairs.push(format!("urls={urls}"));
+        }
+        pairs.join("&")
+    }
+}
+
+impl ProfApp {
+    /// Called once before the first frame.
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        data_source: Box<dyn DataSource>,
+        extra_source: Option<Box<dyn DataSource>>,
+        view: ViewState,
+    ) -> Self {
+        // This is also where you can customized the look at feel of egui using
+        // `cc.egui_ctx.set_visuals` and `cc.egui_ctx.set_fonts`.
+
+        // Load previous app state (if any).
+        // Note that you must enable the `persistence` feature for this to work.
+        let mut result: Self = if let Some(storage) = cc.storage {
+            eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default()
+        } else {
+            Default::default()
+        };
+
+        result.windows.clear();
+        result.windows.push(Window::new(data_source, 0));
+        let window = result.windows.last().unwrap();
+        result.cx.total_interval = window.config.interval;
+        result.extra_source = extra_source;
+        Self::zoom(&mut result.cx, window.config.interval);
+        if let Some(interval) = view.interval {
+            Self::zoom_to(&mut result.cx, interval);
+        }
+        if let Some(entry_id) = &view.entry_id {
+            result.windows.last_mut().unwrap().expand_to(entry_id);
+        }
+        // The caller connected to these, if any
+        if let Some(urls) = view.urls.as_deref().and_then(|urls| parse_urls(urls).ok()) {
+            Self::remember_server(&mut result.cx, &urls);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            result.last_update = Some(Instant::now());
+        }
+
+        let theme = if result.cx.toggle_dark_mode {
+            egui::Visuals::dark()
         } else {
             egui::Visuals::light()
         };
@@ -1066,15 +1972,318 @@ impl ProfApp {
             return;
         }
 
+        // Going to where redo would go keeps the rest of the redo history
+        if cx.zoom_state.levels.get(cx.zoom_state.index + 1) == Some(&interval) {
+            ProfApp::redo_zoom(cx);
+            return;
+        }
+
         cx.view_interval = interval;
         cx.view_interval_start_buffer = cx.view_interval.start.to_string();
         cx.view_interval_stop_buffer = cx.view_interval.stop.to_string();
         cx.zoom_state.levels.truncate(cx.zoom_state.index + 1);
         cx.zoom_state.levels.push(cx.view_interval);
+        if cx.zoom_state.levels.len() > MAX_ZOOM_LEVELS {
+            let excess = cx.zoom_state.levels.len() - MAX_ZOOM_LEVELS;
+            cx.zoom_state.levels.drain(..excess);
+        }
         cx.zoom_state.index = cx.zoom_state.levels.len() - 1;
         cx.zoom_state.zoom_count = 0;
     }
 
+    fn follow_live(ctx: &egui::Context, windows: &mut [Window], cx: &mut Context) {
+        const POLL_INTERVAL: f64 = 1.0; // seconds
+
+        if !cx.follow_live {
+            cx.live_view = None;
+            return;
+        }
+        // Zooming keeps following (at the new width), panning doesn't
+        if let Some(live_view) = cx.live_view {
+            if cx.view_interval != live_view
+                && cx.view_interval.duration_ns() == live_view.duration_ns()
+            {
+                cx.follow_live = false;
+                cx.live_view = None;
+                return;
+            }
+        }
+
+        let now = ctx.input(|i| i.time);
+        if cx.live_view.is_none() || now - cx.last_live_poll >= POLL_INTERVAL {
+            cx.last_live_poll = now;
+            for window in windows.iter_mut() {
+                window.config.interval = window.config.data_source.interval();
+                cx.total_interval = cx.total_interval.union(window.config.interval);
+            }
+        }
+
+        // Not a zoom, so that following doesn't flood the undo history
+        let view = ProfApp::follow_interval(cx.view_interval, cx.total_interval);
+        if view != cx.view_interval {
+            cx.view_interval = view;
+            cx.view_interval_start_buffer = view.start.to_string();
+            cx.view_interval_stop_buffer = view.stop.to_string();
+        }
+        cx.live_view = Some(view);
+        ctx.request_repaint_after(std::time::Duration::from_secs_f64(POLL_INTERVAL));
+    }
+
+    // The interval as wide as view that ends at the end of total, without
+    // starting before total does
+    fn follow_interval(view: Interval, total: Interval) -> Interval {
+        let width = Timestamp(view.duration_ns());
+        let start = (total.stop - width).max(total.start);
+        Interval::new(start, start + width)
+    }
+
+    fn view_state(cx: &Context) -> ViewState {
+        ViewState {
+            interval: Some(cx.view_interval),
+            entry_id: cx
+                .selected_state
+                .selected
+                .as_ref()
+                .map(|item| item.entry_id.clone()),
+            urls: cx.recent_servers.first().filter(|_| cx.connected).cloned(),
+        }
+    }
+
+    fn add_bookmark(cx: &mut Context, name: String, interval: Interval) {
+        cx.bookmarks.push(Bookmark { name, interval });
+    }
+
+    fn remove_bookmark(cx: &mut Context, index: usize) {
+        if index < cx.bookmarks.len() {
+            cx.bookmarks.remove(index);
+        }
+    }
+
+    fn goto_bookmark(cx: &mut Context, index: usize) {
+        if let Some(bookmark) = cx.bookmarks.get(index) {
+            ProfApp::zoom_to(cx, bookmark.interval);
+        }
+    }
+
+    fn bookmarks(ui: &mut egui::Ui, cx: &mut Context) {
+        ui.heading("Bookmarks");
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut cx.bookmark_name_buffer);
+            if ui.button("Add").clicked() && !cx.bookmark_name_buffer.is_empty() {
+                let name = std::mem::take(&mut cx.bookmark_name_buffer);
+                ProfApp::add_bookmark(cx, name, cx.view_interval);
+            }
+        });
+
+        let mut goto = None;
+        let mut remove = None;
+        for (index, bookmark) in cx.bookmarks.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui
+                    .link(&bookmark.name)
+                    .on_hover_text(bookmark.interval.format(&cx.display_config))
+                    .clicked()
+                {
+                    goto = Some(index);
+                }
+                if ui.small_button("x").clicked() {
+                    remove = Some(index);
+                }
+            });
+        }
+        if let Some(index) = goto {
+            ProfApp::goto_bookmark(cx, index);
+        }
+        if let Some(index) = remove {
+            ProfApp::remove_bookmark(cx, index);
+        }
+    }
+
+    // Horizontal extent of the view interval within an overview of the
+    // whole profile that is width pixels wide
+    fn overview_rect(cx: &Context, width: f32) -> (f32, f32) {
+        (
+            cx.total_interval.unlerp(cx.view_interval.start) * width,
+            cx.total_interval.unlerp(cx.view_interval.stop) * width,
+        )
+    }
+
+    fn overview(ui: &mut egui::Ui, cx: &mut Context) {
+        let (rect, response) = ui.allocate_exact_size(
+            Vec2::new(ui.available_width(), ui.spacing().interact_size.y),
+            egui::Sense::click_and_drag(),
+        );
+        let visuals = ui.style().interact_selectable(&response, false);
+        ui.painter()
+            .rect(rect, 0.0, ui.visuals().extreme_bg_color, visuals.bg_stroke);
+
+        let (mut min, mut max) = ProfApp::overview_rect(cx, rect.width());
+        if response.drag_started() {
+            cx.overview_drag_origin = response.interact_pointer_pos();
+        }
+        // Like selecting an interval to zoom to, the view only changes once
+        // the drag is released
+        let mut drag_delta = 0.0;
+        if let (Some(origin), Some(current)) =
+            (cx.overview_drag_origin, response.interact_pointer_pos())
+        {
+            drag_delta = current.x - origin.x;
+            min += drag_delta;
+            max += drag_delta;
+        }
+        let view_rect = Rect::from_min_max(
+            Pos2::new(rect.left() + min, rect.top()),
+            // Keep the viewport visible even when zoomed in very far
+            Pos2::new(rect.left() + max.at_least(min + 2.0), rect.bottom()),
+        );
+        ui.painter()
+            .rect(view_rect, 0.0, visuals.bg_fill, visuals.fg_stroke);
+
+        if response.drag_released() {
+            let fraction = drag_delta / rect.width() * cx.total_interval.duration_ns() as f32
+                / cx.view_interval.duration_ns() as f32;
+            ProfApp::pan_fraction(cx, fraction);
+            cx.overview_drag_origin = None;
+        } else if response.clicked() {
+            // Center the view on the point that was clicked
+            if let Some(pos) = response.interact_pointer_pos() {
+                let center = (min + max) / 2.0;
+                let fraction = (pos.x - rect.left() - center) / (max - min).at_least(1.0);
+                ProfApp::pan_fraction(cx, fraction);
+            }
+        }
+    }
+
+    fn pan_fraction(cx: &mut Context, fraction: f32) {
+        let delta = Timestamp((cx.view_interval.duration_ns() as f32 * fraction) as i64);
+        let mut interval = cx.view_interval.shifted(delta);
+        // Stay inside the profile without changing the duration
+        if interval.stop > cx.total_interval.stop {
+            interval = interval.shifted(cx.total_interval.stop - interval.stop);
+        }
+        if interval.start < cx.total_interval.start {
+            interval = interval.shifted(cx.total_interval.start - interval.start);
+        }
+        ProfApp::zoom_to(cx, interval);
+    }
+
+    fn zoom_about_center(cx: &mut Context, factor: f32) {
+        ProfApp::zoom_at(cx, 0.5, factor);
+    }
+
+    // Keeps the time at cursor_frac of the way across the view in place
+    fn zoom_at(cx: &mut Context, cursor_frac: f32, factor: f32) {
+        ProfApp::zoom_to(cx, cx.view_interval.zoom_at(cursor_frac, factor));
+    }
+
+    // The interval between two points in time, in whichever order
+    fn measure(a: Timestamp, b: Timestamp) -> Interval {
+        Interval::new(a.min(b), a.max(b))
+    }
+
+    // The slots a screen-space rect touches, and the times it spans given
+    // that the timeline rect shows view. None if it touches no slot.
+    fn region_of(
+        slot_rects: &[(EntryID, Rect)],
+        rect: Rect,
+        timeline: Rect,
+        view: Interval,
+    ) -> Option<(Vec<EntryID>, Interval)> {
+        let entries: Vec<_> = slot_rects
+            .iter()
+            .filter(|(_, slot_rect)| slot_rect.intersects(rect))
+            .map(|(entry_id, _)| entry_id.clone())
+            .collect();
+        if entries.is_empty() || timeline.width() <= 0.0 {
+            return None;
+        }
+        let to_time = |x: f32| {
+            let fraction = ((x - timeline.left()) / timeline.width()).clamp(0.0, 1.0);
+            view.lerp(fraction)
+        };
+        Some((
+            entries,
+            Interval::new(to_time(rect.left()), to_time(rect.right())),
+        ))
+    }
+
+    fn remember_server(cx: &mut Context, urls: &[Url]) {
+        const MAX_RECENT_SERVERS: usize = 8;
+
+        let urls: Vec<_> = urls.iter().map(Url::as_str).collect();
+        let urls = urls.join(",");
+        cx.recent_servers.retain(|server| *server != urls);
+        cx.recent_servers.insert(0, urls);
+        cx.recent_servers.truncate(MAX_RECENT_SERVERS);
+        cx.connected = true;
+    }
+
+    // Replaces the profile(s) shown with those served at urls (a
+    // comma-separated list)
+    #[cfg(not(target_arch = "wasm32"))]
+    fn connect(windows: &mut Vec<Window>, cx: &mut Context, urls: &str) -> Result<(), String> {
+        let urls = parse_urls(urls)?;
+        windows.clear();
+        let source_urls = urls.clone();
+        let data_source = crate::deferred::DeferredDataSource::new(move || {
+            crate::http::client::connect(&source_urls)
+        });
+        windows.push(Window::new(Box::new(data_source), 0));
+        let window = windows.last().unwrap();
+        cx.total_interval = window.config.interval;
+        cx.selected_state = SelectedState::default();
+        Self::zoom(cx, window.config.interval);
+        Self::remember_server(cx, &urls);
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn server_menu(ui: &mut egui::Ui, windows: &mut Vec<Window>, cx: &mut Context) {
+        let mut choice = None;
+        for (i, server) in cx.recent_servers.iter().enumerate() {
+            let current = i == 0 && cx.connected;
+            if ui.selectable_label(current, server).clicked() {
+                choice = Some(server.clone());
+            }
+        }
+        if !cx.recent_servers.is_empty() {
+            ui.separator();
+        }
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut cx.server_buffer)
+                .on_hover_text("Comma-separated URLs, e.g., http://localhost:8080");
+            if ui.button("Connect").clicked() {
+                choice = Some(cx.server_buffer.clone());
+            }
+        });
+        if let Some(error) = &cx.server_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+
+        if let Some(urls) = choice {
+            match Self::connect(windows, cx, &urls) {
+                Ok(()) => {
+                    cx.server_error = None;
+                    cx.server_buffer.clear();
+                    ui.close_menu();
+                }
+                Err(e) => cx.server_error = Some(e),
+            }
+        }
+    }
+
+    fn zoom_to_fit(cx: &mut Context) {
+        ProfApp::zoom(cx, cx.total_interval);
+    }
+
+    // Like zoom, but never leaves the bounds of the profile(s)
+    fn zoom_to(cx: &mut Context, interval: Interval) {
+        let interval = interval.clamp(cx.total_interval);
+        if interval.duration_ns() > 0 {
+            ProfApp::zoom(cx, interval);
+        }
+    }
+
     fn undo_zoom(cx: &mut Context) {
         if cx.zoom_state.index == 0 {
             return;
@@ -1097,7 +2306,41 @@ impl ProfApp {
         cx.zoom_state.zoom_count = 0;
     }
 
-    fn keyboard(ctx: &egui::Context, cx: &mut Context) {
+    // Recenter the view on the next (or previous) task in the hovered slot
+    fn step_task(windows: &mut [Window], cx: &mut Context, forward: bool) {
+        let center = cx.view_interval.lerp(0.5);
+        let mut result: Option<Interval> = None;
+        for window in windows.iter_mut() {
+            let config = &mut window.config;
+            let entry_id = match &config.hovered_slot {
+                Some(entry_id) => entry_id,
+                None => continue,
+            };
+            let task = if forward {
+                config.data_source.next_task_after(entry_id, center)
+            } else {
+                config.data_source.prev_task_before(entry_id, center)
+            };
+            if let Some(task) = task {
+                let closer = result.map_or(true, |best| {
+                    if forward {
+                        task.start < best.start
+                    } else {
+                        task.start > best.start
+                    }
+                });
+                if closer {
+                    result = Some(task);
+                }
+            }
+        }
+        if let Some(task) = result {
+            let delta = task.lerp(0.5) - center;
+            ProfApp::zoom_to(cx, cx.view_interval.shifted(delta));
+        }
+    }
+
+    fn keyboard(ctx: &egui::Context, windows: &mut [Window], cx: &mut Context) {
         // Focus is elsewhere, don't check any keys
         if ctx.memory(|m| m.focus().is_some()) {
             return;
@@ -1106,15 +2349,39 @@ impl ProfApp {
             UndoZoom,
             RedoZoom,
             ResetZoom,
+            Pan(f32),
+            Zoom(f32),
+            StepTask(bool),
             NoAction,
         }
+        let nav = &cx.nav_config;
         let action = ctx.input(|i| {
-            if i.key_pressed(egui::Key::ArrowLeft) {
+            // Shift distinguishes panning from undo/redo
+            if i.modifiers.shift && i.key_pressed(egui::Key::ArrowLeft) {
+                Actions::Pan(-nav.pan_step)
+            } else if i.modifiers.shift && i.key_pressed(egui::Key::ArrowRight) {
+                Actions::Pan(nav.pan_step)
+            } else if i.key_pressed(egui::Key::PlusEquals) {
+                Actions::Zoom(nav.zoom_factor)
+            } else if i.key_pressed(egui::Key::Minus) {
+                Actions::Zoom(1.0 / nav.zoom_factor)
+            } else if i.key_pressed(egui::Key::ArrowLeft)
+                || i.pointer.button_pressed(egui::PointerButton::Extra1)
+            {
+                // Including the mouse's back button, as in a browser
                 Actions::UndoZoom
-            } else if i.key_pressed(egui::Key::ArrowRight) {
+            } else if i.key_pressed(egui::Key::ArrowRight)
+                || i.pointer.button_pressed(egui::PointerButton::Extra2)
+            {
                 Actions::RedoZoom
-            } else if i.modifiers.ctrl && i.key_pressed(egui::Key::Num0) {
+            } else if (i.modifiers.ctrl && i.key_pressed(egui::Key::Num0))
+                || i.key_pressed(egui::Key::F)
+            {
                 Actions::ResetZoom
+            } else if i.key_pressed(egui::Key::N) {
+                Actions::StepTask(true)
+            } else if i.key_pressed(egui::Key::P) {
+                Actions::StepTask(false)
             } else {
                 Actions::NoAction
             }
@@ -1122,17 +2389,24 @@ impl ProfApp {
         match action {
             Actions::UndoZoom => ProfApp::undo_zoom(cx),
             Actions::RedoZoom => ProfApp::redo_zoom(cx),
-            Actions::ResetZoom => ProfApp::zoom(cx, cx.total_interval),
+            Actions::ResetZoom => ProfApp::zoom_to_fit(cx),
+            Actions::Pan(fraction) => ProfApp::pan_fraction(cx, fraction),
+            Actions::Zoom(factor) => ProfApp::zoom_about_center(cx, factor),
+            Actions::StepTask(forward) => ProfApp::step_task(windows, cx, forward),
             Actions::NoAction => {}
         }
     }
 
-    fn cursor(ui: &mut egui::Ui, cx: &mut Context) {
+    fn cursor(ui: &mut egui::Ui, windows: &mut [Window], cx: &mut Context) {
         // Hack: the UI rect we have at this point is not where the
         // timeline is being drawn. So fish out the coordinates we
         // need to draw the correct rect.
         let ui_rect = ui.min_rect();
-        let slot_rect = cx.slot_rect.unwrap();
+        let slot_rect = match cx.slot_rect {
+            Some(slot_rect) => slot_rect,
+            // Nothing has been drawn yet (e.g., the window panicked)
+            None => return,
+        };
         let rect = Rect::from_min_max(
             Pos2::new(slot_rect.min.x, ui_rect.min.y),
             Pos2::new(slot_rect.max.x, ui_rect.max.y),
@@ -1140,6 +2414,56 @@ impl ProfApp {
 
         let response = ui.allocate_rect(rect, egui::Sense::drag());
 
+        // Draw the time axis along the top
+        let tick_color = ui.visuals().weak_text_color();
+        let tick_font = TextStyle::Small.resolve(ui.style());
+        let wall_clock = windows
+            .iter()
+            .find_map(|window| window.config.time_origin.wall_clock)
+            .filter(|_| cx.wall_clock_axis);
+        let ticks = match wall_clock {
+            Some(origin) => {
+                cx.axis_formatter
+                    .wall_clock_ticks(cx.view_interval, rect.width(), origin)
+            }
+            None => cx.axis_formatter.ticks(cx.view_interval, rect.width()),
+        };
+        for (x, label) in ticks {
+            let top = Pos2::new(rect.left() + x, rect.top());
+            ui.painter().line_segment(
+                [top, top + Vec2::new(0.0, 4.0)],
+                Stroke::new(1.0, tick_color),
+            );
+            ui.painter().text(
+                top + Vec2::new(2.0, 4.0),
+                egui::Align2::LEFT_TOP,
+                label,
+                tick_font.clone(),
+                tick_color,
+            );
+        }
+
+        // Highlight the selected region(s) where their slots are on screen
+        for window in windows.iter() {
+            let region = window.selection_region();
+            let visible = region.and_then(|(entries, interval)| {
+                Some((entries, interval.intersection(cx.view_interval)?))
+            });
+            if let Some((entries, interval)) = visible {
+                let to_x = |time| rect.left() + cx.view_interval.unlerp(time) * rect.width();
+                let color = Color32::LIGHT_GREEN.linear_multiply(0.2);
+                for (entry_id, slot_rect) in &window.config.slot_rects {
+                    if entries.contains(entry_id) {
+                        let region_rect = Rect::from_x_y_ranges(
+                            to_x(interval.start)..=to_x(interval.stop),
+                            slot_rect.y_range(),
+                        );
+                        ui.painter().rect(region_rect, 0.0, color, Stroke::NONE);
+                    }
+                }
+            }
+        }
+
         // Handle drag detection
         let mut drag_interval = None;
 
@@ -1148,6 +2472,7 @@ impl ProfApp {
             // On the beginning of a drag, save our position so we can
             // calculate the delta
             cx.drag_origin = response.interact_pointer_pos();
+            cx.region_drag = ui.input(|i| i.modifiers.command);
         }
 
         if let (Some(origin), Some(current)) = (cx.drag_origin, response.interact_pointer_pos()) {
@@ -1163,10 +2488,14 @@ impl ProfApp {
 
             let interval = Interval::new(start, stop);
 
+            let region_rect = Rect::from_two_pos(origin, current);
             if is_active_drag {
                 // Still in drag, draw a rectangle to show the dragged region
-                let drag_rect =
-                    Rect::from_min_max(Pos2::new(min, rect.min.y), Pos2::new(max, rect.max.y));
+                let drag_rect = if cx.region_drag {
+                    region_rect
+                } else {
+                    Rect::from_min_max(Pos2::new(min, rect.min.y), Pos2::new(max, rect.max.y))
+                };
                 let color = Color32::DARK_GRAY.linear_multiply(0.5);
                 ui.painter().rect(drag_rect, 0.0, color, Stroke::NONE);
 
@@ -1175,10 +2504,63 @@ impl ProfApp {
                 // Only set view interval if the drag was a certain amount
                 const MIN_DRAG_DISTANCE: f32 = 4.0;
                 if max - min > MIN_DRAG_DISTANCE {
-                    ProfApp::zoom(cx, interval);
+                    if cx.region_drag {
+                        for window in windows.iter_mut() {
+                            window.select_region(region_rect, rect, cx.view_interval);
+                        }
+                    } else {
+                        ProfApp::zoom_to(cx, interval);
+                    }
                 }
 
                 cx.drag_origin = None;
+                cx.region_drag = false;
+            }
+        }
+
+        // Ctrl+scroll (or pinch) zooms about the cursor
+        if let Some(hover) = response.hover_pos() {
+            let factor = ui.input(|i| i.zoom_delta());
+            if factor != 1.0 {
+                let cursor_frac = (hover.x - rect.left()) / rect.width();
+                ProfApp::zoom_at(cx, cursor_frac, factor);
+            }
+        }
+
+        // Handle the ruler
+        let mut live_measurement = None;
+        if let Some(hover) = response.hover_pos() {
+            let time = cx
+                .view_interval
+                .lerp((hover.x - rect.left()) / rect.width());
+            let shift_clicked = ui.input(|i| i.modifiers.shift && i.pointer.primary_clicked());
+            if shift_clicked {
+                if let Some(anchor) = cx.measure_anchor.take() {
+                    cx.measurement = Some(ProfApp::measure(anchor, time));
+                } else {
+                    cx.measure_anchor = Some(time);
+                    cx.measurement = None;
+                }
+            } else if let Some(anchor) = cx.measure_anchor {
+                live_measurement = Some(ProfApp::measure(anchor, time));
+            }
+        }
+        if let Some(measurement) = live_measurement.or(cx.measurement) {
+            let to_x = |time| rect.left() + cx.view_interval.unlerp(time) * rect.width();
+            let measure_rect = Rect::from_min_max(
+                Pos2::new(to_x(measurement.start), rect.min.y),
+                Pos2::new(to_x(measurement.stop), rect.max.y),
+            );
+            let color = Color32::LIGHT_BLUE.linear_multiply(0.25);
+            ui.painter().rect(measure_rect, 0.0, color, Stroke::NONE);
+            if live_measurement.is_none() {
+                ui.painter().text(
+                    measure_rect.center_top(),
+                    egui::Align2::CENTER_TOP,
+                    measurement.format(&cx.display_config),
+                    TextStyle::Body.resolve(ui.style()),
+                    ui.visuals().text_color(),
+                );
             }
         }
 
@@ -1205,7 +2587,11 @@ impl ProfApp {
             // Hack: This avoids an issue where popups displayed normally are
             // forced to stack, even when an explicit position is
             // requested. Instead we display the popup manually via black magic
-            let popup_size = if drag_interval.is_some() { 300.0 } else { 90.0 };
+            let popup_size = if drag_interval.is_some() || live_measurement.is_some() {
+                300.0
+            } else {
+                90.0
+            };
             let mut popup_rect = Rect::from_min_size(
                 Pos2::new(top.x + HOVER_PADDING, top.y),
                 Vec2::new(popup_size, 100.0),
@@ -1225,13 +2611,81 @@ impl ProfApp {
             );
             egui::Frame::popup(ui.style()).show(&mut popup_ui, |ui| {
                 if let Some(drag) = drag_interval {
-                    ui.label(format!("{drag}"));
+                    ui.label(drag.format(&cx.display_config));
+                } else if let Some(measurement) = live_measurement {
+                    ui.label(measurement.format(&cx.display_config));
                 } else {
-                    ui.label(format!("t={time}"));
+                    ui.label(format!("t={}", time.format(&cx.display_config)));
                 }
             });
         }
     }
+
+    // Context menu for the task last right-clicked
+    fn task_menu(ctx: &egui::Context, cx: &mut Context) {
+        let (pos, details) = match &cx.task_menu {
+            Some(menu) => menu,
+            None => return,
+        };
+        let mut close = ctx.input(|i| i.key_pressed(egui::Key::Escape));
+        let response = egui::Area::new("task_menu")
+            .order(egui::Order::Foreground)
+            .fixed_pos(*pos)
+            .show(ctx, |ui| {
+                egui::Frame::menu(ui.style()).show(ui, |ui| {
+                    if ui.button("Copy task details").clicked() {
+                        ui.output_mut(|o| o.copied_text = details.clone());
+                        close = true;
+                    }
+                });
+            })
+            .response;
+        // The right click that opened the menu doesn't close it
+        if response.clicked_elsewhere() && !ctx.input(|i| i.pointer.secondary_clicked()) {
+            close = true;
+        }
+        if close {
+            cx.task_menu = None;
+        }
+    }
+
+    // Everything but the side panel, i.e., the part worth a screenshot
+    fn timeline(ctx: &egui::Context, windows: &mut [Window], cx: &mut Context) {
+        // Note: this must be a separate panel so that the cursor (which
+        // covers the central panel) doesn't capture drags meant for it
+        egui::TopBottomPanel::top("overview_panel").show(ctx, |ui| {
+            Self::overview(ui, cx);
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            // Use body font to figure out how tall to draw rectangles.
+
+            let font_id = TextStyle::Body.resolve(ui.style());
+            let row_height = ui.fonts(|f| f.row_height(&font_id));
+            // Just set this on every frame for now
+            cx.row_height = row_height * cx.density.scale();
+
+            let mut remaining = windows.len();
+            // Only wrap in a frame if more than one profile
+            if remaining > 1 {
+                for window in windows.iter_mut() {
+                    egui::Frame::group(ui.style()).show(ui, |ui| {
+                        ui.push_id(window.index, |ui| {
+                            ui.set_height(ui.available_height() / (remaining as f32));
+                            ui.set_width(ui.available_width());
+                            window.guarded_content(ui, cx);
+                            remaining -= 1;
+                        });
+                    });
+                }
+            } else {
+                for window in windows.iter_mut() {
+                    window.guarded_content(ui, cx);
+                }
+            }
+            Self::cursor(ui, windows, cx);
+        });
+    }
 }
 
 impl eframe::App for ProfApp {
@@ -1247,6 +2701,10 @@ impl eframe::App for ProfApp {
             cx,
             #[cfg(not(target_arch = "wasm32"))]
             last_update,
+            #[cfg(not(target_arch = "wasm32"))]
+            screenshot_requested,
+            #[cfg(not(target_arch = "wasm32"))]
+            screenshot_result,
             ..
         } = self;
 
@@ -1260,14 +2718,34 @@ impl eframe::App for ProfApp {
             *last_update = Some(now);
         }
 
+        Self::follow_live(ctx, windows, cx);
+
         #[cfg(not(target_arch = "wasm32"))]
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
+                    if ui.button("Save Screenshot").clicked() {
+                        // Taken at the end of the frame, once the view is drawn
+                        *screenshot_requested = true;
+                        ui.close_menu();
+                    }
                     if ui.button("Quit").clicked() {
                         _frame.close();
                     }
                 });
+                ui.menu_button("Server", |ui| Self::server_menu(ui, windows, cx));
+                match screenshot_result {
+                    Some(Ok(path)) => {
+                        ui.label(format!("Saved screenshot to {}", path.display()));
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(
+                            ui.visuals().error_fg_color,
+                            format!("Unable to save screenshot: {e}"),
+                        );
+                    }
+                    None => {}
+                }
             });
         });
 
@@ -1301,6 +2779,11 @@ impl eframe::App for ProfApp {
                 ProfApp::zoom(cx, cx.total_interval);
             }
 
+            egui::Frame::group(ui.style()).show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                Self::bookmarks(ui, cx);
+            });
+
             egui::Frame::group(ui.style()).show(ui, |ui| {
                 ui.set_width(ui.available_width());
                 ui.heading("Task Details");
@@ -1335,14 +2818,19 @@ impl eframe::App for ProfApp {
                             for node in window.panel.slots.iter_mut() {
                                 for channel in node.slots.iter_mut() {
                                     for slot in channel.slots.iter_mut() {
-                                        if slot.tiles.is_empty() {
-                                            slot.inflate(config, cx)
+                                        if !slot.loaded {
+                                            slot.inflate_blocking(config, cx)
                                         };
 
+                                        let clip =
+                                            slot.fetched_interval.unwrap_or(config.interval);
                                         for tile in slot.tiles.iter_mut() {
-                                            let meta = config
-                                                .data_source
-                                                .fetch_slot_meta_tile(&slot.entry_id, tile.tile_id);
+                                            let meta =
+                                                config.data_source.fetch_clipped_slot_meta_tile(
+                                                    &slot.entry_id,
+                                                    tile.tile_id,
+                                                    clip,
+                                                );
                                             for (row, i) in meta.items.iter().enumerate() {
                                                 for (idx, j) in i.iter().enumerate() {
                                                     if cx.selected_state.search(&j.title) {
@@ -1565,6 +3053,54 @@ impl eframe::App for ProfApp {
                     }
                 });
 
+                ui.checkbox(
+                    &mut cx.display_config.group_digits,
+                    "Group digits of nanosecond values",
+                );
+                ui.add(
+                    Slider::new(&mut cx.axis_formatter.min_spacing, 40.0..=400.0)
+                        .text("Axis tick spacing (px)"),
+                );
+                ui.checkbox(&mut cx.wall_clock_axis, "Wall-clock time axis")
+                    .on_hover_text(
+                        "Label the axis with UTC time of day, if the profile records when it started",
+                    );
+
+                egui::ComboBox::from_label("Row density")
+                    .selected_text(format!("{:?}", cx.density))
+                    .show_ui(ui, |ui| {
+                        for density in [Density::Normal, Density::Compact, Density::Dense] {
+                            ui.selectable_value(&mut cx.density, density, format!("{density:?}"));
+                        }
+                    });
+
+                egui::ComboBox::from_label("Colors")
+                    .selected_text(format!("{:?}", cx.color_scheme))
+                    .show_ui(ui, |ui| {
+                        for scheme in [
+                            ColorScheme::Default,
+                            ColorScheme::ColorblindSafe,
+                            ColorScheme::Grayscale,
+                        ] {
+                            ui.selectable_value(
+                                &mut cx.color_scheme,
+                                scheme,
+                                format!("{scheme:?}"),
+                            );
+                        }
+                    });
+
+                ui.add(
+                    Slider::new(&mut cx.nav_config.pan_step, 0.01..=1.0)
+                        .text("Pan step (Shift+Left/Right)"),
+                );
+                ui.add(
+                    Slider::new(&mut cx.nav_config.zoom_factor, 1.1..=10.0)
+                        .text("Zoom factor (+/-)"),
+                );
+                ui.checkbox(&mut cx.follow_live, "Follow live data")
+                    .on_hover_text("Keep the newest data in view. Panning turns this off.");
+
                 egui::warn_if_debug_build(ui);
 
                 #[cfg(not(target_arch = "wasm32"))]
@@ -1575,39 +3111,55 @@ impl eframe::App for ProfApp {
             });
         });
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            // Use body font to figure out how tall to draw rectangles.
+        Self::timeline(ctx, windows, cx);
 
-            let font_id = TextStyle::Body.resolve(ui.style());
-            let row_height = ui.fonts(|f| f.row_height(&font_id));
-            // Just set this on every frame for now
-            cx.row_height = row_height;
+        Self::task_menu(ctx, cx);
 
-            let mut remaining = windows.len();
-            // Only wrap in a frame if more than one profile
-            if remaining > 1 {
-                for window in windows.iter_mut() {
-                    egui::Frame::group(ui.style()).show(ui, |ui| {
-                        ui.push_id(window.index, |ui| {
-                            ui.set_height(ui.available_height() / (remaining as f32));
-                            ui.set_width(ui.available_width());
-                            window.content(ui, cx);
-                            remaining -= 1;
-                        });
-                    });
+        Self::keyboard(ctx, windows, cx);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if std::mem::take(screenshot_requested) {
+            // Twice the resolution of the screen, e.g., for printing
+            const SCALE: f32 = 2.0;
+            let size = ctx.screen_rect().size() * ctx.pixels_per_point() * SCALE;
+            let size = [size.x as u32, size.y as u32];
+            // In the working directory, spelled out in full so that the
+            // message saying where it went is of use
+            let path = std::env::current_dir()
+                .unwrap_or_default()
+                .join(screenshot_path(std::time::SystemTime::now()));
+            let result = crate::screenshot::export_png(ctx, &path, size, |ctx| {
+                Self::timeline(ctx, windows, cx)
+            });
+            *screenshot_result = match result {
+                Ok(()) => {
+                    log::info!("saved screenshot to {}", path.display());
+                    Some(Ok(path))
                 }
-            } else {
-                for window in windows.iter_mut() {
-                    window.content(ui, cx);
+                Err(e) => {
+                    log::error!("unable to save screenshot: {e}");
+                    Some(Err(e.to_string()))
                 }
-            }
-            Self::cursor(ui, cx);
-        });
-
-        Self::keyboard(ctx, cx);
+            };
+        }
     }
 }
 
+// Named after the time it was taken, so that earlier screenshots aren't
+// overwritten
+#[cfg(not(target_arch = "wasm32"))]
+fn screenshot_path(now: std::time::SystemTime) -> std::path::PathBuf {
+    let since_epoch = now
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    format!(
+        "screenshot-{}-{:03}.png",
+        since_epoch.as_secs(),
+        since_epoch.subsec_millis()
+    )
+    .into()
+}
+
 trait UiExtra {
     fn subheading(&mut self, text: impl Into<egui::RichText>, cx: &Context) -> egui::Response;
     fn show_tooltip(
@@ -1713,7 +3265,11 @@ fn get_filtered_entries<'a>(
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-pub fn start(data_source: Box<dyn DataSource>, extra_source: Option<Box<dyn DataSource>>) {
+pub fn start(
+    data_source: Box<dyn DataSource>,
+    extra_source: Option<Box<dyn DataSource>>,
+    view: ViewState,
+) {
     // Log to stdout (if you run with `RUST_LOG=debug`).
     tracing_subscriber::fmt::init();
 
@@ -1721,13 +3277,17 @@ pub fn start(data_source: Box<dyn DataSource>, extra_source: Option<Box<dyn Data
     eframe::run_native(
         "Legion Prof",
         native_options,
-        Box::new(|cc| Box::new(ProfApp::new(cc, data_source, extra_source))),
+        Box::new(|cc| Box::new(ProfApp::new(cc, data_source, extra_source, view))),
     )
     .expect("failed to start eframe");
 }
 
 #[cfg(target_arch = "wasm32")]
-pub fn start(data_source: Box<dyn DataSource>, extra_source: Option<Box<dyn DataSource>>) {
+pub fn start(
+    data_source: Box<dyn DataSource>,
+    extra_source: Option<Box<dyn DataSource>>,
+    view: ViewState,
+) {
     // Make sure panics are logged using `console.error`.
     console_error_panic_hook::set_once();
 
@@ -1740,9 +3300,450 @@ pub fn start(data_source: Box<dyn DataSource>, extra_source: Option<Box<dyn Data
         eframe::start_web(
             "the_canvas_id", // hardcode it
             web_options,
-            Box::new(|cc| Box::new(ProfApp::new(cc, data_source, extra_source))),
+            Box::new(|cc| Box::new(ProfApp::new(cc, data_source, extra_source, view))),
         )
         .await
         .expect("failed to start eframe");
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::SummaryTile;
+    use crate::deferred::DeferredDataSource;
+    use crate::mock::tests::{interval, sample, slot_id, summary_id};
+    use crate::mock::MockDataSource;
+
+    // Draws a frame of window, as the app would
+    fn draw(window: &mut Window, cx: &mut Context) {
+        let ctx = egui::Context::default();
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| window.content(ui, cx));
+        });
+    }
+
+    #[test]
+    fn loading_regions_clear_once_fetched() {
+        let data_source = DeferredDataSource::new(|| Box::new(sample()));
+        let mut window = Window::new(Box::new(data_source), 0);
+        window.expand_to(&slot_id());
+        let mut cx = Context {
+            view_interval: interval(),
+            row_height: 20.0,
+            ..Default::default()
+        };
+
+        // The first frame only starts fetching
+        draw(&mut window, &mut cx);
+        let loading = window.loading_regions();
+        assert!(loading.contains(&(slot_id(), interval())));
+        assert!(loading.contains(&(summary_id(), interval())));
+
+        let start = Instant::now();
+        while !window.loading_regions().is_empty() {
+            assert!(start.elapsed().as_secs() < 5, "still loading");
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            draw(&mut window, &mut cx);
+        }
+        assert!(!window.config.is_loading(&slot_id()));
+    }
+
+    #[test]
+    fn panning_within_fetched_tiles_is_covered() {
+        let span = |start: i64, stop: i64| Interval::new(Timestamp(start), Timestamp(stop));
+        let bounds = span(0, 1000);
+        let fetched = Some(span(100, 300));
+        let last = Some(span(120, 180));
+
+        assert!(still_covered(fetched, last, span(140, 200), bounds));
+        // Panned past the tiles
+        assert!(!still_covered(fetched, last, span(260, 320), bounds));
+        // Zoomed
+        assert!(!still_covered(fetched, last, span(140, 160), bounds));
+        // Nothing fetched yet
+        assert!(!still_covered(None, last, span(140, 200), bounds));
+        // Only the part of the view inside the profile needs tiles
+        let fetched = Some(span(900, 1000));
+        let last = Some(span(920, 980));
+        assert!(still_covered(fetched, last, span(960, 1020), bounds));
+    }
+
+    #[test]
+    fn screenshots_are_named_by_time() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let first = UNIX_EPOCH + Duration::from_millis(1_700_000_000_005);
+        assert_eq!(
+            screenshot_path(first),
+            std::path::PathBuf::from("screenshot-1700000000-005.png")
+        );
+        let second = first + Duration::from_millis(1);
+        assert_ne!(screenshot_path(first), screenshot_path(second));
+    }
+
+    #[test]
+    fn colorblind_safe_colors_ignore_lookup_order() {
+        let colors: Vec<_> = (0..20).map(|i| Color32::from_rgb(i * 10, 0, 0)).collect();
+        let recolor = |color: &Color32| color_for(*color, ColorScheme::ColorblindSafe);
+        let forwards: Vec<_> = colors.iter().map(recolor).collect();
+        let mut backwards: Vec<_> = colors.iter().rev().map(recolor).collect();
+        backwards.reverse();
+        assert_eq!(forwards, backwards);
+        assert!(forwards.iter().all(|color| OKABE_ITO.contains(color)));
+        // Spread over the palette rather than all landing on one entry
+        let mut used: Vec<_> = forwards.iter().map(|color| color.to_array()).collect();
+        used.sort();
+        used.dedup();
+        assert!(used.len() > 1);
+    }
+
+    fn span(start: i64, stop: i64) -> Interval {
+        Interval::new(Timestamp(start), Timestamp(stop))
+    }
+
+    // Showing start to stop of a profile spanning interval()
+    fn viewing(start: i64, stop: i64) -> Context {
+        Context {
+            total_interval: interval(),
+            view_interval: span(start, stop),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn zoom_to_stays_within_the_profile() {
+        let mut cx = viewing(200, 400);
+        ProfApp::zoom_to_fit(&mut cx);
+        assert_eq!(cx.view_interval, interval());
+
+        ProfApp::zoom_to(&mut cx, span(-500, 200));
+        assert_eq!(cx.view_interval, span(0, 200));
+        ProfApp::zoom_to(&mut cx, span(900, 1500));
+        assert_eq!(cx.view_interval, span(900, 1000));
+        // Nothing left once clamped
+        ProfApp::zoom_to(&mut cx, span(2000, 3000));
+        assert_eq!(cx.view_interval, span(900, 1000));
+    }
+
+    #[test]
+    fn view_state_round_trips_through_query() {
+        let view = ViewState {
+            interval: Some(span(100, 250)),
+            entry_id: Some(summary_id()),
+            urls: Some("http://a:8080,http://b:8080".to_owned()),
+        };
+        let query = view.to_query();
+        assert_eq!(
+            query,
+            "start=100ns&stop=250ns&entry=0/0/summary&urls=http://a:8080,http://b:8080"
+        );
+        let parsed = ViewState::from_query(&format!("?{query}"));
+        assert_eq!(parsed.interval, view.interval);
+        assert_eq!(parsed.entry_id, view.entry_id);
+        assert_eq!(parsed.urls, view.urls);
+
+        // Bad values are dropped
+        let parsed = ViewState::from_query("start=1us&stop=x&entry=0/-1&other=1");
+        assert_eq!(parsed.interval, None);
+        assert_eq!(parsed.entry_id, None);
+    }
+
+    #[test]
+    fn measurements_are_positive_either_way() {
+        let (a, b) = (Timestamp(700), Timestamp(250));
+        assert_eq!(ProfApp::measure(a, b), span(250, 700));
+        assert_eq!(ProfApp::measure(b, a), span(250, 700));
+        assert_eq!(ProfApp::measure(a, b).duration_ns(), 450);
+    }
+
+    #[test]
+    fn keyboard_pan_and_zoom() {
+        let mut cx = viewing(200, 400);
+        ProfApp::pan_fraction(&mut cx, 0.5);
+        assert_eq!(cx.view_interval, span(300, 500));
+        ProfApp::pan_fraction(&mut cx, -0.5);
+        assert_eq!(cx.view_interval, span(200, 400));
+        // Stops at the end of the profile, keeping the width
+        ProfApp::pan_fraction(&mut cx, 10.0);
+        assert_eq!(cx.view_interval, span(800, 1000));
+
+        let mut cx = viewing(200, 600);
+        ProfApp::zoom_about_center(&mut cx, 2.0);
+        assert_eq!(cx.view_interval, span(300, 500));
+        ProfApp::zoom_about_center(&mut cx, 0.5);
+        assert_eq!(cx.view_interval, span(200, 600));
+    }
+
+    #[test]
+    fn overview_highlights_the_view() {
+        assert_eq!(ProfApp::overview_rect(&viewing(0, 100), 500.0), (0.0, 50.0));
+        assert_eq!(
+            ProfApp::overview_rect(&viewing(450, 550), 500.0),
+            (225.0, 275.0)
+        );
+        assert_eq!(
+            ProfApp::overview_rect(&viewing(900, 1000), 500.0),
+            (450.0, 500.0)
+        );
+    }
+
+    #[test]
+    fn bookmarks_can_be_added_removed_and_visited() {
+        let mut cx = viewing(0, 1000);
+        ProfApp::add_bookmark(&mut cx, "start".to_owned(), span(0, 100));
+        ProfApp::add_bookmark(&mut cx, "middle".to_owned(), span(400, 600));
+        ProfApp::add_bookmark(&mut cx, "past the end".to_owned(), span(900, 1200));
+        let names: Vec<_> = cx.bookmarks.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, vec!["start", "middle", "past the end"]);
+
+        ProfApp::goto_bookmark(&mut cx, 1);
+        assert_eq!(cx.view_interval, span(400, 600));
+        ProfApp::goto_bookmark(&mut cx, 2);
+        assert_eq!(cx.view_interval, span(900, 1000));
+        // Out of range
+        ProfApp::goto_bookmark(&mut cx, 3);
+        assert_eq!(cx.view_interval, span(900, 1000));
+
+        ProfApp::remove_bookmark(&mut cx, 0);
+        ProfApp::remove_bookmark(&mut cx, 5);
+        let names: Vec<_> = cx.bookmarks.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, vec!["middle", "past the end"]);
+        ProfApp::goto_bookmark(&mut cx, 0);
+        assert_eq!(cx.view_interval, span(400, 600));
+    }
+
+    // Like sample, but fetching slot tiles panics, as a malformed tile might
+    struct PanickingDataSource(MockDataSource);
+
+    impl DataSource for PanickingDataSource {
+        fn interval(&mut self) -> Interval {
+            self.0.interval()
+        }
+        fn fetch_info(&mut self) -> EntryInfo {
+            self.0.fetch_info()
+        }
+        fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+            self.0.request_tiles(entry_id, request_interval)
+        }
+        fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+            self.0.fetch_summary_tile(entry_id, tile_id)
+        }
+        fn fetch_slot_tile(&mut self, _: &EntryID, _: TileID) -> SlotTile {
+            panic!("malformed tile")
+        }
+        fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+            self.0.fetch_slot_meta_tile(entry_id, tile_id)
+        }
+    }
+
+    #[test]
+    fn panics_only_stop_their_window() {
+        let mut window = Window::new(Box::new(PanickingDataSource(sample())), 0);
+        window.expand_to(&slot_id());
+        let mut cx = Context {
+            view_interval: interval(),
+            row_height: 20.0,
+            ..Default::default()
+        };
+        let ctx = egui::Context::default();
+        for _ in 0..2 {
+            let _ = ctx.run(egui::RawInput::default(), |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| window.guarded_content(ui, &mut cx));
+            });
+            assert_eq!(window.panic_message.as_deref(), Some("malformed tile"));
+        }
+    }
+
+    #[test]
+    fn following_keeps_the_newest_data_in_view() {
+        // The profile grew from 1000 to 1500 since the last poll
+        let total = span(0, 1500);
+        assert_eq!(
+            ProfApp::follow_interval(span(700, 1000), total),
+            span(1200, 1500)
+        );
+        // Already at the end
+        assert_eq!(
+            ProfApp::follow_interval(span(1200, 1500), total),
+            span(1200, 1500)
+        );
+        // Wider than the profile: starts at its start instead
+        assert_eq!(
+            ProfApp::follow_interval(span(0, 2000), total),
+            span(0, 2000)
+        );
+
+        let ctx = egui::Context::default();
+        let mut cx = Context {
+            follow_live: true,
+            ..viewing(200, 400)
+        };
+        ProfApp::follow_live(&ctx, &mut [], &mut cx);
+        assert_eq!(cx.view_interval, span(800, 1000));
+        // Zooming keeps following, at the new width
+        cx.view_interval = span(900, 1000);
+        ProfApp::follow_live(&ctx, &mut [], &mut cx);
+        assert!(cx.follow_live);
+        // Panning away stops it
+        cx.view_interval = span(500, 600);
+        ProfApp::follow_live(&ctx, &mut [], &mut cx);
+        assert!(!cx.follow_live);
+        assert_eq!(cx.view_interval, span(500, 600));
+    }
+
+    #[test]
+    fn regions_cover_the_slots_and_times_under_the_rect() {
+        // Three slots, 20 px tall, stacked in a timeline 800 px wide
+        let slot_rects: Vec<_> = (0..3)
+            .map(|i| {
+                let top = 100.0 + 20.0 * i as f32;
+                let rect = Rect::from_min_max(Pos2::new(100.0, top), Pos2::new(900.0, top + 20.0));
+                (EntryID::root().child(0).child(i), rect)
+            })
+            .collect();
+        let timeline = Rect::from_min_max(Pos2::new(100.0, 100.0), Pos2::new(900.0, 160.0));
+        let region = |min: Pos2, max: Pos2| {
+            ProfApp::region_of(
+                &slot_rects,
+                Rect::from_min_max(min, max),
+                timeline,
+                interval(),
+            )
+        };
+
+        let (entries, times) = region(Pos2::new(300.0, 110.0), Pos2::new(500.0, 130.0)).unwrap();
+        assert_eq!(
+            entries,
+            vec![slot_rects[0].0.clone(), slot_rects[1].0.clone()]
+        );
+        assert_eq!(times, span(250, 500));
+
+        // Times past either end of the timeline are clamped to the view
+        let (entries, times) = region(Pos2::new(0.0, 145.0), Pos2::new(1000.0, 150.0)).unwrap();
+        assert_eq!(entries, vec![slot_rects[2].0.clone()]);
+        assert_eq!(times, interval());
+
+        // Below every slot
+        assert_eq!(
+            region(Pos2::new(300.0, 200.0), Pos2::new(500.0, 220.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn hovered_rows_follow_the_row_height() {
+        // The row of 4 whose items are under y, as hit-tested when drawing
+        let row_at = |y: f32, row_height: f32| {
+            (0..4).find(|&row| {
+                let (top, bottom) = entry_y_range(row, row_height);
+                (top..=bottom).contains(&y)
+            })
+        };
+
+        assert_eq!(entry_y_range(1, 20.0), (21.0, 39.0));
+        assert_eq!(row_at(5.0, 20.0), Some(0));
+        assert_eq!(row_at(25.0, 20.0), Some(1));
+        // In the gap between rows
+        assert_eq!(row_at(40.0, 20.0), None);
+        // Past the last row
+        assert_eq!(row_at(85.0, 20.0), None);
+
+        // The same spots, with dense rows
+        let dense = 20.0 * Density::Dense.scale();
+        assert_eq!(row_at(5.0, dense), Some(0));
+        assert_eq!(row_at(25.0, dense), Some(3));
+        assert_eq!(row_at(40.0, dense), None);
+    }
+
+    #[test]
+    fn panels_are_labeled_with_the_kind_of_their_slots() {
+        let slot = |name: &str, kind| EntryInfo::Slot {
+            short_name: name.to_owned(),
+            long_name: name.to_owned(),
+            max_rows: 1,
+            kind,
+        };
+        let panel = |name: &str, slots| EntryInfo::Panel {
+            short_name: name.to_owned(),
+            long_name: name.to_owned(),
+            summary: None,
+            slots,
+        };
+        let node = panel(
+            "Node 0",
+            vec![
+                panel(
+                    "CPU",
+                    vec![
+                        slot("CPU 0", EntryKind::Processor),
+                        slot("CPU 1", EntryKind::Processor),
+                    ],
+                ),
+                panel("SYS", vec![slot("SYS 0", EntryKind::Memory)]),
+                panel(
+                    "Mixed",
+                    vec![
+                        slot("CPU 2", EntryKind::Processor),
+                        slot("CHAN 0", EntryKind::Channel),
+                    ],
+                ),
+            ],
+        );
+        let mut source = MockDataSource::new(panel("root", vec![node]), interval());
+
+        let info = source.fetch_info();
+        let node = match &info {
+            EntryInfo::Panel { slots, .. } => &slots[0],
+            _ => unreachable!(),
+        };
+        let kinds = match node {
+            EntryInfo::Panel { slots, .. } => slots.iter().map(EntryInfo::kind).collect::<Vec<_>>(),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            kinds,
+            vec![Some(EntryKind::Processor), Some(EntryKind::Memory), None]
+        );
+
+        let root = Panel::<Panel<Panel<Slot>>>::new(&info, EntryID::root());
+        let hover_texts: Vec<_> = root.slots[0]
+            .slots
+            .iter()
+            .map(|panel| panel.hover_text())
+            .collect();
+        assert_eq!(
+            hover_texts,
+            vec!["CPU (processors)", "SYS (memories)", "Mixed"]
+        );
+        assert_eq!(root.slots[0].slots[1].slots[0].kind, EntryKind::Memory);
+    }
+
+    #[test]
+    fn back_and_forward_through_the_zoom_history() {
+        let mut cx = viewing(200, 400);
+        ProfApp::zoom_to_fit(&mut cx);
+        for view in [span(0, 500), span(100, 300), span(150, 200)] {
+            ProfApp::zoom(&mut cx, view);
+        }
+        ProfApp::undo_zoom(&mut cx);
+        ProfApp::undo_zoom(&mut cx);
+        ProfApp::redo_zoom(&mut cx);
+        assert_eq!(cx.view_interval, span(100, 300));
+
+        // Consecutive identical views are recorded once, and going where
+        // Forward would go keeps the rest of the history
+        ProfApp::zoom(&mut cx, span(100, 300));
+        ProfApp::zoom(&mut cx, span(150, 200));
+        assert_eq!(cx.zoom_state.levels.len(), 4);
+        assert_eq!(cx.zoom_state.index, 3);
+
+        // The oldest views are forgotten
+        for i in 0..MAX_ZOOM_LEVELS as i64 {
+            ProfApp::zoom(&mut cx, span(i, i + 10));
+        }
+        assert_eq!(cx.zoom_state.levels.len(), MAX_ZOOM_LEVELS);
+        assert_eq!(cx.zoom_state.levels[0], span(0, 10));
+        assert_eq!(cx.zoom_state.index, MAX_ZOOM_LEVELS - 1);
+    }
+}
@@ -0,0 +1,287 @@
+#![warn(clippy::all, rust_2018_idioms)]
+
+// Replays a trace of view intervals (e.g., a pan, then a zoom) against a
+// synthetic profile served by MockDataSource, and reports how many tiles were
+// fetched, how often the cache answered, and how many bytes were fetched.
+//
+// Usage: bench [TRACE] [--prefetch N] [--budget BYTES]
+//
+// TRACE defaults to pan_zoom.trace next to this file.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use egui::Color32;
+
+use legion_prof_viewer::cache::{CacheStats, CachingDataSource};
+use legion_prof_viewer::data::{
+    DataSource, DataSourceError, EntryID, EntryInfo, EntryKind, Item, ItemUID, SlotMetaTile,
+    SlotTile, SummaryTile, TileID, UtilPoint,
+};
+use legion_prof_viewer::mock::MockDataSource;
+use legion_prof_viewer::timestamp::{Interval, Timestamp};
+
+const DEFAULT_TRACE: &str = include_str!("pan_zoom.trace");
+
+const NS_PER_MS: i64 = 1_000_000;
+// The synthetic profile: SLOTS slots over PROFILE_MS, cut into 1 ms tiles
+const PROFILE_MS: i64 = 100;
+const SLOTS: u64 = 8;
+const ITEMS_PER_TILE: i64 = 50;
+
+#[derive(Debug, Default)]
+struct FetchCounts {
+    tiles: u64,
+    bytes: u64,
+}
+
+#[derive(Debug)]
+struct Report {
+    counts: FetchCounts,
+    stats: CacheStats,
+    elapsed: Duration,
+}
+
+// Sits under the cache, so that it only sees fetches the cache missed
+struct CountingDataSource {
+    data_source: Box<dyn DataSource>,
+    counts: Rc<RefCell<FetchCounts>>,
+}
+
+impl CountingDataSource {
+    fn count<T: serde::Serialize>(&self, tile: &T) {
+        let mut counts = self.counts.borrow_mut();
+        counts.tiles += 1;
+        counts.bytes += bincode::serialize(tile).map_or(0, |body| body.len() as u64);
+    }
+}
+
+impl DataSource for CountingDataSource {
+    fn interval(&mut self) -> Interval {
+        self.data_source.interval()
+    }
+
+    fn fetch_info(&mut self) -> EntryInfo {
+        self.data_source.fetch_info()
+    }
+
+    fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+        self.data_source.request_tiles(entry_id, request_interval)
+    }
+
+    fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+        let tile = self.data_source.fetch_summary_tile(entry_id, tile_id);
+        self.count(&tile);
+        tile
+    }
+
+    fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+        let tile = self.data_source.fetch_slot_tile(entry_id, tile_id);
+        self.count(&tile);
+        tile
+    }
+
+    fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+        let tile = self.data_source.fetch_slot_meta_tile(entry_id, tile_id);
+        self.count(&tile);
+        tile
+    }
+
+    fn last_error(&self) -> Option<String> {
+        self.data_source.last_error()
+    }
+
+    fn get_errors(&mut self) -> Vec<DataSourceError> {
+        self.data_source.get_errors()
+    }
+}
+
+// One node with one kind holding SLOTS slots, plus a summary for the kind
+fn profile() -> (MockDataSource, Vec<EntryID>, EntryID) {
+    let kind_id = EntryID::root().child(0).child(0);
+    let summary_id = kind_id.summary();
+    let slot_ids: Vec<_> = (0..SLOTS).map(|i| kind_id.child(i)).collect();
+
+    let slots = (0..SLOTS)
+        .map(|i| EntryInfo::Slot {
+            short_name: format!("s{i}"),
+            long_name: format!("Slot {i}"),
+            max_rows: 1,
+            kind: EntryKind::Processor,
+        })
+        .collect();
+    let kind = EntryInfo::Panel {
+        short_name: "kind".to_owned(),
+        long_name: "Kind".to_owned(),
+        summary: Some(Box::new(EntryInfo::Summary {
+            color: Color32::GRAY,
+        })),
+        slots,
+    };
+    let node = EntryInfo::Panel {
+        short_name: "n0".to_owned(),
+        long_name: "Node 0".to_owned(),
+        summary: None,
+        slots: vec![kind],
+    };
+    let info = EntryInfo::Panel {
+        short_name: "root".to_owned(),
+        long_name: "root".to_owned(),
+        summary: None,
+        slots: vec![node],
+    };
+
+    let interval = Interval::new(Timestamp(0), Timestamp(PROFILE_MS * NS_PER_MS));
+    let mut data_source = MockDataSource::new(info, interval);
+    let mut next_uid = 0;
+    for tile_id in interval
+        .split_evenly(PROFILE_MS as usize)
+        .into_iter()
+        .map(TileID)
+    {
+        let step = tile_id.0.duration_ns() / ITEMS_PER_TILE;
+        for slot_id in &slot_ids {
+            let items = (0..ITEMS_PER_TILE)
+                .map(|i| {
+                    next_uid += 1;
+                    let start = tile_id.0.start + Timestamp(i * step);
+                    Item {
+                        item_uid: ItemUID(next_uid),
+                        interval: Interval::new(start, start + Timestamp(step * 3 / 4)),
+                        color: Color32::LIGHT_BLUE,
+                    }
+                })
+                .collect();
+            data_source = data_source.with_slot_tile(
+                slot_id.clone(),
+                SlotTile {
+                    tile_id,
+                    items: vec![items],
+                },
+            );
+        }
+        let utilization = tile_id
+            .0
+            .split_evenly(10)
+            .into_iter()
+            .map(|part| UtilPoint {
+                time: part.start,
+                util: 0.75,
+            })
+            .collect();
+        data_source = data_source.with_summary_tile(
+            summary_id.clone(),
+            SummaryTile {
+                tile_id,
+                utilization,
+            },
+        );
+    }
+    (data_source, slot_ids, summary_id)
+}
+
+fn parse_trace(trace: &str) -> Vec<Interval> {
+    trace
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            Interval::parse_interval(line)
+                .unwrap_or_else(|e| panic!("bad interval in trace {line:?}: {e}"))
+        })
+        .collect()
+}
+
+// Fetches what the app would draw for each of views, in order
+fn replay(views: &[Interval], prefetch_radius: usize, byte_budget: usize) -> Report {
+    let (mock, slot_ids, summary_id) = profile();
+    let counts = Rc::new(RefCell::new(FetchCounts::default()));
+    let counting = CountingDataSource {
+        data_source: Box::new(mock),
+        counts: counts.clone(),
+    };
+    let mut data_source = CachingDataSource::new(Box::new(counting), byte_budget);
+    data_source.prefetch_radius = prefetch_radius;
+
+    let start = Instant::now();
+    for view in views {
+        for tile_id in data_source.request_tiles(&summary_id, *view) {
+            data_source.fetch_summary_tile(&summary_id, tile_id);
+        }
+        for slot_id in &slot_ids {
+            for tile_id in data_source.request_tiles(slot_id, *view) {
+                data_source.fetch_slot_tile(slot_id, tile_id);
+            }
+        }
+        // The app prefetches while waiting for the next view
+        while data_source.idle() {}
+    }
+    Report {
+        counts: counts.take(),
+        stats: data_source.cache_stats(),
+        elapsed: start.elapsed(),
+    }
+}
+
+fn main() {
+    let mut trace_path = None;
+    let mut prefetch_radius = 0;
+    let mut byte_budget = 64 << 20;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or_else(|| panic!("{name} expects a number"))
+        };
+        match arg.as_str() {
+            "--prefetch" => prefetch_radius = value("--prefetch"),
+            "--budget" => byte_budget = value("--budget"),
+            _ => trace_path = Some(arg),
+        }
+    }
+    let trace = match &trace_path {
+        Some(path) => std::fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("unable to read trace {path}: {e}")),
+        None => DEFAULT_TRACE.to_owned(),
+    };
+    let views = parse_trace(&trace);
+
+    let report = replay(&views, prefetch_radius, byte_budget);
+
+    let stats = report.stats;
+    let counts = report.counts;
+    let lookups = stats.hits + stats.misses;
+    let hit_rate = if lookups > 0 {
+        100.0 * stats.hits as f64 / lookups as f64
+    } else {
+        0.0
+    };
+    println!("views replayed:  {}", views.len());
+    println!("tiles fetched:   {}", counts.tiles);
+    println!("  prefetched:    {}", stats.prefetches);
+    println!(
+        "cache hits:      {} of {lookups} ({hit_rate:.1}%)",
+        stats.hits
+    );
+    println!("cache evictions: {}", stats.evictions);
+    println!("bytes fetched:   {}", counts.bytes);
+    println!("elapsed:         {:?}", report.elapsed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_trace_replays_end_to_end() {
+        let views = parse_trace(DEFAULT_TRACE);
+        assert!(!views.is_empty());
+        let report = replay(&views, 0, 64 << 20);
+        assert!(report.counts.tiles > 0);
+        assert!(report.counts.bytes > 0);
+        // Panning revisits tiles
+        assert!(report.stats.hits > 0);
+    }
+}
@@ -0,0 +1,490 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::mem::size_of;
+
+use crate::data::{
+    DataSource, DataSourceError, EntryID, EntryInfo, Item, ItemMeta, SlotMetaTile, SlotTile,
+    SummaryTile, TileID, TimeOrigin, UtilPoint,
+};
+use crate::layer::DataSourceLayer;
+use crate::timestamp::{Interval, Timestamp};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum TileKind {
+    Summary,
+    Slot,
+    SlotMeta,
+}
+
+type CacheKey = (TileKind, EntryID, TileID);
+
+// Prefetches not yet done when this many more are queued are dropped, since
+// they're likely for views that have since moved on
+const MAX_QUEUED_PREFETCHES: usize = 64;
+
+#[derive(Debug, Clone)]
+enum CachedTile {
+    Summary(SummaryTile),
+    Slot(SlotTile),
+    SlotMeta(SlotMetaTile),
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    tile: CachedTile,
+    bytes: usize,
+    last_used: u64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub prefetches: u64,
+}
+
+/// Wraps any DataSource and keeps recently fetched tiles in memory. Tiles
+/// are evicted in least-recently-used order once the (approximate) size of
+/// the cached tiles exceeds the byte budget.
+///
+/// Optionally, fetching a slot tile also prefetches the prefetch_radius
+/// tiles of the same size immediately before and after it. Prefetches are
+/// queued and only done when idle, so they never hold up a fetch that was
+/// asked for. Prefetched tiles only go into the cache; they are returned
+/// once they are actually asked for.
+pub struct CachingDataSource {
+    pub prefetch_radius: usize,
+    data_source: Box<dyn DataSource>,
+    byte_budget: usize,
+    bytes: usize,
+    tick: u64,
+    entries: BTreeMap<CacheKey, CacheEntry>,
+    lru: BTreeMap<u64, CacheKey>, // last_used -> key
+    stats: CacheStats,
+    bounds: Option<Interval>,
+    queued_prefetches: VecDeque<(EntryID, TileID)>,
+    errors: Vec<DataSourceError>,
+}
+
+impl CachingDataSource {
+    pub fn new(data_source: Box<dyn DataSource>, byte_budget: usize) -> Self {
+        Self {
+            prefetch_radius: 0,
+            data_source,
+            byte_budget,
+            bytes: 0,
+            tick: 0,
+            entries: BTreeMap::new(),
+            lru: BTreeMap::new(),
+            stats: CacheStats::default(),
+            bounds: None,
+            queued_prefetches: VecDeque::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    fn lookup(&mut self, key: &CacheKey) -> Option<CachedTile> {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.lru.remove(&entry.last_used);
+            self.lru.insert(tick, key.clone());
+            entry.last_used = tick;
+            self.stats.hits += 1;
+            log::trace!("cache hit: {:?} {} {:?}", key.0, key.1, (key.2).0);
+            Some(entry.tile.clone())
+        } else {
+            self.stats.misses += 1;
+            None
+        }
+    }
+
+    // Queue up the neighbors of tile_id to be fetched when idle
+    fn prefetch_slot_tiles(&mut self, entry_id: &EntryID, tile_id: TileID) {
+        if self.prefetch_radius == 0 {
+            return;
+        }
+
+        let bounds = *self
+            .bounds
+            .get_or_insert_with(|| self.data_source.interval());
+        let step = tile_id.0.duration_ns();
+        let radius = self.prefetch_radius as i64;
+        for i in (-radius..=radius).filter(|i| *i != 0) {
+            let neighbor = TileID(tile_id.0.shifted(Timestamp(i * step)));
+            if !bounds.overlaps(neighbor.0) {
+                continue;
+            }
+            let key = (TileKind::Slot, entry_id.clone(), neighbor);
+            if self.entries.contains_key(&key) {
+                continue;
+            }
+            self.queued_prefetches
+                .push_back((entry_id.clone(), neighbor));
+        }
+        while self.queued_prefetches.len() > MAX_QUEUED_PREFETCHES {
+            self.queued_prefetches.pop_front();
+        }
+    }
+
+    // A failed fetch comes back as an empty tile, which mustn't be cached or
+    // it would be served for the rest of the session
+    fn failed(&mut self, entry_id: &EntryID, tile_id: TileID) -> bool {
+        let errors = self.data_source.get_errors();
+        let failed = errors
+            .iter()
+            .any(|error| error.entry_id == *entry_id && error.tile_id == tile_id);
+        self.errors.extend(errors);
+        failed
+    }
+
+    fn insert(&mut self, key: CacheKey, tile: CachedTile) {
+        let bytes = tile.bytes();
+        if bytes > self.byte_budget {
+            // Would evict everything else and still not fit
+            return;
+        }
+
+        self.tick += 1;
+        let entry = CacheEntry {
+            tile,
+            bytes,
+            last_used: self.tick,
+        };
+        self.lru.insert(entry.last_used, key.clone());
+        self.bytes += entry.bytes;
+        if let Some(old) = self.entries.insert(key, entry) {
+            self.lru.remove(&old.last_used);
+            self.bytes -= old.bytes;
+        }
+
+        while self.bytes > self.byte_budget {
+            let oldest = *self.lru.keys().next().unwrap();
+            let key = self.lru.remove(&oldest).unwrap();
+            let entry = self.entries.remove(&key).unwrap();
+            self.bytes -= entry.bytes;
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+impl CachedTile {
+    // This is an estimate of the in-memory size, not the serialized size
+    fn bytes(&self) -> usize {
+        match self {
+            CachedTile::Summary(tile) => {
+                size_of::<SummaryTile>() + tile.utilization.len() * size_of::<UtilPoint>()
+            }
+            CachedTile::Slot(tile) => {
+                size_of::<SlotTile>()
+                    + tile
+                        .items
+                        .iter()
+                        .map(|row| size_of::<Vec<Item>>() + row.len() * size_of::<Item>())
+                        .sum::<usize>()
+            }
+            CachedTile::SlotMeta(tile) => {
+                size_of::<SlotMetaTile>()
+                    + tile
+                        .items
+                        .iter()
+                        .flatten()
+                        .map(|item| {
+                            size_of::<ItemMeta>()
+                                + item.title.len()
+                                + item
+                                    .fields
+                                    .iter()
+                                    .map(|(name, _)| name.len())
+                                    .sum::<usize>()
+                        })
+                        .sum::<usize>()
+            }
+        }
+    }
+}
+
+impl DataSource for CachingDataSource {
+    fn interval(&mut self) -> Interval {
+        self.data_source.interval()
+    }
+
+    fn fetch_info(&mut self) -> EntryInfo {
+        self.data_source.fetch_info()
+    }
+
+    fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+        self.data_source.request_tiles(entry_id, request_interval)
+    }
+
+    fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+        let key = (TileKind::Summary, entry_id.clone(), tile_id);
+        if let Some(CachedTile::Summary(tile)) = self.lookup(&key) {
+            return tile;
+        }
+        let tile = self.data_source.fetch_summary_tile(entry_id, tile_id);
+        if !self.failed(entry_id, tile_id) {
+            self.insert(key, CachedTile::Summary(tile.clone()));
+        }
+        tile
+    }
+
+    fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+        let key = (TileKind::Slot, entry_id.clone(), tile_id);
+        let tile = if let Some(CachedTile::Slot(tile)) = self.lookup(&key) {
+            tile
+        } else {
+            let tile = self.data_source.fetch_slot_tile(entry_id, tile_id);
+            if !self.failed(entry_id, tile_id) {
+                self.insert(key, CachedTile::Slot(tile.clone()));
+            }
+            tile
+        };
+        self.prefetch_slot_tiles(entry_id, tile_id);
+        tile
+    }
+
+    fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+        let key = (TileKind::SlotMeta, entry_id.clone(), tile_id);
+        if let Some(CachedTile::SlotMeta(tile)) = self.lookup(&key) {
+            return tile;
+        }
+        let tile = self.data_source.fetch_slot_meta_tile(entry_id, tile_id);
+        if !self.failed(entry_id, tile_id) {
+            self.insert(key, CachedTile::SlotMeta(tile.clone()));
+        }
+        tile
+    }
+
+    fn last_error(&self) -> Option<String> {
+        self.data_source.last_error()
+    }
+
+    fn get_errors(&mut self) -> Vec<DataSourceError> {
+        let mut errors = std::mem::take(&mut self.errors);
+        errors.extend(self.data_source.get_errors());
+        errors
+    }
+
+    fn time_origin(&mut self) -> TimeOrigin {
+        self.data_source.time_origin()
+    }
+
+    fn tile_byte_estimate(&mut self, entry_id: &EntryID, tile_id: TileID) -> Option<usize> {
+        self.data_source.tile_byte_estimate(entry_id, tile_id)
+    }
+
+    fn set_summary_samples(&mut self, samples: usize) {
+        self.data_source.set_summary_samples(samples)
+    }
+
+    // Does one queued prefetch, if any
+    fn idle(&mut self) -> bool {
+        if let Some((entry_id, tile_id)) = self.queued_prefetches.pop_front() {
+            let key = (TileKind::Slot, entry_id, tile_id);
+            if !self.entries.contains_key(&key) {
+                let tile = self.data_source.fetch_slot_tile(&key.1, tile_id);
+                self.stats.prefetches += 1;
+                if !self.failed(&key.1, tile_id) {
+                    self.insert(key, CachedTile::Slot(tile));
+                }
+            }
+            return true;
+        }
+        self.data_source.idle()
+    }
+}
+
+pub struct CacheLayer {
+    pub byte_budget: usize,
+    pub prefetch_radius: usize,
+}
+
+impl CacheLayer {
+    pub fn new(byte_budget: usize) -> Self {
+        Self {
+            byte_budget,
+            prefetch_radius: 0,
+        }
+    }
+
+    pub fn with_prefetch_radius(mut self, prefetch_radius: usize) -> Self {
+        self.prefetch_radius = prefetch_radius;
+        self
+    }
+}
+
+impl DataSourceLayer for CacheLayer {
+    fn wrap(self, inner: Box<dyn DataSource>) -> Box<dyn DataSource> {
+        let mut data_source = CachingDataSource::new(inner, self.byte_budget);
+        data_source.prefetch_radius = self.prefetch_radius;
+        Box::new(data_source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use crate::mock::tests::{interval, sample, slot_id};
+    use crate::mock::MockDataSource;
+
+    #[test]
+    fn prefetches_wait_for_idle() {
+        let fetches = Rc::new(Cell::new(0));
+        let source = CountingDataSource(sample(), fetches.clone());
+        let mut cache = CachingDataSource::new(Box::new(source), 1 << 20);
+        cache.prefetch_radius = 2;
+        let tile_id = |start: i64| TileID(Interval::new(Timestamp(start), Timestamp(start + 100)));
+
+        cache.fetch_slot_tile(&slot_id(), tile_id(400));
+        assert_eq!(cache.cache_stats().prefetches, 0);
+        assert_eq!(fetches.get(), 1);
+
+        // Two tiles on either side
+        while cache.idle() {}
+        assert_eq!(cache.cache_stats().prefetches, 4);
+        assert_eq!(fetches.get(), 5);
+        let hits = cache.cache_stats().hits;
+        for start in [200, 300, 500, 600] {
+            cache.fetch_slot_tile(&slot_id(), tile_id(start));
+        }
+        assert_eq!(cache.cache_stats().hits, hits + 4);
+        assert_eq!(fetches.get(), 5);
+    }
+
+    // Counts slot tile fetches that reach the wrapped data source
+    struct CountingDataSource(MockDataSource, Rc<Cell<usize>>);
+
+    impl DataSource for CountingDataSource {
+        fn interval(&mut self) -> Interval {
+            self.0.interval()
+        }
+        fn fetch_info(&mut self) -> EntryInfo {
+            self.0.fetch_info()
+        }
+        fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+            self.0.request_tiles(entry_id, request_interval)
+        }
+        fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+            self.0.fetch_summary_tile(entry_id, tile_id)
+        }
+        fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+            self.1.set(self.1.get() + 1);
+            self.0.fetch_slot_tile(entry_id, tile_id)
+        }
+        fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+            self.0.fetch_slot_meta_tile(entry_id, tile_id)
+        }
+    }
+
+    #[test]
+    fn repeated_fetches_are_served_from_cache() {
+        let fetches = Rc::new(Cell::new(0));
+        let source = CountingDataSource(sample(), fetches.clone());
+        let mut cache = CachingDataSource::new(Box::new(source), 1 << 20);
+        let tile_id = TileID(interval());
+
+        let first = cache.fetch_slot_tile(&slot_id(), tile_id);
+        let second = cache.fetch_slot_tile(&slot_id(), tile_id);
+        assert_eq!(fetches.get(), 1);
+        assert_eq!(first.items[0].len(), second.items[0].len());
+        let stats = cache.cache_stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (1, 1, 0));
+    }
+
+    #[test]
+    fn least_recently_used_tiles_are_evicted() {
+        let fetches = Rc::new(Cell::new(0));
+        let source = CountingDataSource(sample(), fetches.clone());
+        // Only room for one empty tile
+        let mut cache = CachingDataSource::new(Box::new(source), size_of::<SlotTile>());
+        let tile_id = |start: i64| TileID(Interval::new(Timestamp(start), Timestamp(start + 100)));
+
+        cache.fetch_slot_tile(&slot_id(), tile_id(0));
+        cache.fetch_slot_tile(&slot_id(), tile_id(100));
+        assert_eq!(cache.cache_stats().evictions, 1);
+
+        cache.fetch_slot_tile(&slot_id(), tile_id(100));
+        assert_eq!(fetches.get(), 2);
+        cache.fetch_slot_tile(&slot_id(), tile_id(0));
+        assert_eq!(fetches.get(), 3);
+        let stats = cache.cache_stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (1, 3, 2));
+    }
+
+    // Fails the first `failures` slot tile fetches, reporting each through
+    // get_errors like the HTTP client does
+    struct FlakyDataSource {
+        source: MockDataSource,
+        failures: usize,
+        errors: Vec<DataSourceError>,
+    }
+
+    impl DataSource for FlakyDataSource {
+        fn interval(&mut self) -> Interval {
+            self.source.interval()
+        }
+        fn fetch_info(&mut self) -> EntryInfo {
+            self.source.fetch_info()
+        }
+        fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+            self.source.request_tiles(entry_id, request_interval)
+        }
+        fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+            self.source.fetch_summary_tile(entry_id, tile_id)
+        }
+        fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+            if self.failures == 0 {
+                return self.source.fetch_slot_tile(entry_id, tile_id);
+            }
+            self.failures -= 1;
+            self.errors.push(DataSourceError {
+                entry_id: entry_id.clone(),
+                tile_id,
+                message: "connection reset".to_owned(),
+            });
+            SlotTile {
+                tile_id,
+                items: Vec::new(),
+            }
+        }
+        fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+            self.source.fetch_slot_meta_tile(entry_id, tile_id)
+        }
+        fn get_errors(&mut self) -> Vec<DataSourceError> {
+            std::mem::take(&mut self.errors)
+        }
+    }
+
+    #[test]
+    fn failed_fetches_are_not_cached() {
+        let source = FlakyDataSource {
+            source: sample(),
+            failures: 1,
+            errors: Vec::new(),
+        };
+        let mut cache = CachingDataSource::new(Box::new(source), 1 << 20);
+        let tile_id = TileID(interval());
+
+        let failed = cache.fetch_slot_tile(&slot_id(), tile_id);
+        assert!(failed.items.is_empty());
+        let errors = cache.get_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].tile_id, tile_id);
+        assert!(cache.get_errors().is_empty());
+
+        // The retry goes back to the data source instead of the cache
+        let retried = cache.fetch_slot_tile(&slot_id(), tile_id);
+        assert!(!retried.items.is_empty());
+        assert_eq!(cache.cache_stats().hits, 0);
+        cache.fetch_slot_tile(&slot_id(), tile_id);
+        assert_eq!(cache.cache_stats().hits, 1);
+    }
+}
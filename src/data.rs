@@ -1,8 +1,14 @@
 pub use egui::{Color32, Rgba};
-use serde::{Deserialize, Serialize};
-use std::collections::BTreeSet;
+use fnv::FnvHasher;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::future::Future;
+use std::hash::Hasher;
+use std::pin::Pin;
+use std::str::FromStr;
 
-use crate::timestamp::{Interval, Timestamp};
+use crate::timestamp::{human_readable, Interval, Timestamp};
 
 // We encode EntryID as i64 because it allows us to pack Summary into the
 // value -1. Users shouldn't need to know about this and interact through the
@@ -16,6 +22,28 @@ pub enum EntryIndex {
     Slot(u64),
 }
 
+// What a slot represents, so that the app can style it accordingly
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum EntryKind {
+    #[default]
+    Other,
+    Processor,
+    Memory,
+    Channel,
+}
+
+impl EntryKind {
+    // For headings, e.g., "Node 0 CPU (processors)"
+    pub fn plural_name(self) -> &'static str {
+        match self {
+            EntryKind::Other => "other",
+            EntryKind::Processor => "processors",
+            EntryKind::Memory => "memories",
+            EntryKind::Channel => "channels",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum EntryInfo {
     Panel {
@@ -28,6 +56,9 @@ pub enum EntryInfo {
         short_name: String,
         long_name: String,
         max_rows: u64,
+        // Missing from profiles saved before kinds were added
+        #[serde(default)]
+        kind: EntryKind,
     },
     Summary {
         color: Color32,
@@ -87,12 +118,187 @@ pub struct SlotTile {
     pub items: Vec<Vec<Item>>, // row -> [item]
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+// Serialized with each distinct title and field name stored once, see
+// InternedSlotMetaTile
+#[derive(Debug, Clone)]
 pub struct SlotMetaTile {
     pub tile_id: TileID,
     pub items: Vec<Vec<ItemMeta>>, // row -> [item]
 }
 
+// The serialized form of SlotMetaTile. Titles and field names repeat for
+// every instance of a task, so they're replaced by indices into a table of
+// distinct strings. S and F are borrowed when serializing.
+#[derive(Deserialize, Serialize)]
+struct InternedSlotMetaTile<S, F> {
+    tile_id: TileID,
+    strings: Vec<S>,
+    items: Vec<Vec<InternedItemMeta<F>>>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct InternedItemMeta<F> {
+    item_uid: ItemUID,
+    title: u32,
+    fields: Vec<(u32, F)>,
+}
+
+// The form used before strings were interned, e.g., in saved profiles
+#[derive(Deserialize)]
+struct FlatSlotMetaTile {
+    tile_id: TileID,
+    items: Vec<Vec<ItemMeta>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AnySlotMetaTile {
+    Interned(InternedSlotMetaTile<String, Field>),
+    Flat(FlatSlotMetaTile),
+}
+
+impl<'a> InternedSlotMetaTile<&'a str, &'a Field> {
+    fn new(tile: &'a SlotMetaTile) -> Self {
+        let mut strings = Vec::new();
+        let mut indices = BTreeMap::new();
+        let mut intern = |string: &'a str| {
+            *indices.entry(string).or_insert_with(|| {
+                strings.push(string);
+                (strings.len() - 1) as u32
+            })
+        };
+        let items = tile
+            .items
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|item| InternedItemMeta {
+                        item_uid: item.item_uid,
+                        title: intern(&item.title),
+                        fields: item
+                            .fields
+                            .iter()
+                            .map(|(name, field)| (intern(name), field))
+                            .collect(),
+                    })
+                    .collect()
+            })
+            .collect();
+        Self {
+            tile_id: tile.tile_id,
+            strings,
+            items,
+        }
+    }
+}
+
+impl InternedSlotMetaTile<String, Field> {
+    fn into_tile(self) -> Result<SlotMetaTile, String> {
+        let strings = self.strings;
+        let lookup = |index: u32| {
+            strings
+                .get(index as usize)
+                .cloned()
+                .ok_or_else(|| format!("string index {index} out of range"))
+        };
+        let items = self
+            .items
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|item| {
+                        Ok(ItemMeta {
+                            item_uid: item.item_uid,
+                            title: lookup(item.title)?,
+                            fields: item
+                                .fields
+                                .into_iter()
+                                .map(|(name, field)| Ok((lookup(name)?, field)))
+                                .collect::<Result<_, String>>()?,
+                        })
+                    })
+                    .collect()
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(SlotMetaTile {
+            tile_id: self.tile_id,
+            items,
+        })
+    }
+}
+
+impl Serialize for SlotMetaTile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        InternedSlotMetaTile::new(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SlotMetaTile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Telling the forms apart needs a self-describing format. Binary
+        // formats (i.e., bincode) are only exchanged between a client and a
+        // server built together, or by the disk cache, which drops tiles it
+        // can't read.
+        let interned = if deserializer.is_human_readable() {
+            match AnySlotMetaTile::deserialize(deserializer)? {
+                AnySlotMetaTile::Interned(tile) => tile,
+                AnySlotMetaTile::Flat(tile) => {
+                    return Ok(SlotMetaTile {
+                        tile_id: tile.tile_id,
+                        items: tile.items,
+                    })
+                }
+            }
+        } else {
+            InternedSlotMetaTile::deserialize(deserializer)?
+        };
+        interned.into_tile().map_err(de::Error::custom)
+    }
+}
+
+// Utilization of b minus utilization of a, over the same tile
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SummaryTileDiff {
+    pub tile_id: TileID,
+    pub deltas: Vec<UtilPoint>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DataSourceError {
+    pub entry_id: EntryID,
+    pub tile_id: TileID,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SearchHit {
+    pub entry_id: EntryID,
+    // Set when the hit is an item rather than the entry itself
+    pub interval: Option<Interval>,
+}
+
+// What timestamp 0 of a DataSource means, since different backends count
+// from different epochs (e.g., process start, or the GPU clock)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TimeOrigin {
+    pub epoch: String,
+    // Wall-clock time of timestamp 0, in ns since the Unix epoch, if known.
+    // Sources that know it can be lined up with each other.
+    pub wall_clock: Option<Timestamp>,
+}
+
+impl Default for TimeOrigin {
+    fn default() -> Self {
+        Self {
+            epoch: "start of profile".to_owned(),
+            wall_clock: None,
+        }
+    }
+}
+
+// Bump whenever the layout of export_manifest changes
+pub const MANIFEST_VERSION: u32 = 2;
+
 pub trait DataSource {
     fn interval(&mut self) -> Interval;
     fn fetch_info(&mut self) -> EntryInfo;
@@ -100,6 +306,410 @@ pub trait DataSource {
     fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile;
     fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile;
     fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile;
+
+    // Like fetch_slot_tile, but with only the items overlapping clip, e.g.,
+    // the visible part of the tile. Sources that can (e.g., servers) send
+    // just those.
+    fn fetch_clipped_slot_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Interval,
+    ) -> SlotTile {
+        clip_slot_tile(&self.fetch_slot_tile(entry_id, tile_id), clip)
+    }
+
+    // The metas of the items fetch_clipped_slot_tile returns for clip
+    fn fetch_clipped_slot_meta_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Interval,
+    ) -> SlotMetaTile {
+        let tile = self.fetch_slot_tile(entry_id, tile_id);
+        let meta_tile = self.fetch_slot_meta_tile(entry_id, tile_id);
+        clip_slot_meta_tile(&tile, &meta_tile, clip)
+    }
+
+    // Sources that can fail (e.g., over the network) return empty results on
+    // failure. This reports what went wrong most recently.
+    fn last_error(&self) -> Option<String> {
+        None
+    }
+
+    // Tile fetches that failed since the last call, oldest first
+    fn get_errors(&mut self) -> Vec<DataSourceError> {
+        Vec::new()
+    }
+
+    fn time_origin(&mut self) -> TimeOrigin {
+        TimeOrigin::default()
+    }
+
+    // Serialized size of the tile fetch_summary_tile (for summaries) or
+    // fetch_slot_tile (for slots) would return, without fetching it, e.g.,
+    // to plan prefetching within a byte budget. None if unknown.
+    fn tile_byte_estimate(&mut self, _entry_id: &EntryID, _tile_id: TileID) -> Option<usize> {
+        None
+    }
+
+    // Summary tiles are drawn samples points wide (e.g., the width of the
+    // plot in pixels), so sources that can resample them needn't send more
+    // points than that
+    fn set_summary_samples(&mut self, _samples: usize) {}
+
+    // Called when there's nothing else to fetch, for work that shouldn't hold
+    // up fetches that were asked for (e.g., prefetching). Does a bounded
+    // amount of it and returns whether there's more to do.
+    fn idle(&mut self) -> bool {
+        false
+    }
+
+    // The summary tiles of entry_id overlapping interval. Sources that fetch
+    // in the background (e.g., DeferredDataSource) return None rather than
+    // waiting for them, so poll again (e.g., next frame) until they're ready.
+    fn poll_summary_tiles(
+        &mut self,
+        entry_id: &EntryID,
+        interval: Interval,
+    ) -> Option<Vec<SummaryTile>> {
+        let tile_ids = self.request_tiles(entry_id, interval);
+        let tiles = tile_ids
+            .into_iter()
+            .map(|tile_id| self.fetch_summary_tile(entry_id, tile_id))
+            .collect();
+        Some(tiles)
+    }
+
+    // Like poll_summary_tiles, for the slot tiles of entry_id, clipped to
+    // interval (see fetch_clipped_slot_tile)
+    fn poll_slot_tiles(&mut self, entry_id: &EntryID, interval: Interval) -> Option<Vec<SlotTile>> {
+        let tile_ids = self.request_tiles(entry_id, interval);
+        let tiles = tile_ids
+            .into_iter()
+            .map(|tile_id| self.fetch_clipped_slot_tile(entry_id, tile_id, interval))
+            .collect();
+        Some(tiles)
+    }
+
+    // Like poll_summary_tiles, for the metas of a single slot tile polled
+    // with clip
+    fn poll_slot_meta_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Interval,
+    ) -> Option<SlotMetaTile> {
+        Some(self.fetch_clipped_slot_meta_tile(entry_id, tile_id, clip))
+    }
+
+    // Sources that fetch in the background call on_fetched whenever a fetch
+    // started by the poll_* methods completes (or fails), so that callers
+    // (e.g., the UI) can wait for that rather than polling again and again.
+    // Returns whether this source will.
+    fn notify_on_fetch(&mut self, _on_fetched: Box<dyn Fn() + Send + Sync>) -> bool {
+        false
+    }
+
+    // Background fetches started by the poll_* methods that haven't begun
+    // yet are no longer wanted, e.g., the view moved on. Polling for them
+    // again (e.g., they're still visible) starts them over.
+    fn cancel_queued(&mut self) {}
+
+    // Case-insensitive substring match over entry names and item titles.
+    // Note: this fetches every slot meta tile in the profile, so sources
+    // that can search more cheaply should override it.
+    fn search(&mut self, query: &str) -> Vec<SearchHit> {
+        let query = query.to_lowercase();
+        let interval = self.interval();
+        let info = self.fetch_info();
+
+        let mut result = Vec::new();
+        let mut stack = vec![(EntryID::root(), &info)];
+        while let Some((entry_id, entry)) = stack.pop() {
+            match entry {
+                EntryInfo::Panel {
+                    short_name,
+                    long_name,
+                    slots,
+                    ..
+                } => {
+                    if entry_id.level() > 0
+                        && (short_name.to_lowercase().contains(&query)
+                            || long_name.to_lowercase().contains(&query))
+                    {
+                        result.push(SearchHit {
+                            entry_id: entry_id.clone(),
+                            interval: None,
+                        });
+                    }
+                    // Reversed so that entries are visited in order
+                    for (i, slot) in slots.iter().enumerate().rev() {
+                        stack.push((entry_id.child(i as u64), slot));
+                    }
+                }
+                EntryInfo::Slot {
+                    short_name,
+                    long_name,
+                    ..
+                } => {
+                    if short_name.to_lowercase().contains(&query)
+                        || long_name.to_lowercase().contains(&query)
+                    {
+                        result.push(SearchHit {
+                            entry_id: entry_id.clone(),
+                            interval: None,
+                        });
+                    }
+                    for tile_id in self.request_tiles(&entry_id, interval) {
+                        // Item metadata has no interval, so pair it up with
+                        // the corresponding item in the slot tile
+                        let tile = self.fetch_slot_tile(&entry_id, tile_id);
+                        let meta_tile = self.fetch_slot_meta_tile(&entry_id, tile_id);
+                        for (row, meta_row) in tile.items.iter().zip(&meta_tile.items) {
+                            for (item, meta) in row.iter().zip(meta_row) {
+                                if meta.title.to_lowercase().contains(&query) {
+                                    result.push(SearchHit {
+                                        entry_id: entry_id.clone(),
+                                        interval: Some(item.interval),
+                                    });
+                                }
+                            }
+                        }
+                    }
+                }
+                EntryInfo::Summary { .. } => {}
+            }
+        }
+        result
+    }
+
+    // The innermost (i.e., shortest) item of slot entry_id containing at,
+    // e.g., for a tooltip. Sources that can look up a single item without
+    // fetching whole tiles should override this.
+    fn probe(&mut self, entry_id: &EntryID, at: Timestamp) -> Option<ItemMeta> {
+        // Nothing lies outside the profile. This also keeps at + 1 from
+        // overflowing.
+        if !self.interval().contains(at) {
+            return None;
+        }
+        let point = Interval::new(at, Timestamp(at.0 + 1));
+        let mut result: Option<(i64, ItemMeta)> = None;
+        for tile_id in self.request_tiles(entry_id, point) {
+            // Item metadata has no interval, so pair it up with the
+            // corresponding item in the slot tile
+            let tile = self.fetch_slot_tile(entry_id, tile_id);
+            let meta_tile = self.fetch_slot_meta_tile(entry_id, tile_id);
+            for (row, meta_row) in tile.items.iter().zip(&meta_tile.items) {
+                for (item, meta) in row.iter().zip(meta_row) {
+                    let duration = item.interval.duration_ns();
+                    if item.interval.contains(at)
+                        && result.as_ref().map_or(true, |(best, _)| duration < *best)
+                    {
+                        result = Some((duration, meta.clone()));
+                    }
+                }
+            }
+        }
+        result.map(|(_, meta)| meta)
+    }
+
+    // The first task of slot entry_id that starts after t, skipping any
+    // gap, or None if there is none. Items split across tiles are stitched
+    // back together so the whole task is returned.
+    fn next_task_after(&mut self, entry_id: &EntryID, t: Timestamp) -> Option<Interval> {
+        let interval = self.interval();
+        if t >= interval.stop {
+            return None;
+        }
+        let first_after = |tasks: &BTreeMap<ItemUID, Interval>| {
+            tasks
+                .values()
+                .filter(|task| task.start > t)
+                .min_by_key(|task| task.start)
+                .copied()
+        };
+        let mut tasks = BTreeMap::new();
+        for tile_id in self.request_tiles(entry_id, Interval::new(t, interval.stop)) {
+            stitch_tasks(&mut tasks, &self.fetch_slot_tile(entry_id, tile_id));
+            // Later tiles can only add tasks that start later, unless the
+            // best one so far continues into them
+            if let Some(task) = first_after(&tasks) {
+                if task.stop < tile_id.0.stop {
+                    return Some(task);
+                }
+            }
+        }
+        first_after(&tasks)
+    }
+
+    // The last task of slot entry_id that ends before t. See next_task_after.
+    fn prev_task_before(&mut self, entry_id: &EntryID, t: Timestamp) -> Option<Interval> {
+        let interval = self.interval();
+        if t <= interval.start {
+            return None;
+        }
+        let last_before = |tasks: &BTreeMap<ItemUID, Interval>| {
+            tasks
+                .values()
+                .filter(|task| task.stop <= t)
+                .max_by_key(|task| task.start)
+                .copied()
+        };
+        let mut tasks = BTreeMap::new();
+        let tile_ids = self.request_tiles(entry_id, Interval::new(interval.start, t));
+        for tile_id in tile_ids.into_iter().rev() {
+            stitch_tasks(&mut tasks, &self.fetch_slot_tile(entry_id, tile_id));
+            if let Some(task) = last_before(&tasks) {
+                if task.start > tile_id.0.start {
+                    return Some(task);
+                }
+            }
+        }
+        last_before(&tasks)
+    }
+
+    // Snapshot of everything the viewer can see: the entry hierarchy, the
+    // interval, and the tiles covering that interval for every summary and
+    // slot. The document can also be used as the manifest of a
+    // FileDataSource.
+    fn export_manifest(&mut self) -> serde_json::Value {
+        let interval = self.interval();
+        let info = self.fetch_info();
+
+        let mut tiles = Vec::new();
+        let mut stack = vec![(EntryID::root(), &info)];
+        while let Some((entry_id, entry)) = stack.pop() {
+            match entry {
+                EntryInfo::Panel { summary, slots, .. } => {
+                    for (i, slot) in slots.iter().enumerate().rev() {
+                        stack.push((entry_id.child(i as u64), slot));
+                    }
+                    if let Some(summary) = summary {
+                        stack.push((entry_id.summary(), summary));
+                    }
+                }
+                EntryInfo::Slot { .. } | EntryInfo::Summary { .. } => {
+                    let tile_ids = self.request_tiles(&entry_id, interval);
+                    tiles.push((entry_id, tile_ids));
+                }
+            }
+        }
+
+        serde_json::json!({
+            "version": MANIFEST_VERSION,
+            "info": info,
+            // Written in human-readable form, since manifests are meant to be
+            // inspected by hand
+            "interval": {
+                "start": human_readable::to_string(interval.start),
+                "stop": human_readable::to_string(interval.stop),
+            },
+            "time_origin": self.time_origin(),
+            "tiles": tiles,
+        })
+    }
+
+    // Compare a summary tile with the same tile of another source, e.g.,
+    // another run of the same program
+    fn diff_against(
+        &mut self,
+        other: &mut dyn DataSource,
+        entry_id: &EntryID,
+        tile_id: TileID,
+    ) -> SummaryTileDiff {
+        let a = self.fetch_summary_tile(entry_id, tile_id);
+        let b = other.fetch_summary_tile(entry_id, tile_id);
+        diff_summary(&a, &b)
+    }
+}
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+// Non-blocking counterpart of DataSource, for sources that do I/O (e.g.,
+// the HTTP server, which would otherwise block a worker per request).
+// Methods take &self so that requests can be served concurrently.
+pub trait AsyncDataSource {
+    fn interval(&self) -> BoxFuture<'_, Interval>;
+    fn fetch_info(&self) -> BoxFuture<'_, EntryInfo>;
+    fn request_tiles<'a>(
+        &'a self,
+        entry_id: &'a EntryID,
+        request_interval: Interval,
+    ) -> BoxFuture<'a, Vec<TileID>>;
+    fn fetch_summary_tile<'a>(
+        &'a self,
+        entry_id: &'a EntryID,
+        tile_id: TileID,
+    ) -> BoxFuture<'a, SummaryTile>;
+    fn fetch_slot_tile<'a>(
+        &'a self,
+        entry_id: &'a EntryID,
+        tile_id: TileID,
+    ) -> BoxFuture<'a, SlotTile>;
+    fn fetch_slot_meta_tile<'a>(
+        &'a self,
+        entry_id: &'a EntryID,
+        tile_id: TileID,
+    ) -> BoxFuture<'a, SlotMetaTile>;
+    fn probe<'a>(&'a self, entry_id: &'a EntryID, at: Timestamp)
+        -> BoxFuture<'a, Option<ItemMeta>>;
+    fn time_origin(&self) -> BoxFuture<'_, TimeOrigin>;
+    fn tile_byte_estimate<'a>(
+        &'a self,
+        entry_id: &'a EntryID,
+        tile_id: TileID,
+    ) -> BoxFuture<'a, Option<usize>>;
+}
+
+impl fmt::Display for TimeOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.epoch)?;
+        if let Some(wall_clock) = self.wall_clock {
+            let secs = wall_clock.0.div_euclid(1_000_000_000);
+            let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+            let secs = secs.rem_euclid(86_400);
+            write!(
+                f,
+                " ({year}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC)",
+                secs / 3600,
+                secs / 60 % 60,
+                secs % 60
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl TimeOrigin {
+    // Wall-clock time of t, in ns since the Unix epoch, if known
+    pub fn wall_clock_at(&self, t: Timestamp) -> Option<Timestamp> {
+        self.wall_clock
+            .map(|wall_clock| Timestamp(wall_clock.0.saturating_add(t.0)))
+    }
+}
+
+// None if origin has no wall-clock time
+#[cfg(feature = "chrono")]
+pub fn absolute_time(t: Timestamp, origin: &TimeOrigin) -> Option<chrono::DateTime<chrono::Utc>> {
+    use chrono::TimeZone;
+    let wall_clock = origin.wall_clock_at(t)?;
+    Some(chrono::Utc.timestamp_nanos(wall_clock.0))
+}
+
+// Days since 1970-01-01 to (year, month, day) in the proleptic Gregorian
+// calendar, from http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
 }
 
 impl EntryID {
@@ -154,7 +764,431 @@ impl EntryID {
     }
 }
 
+// The tiles that share at least one nanosecond with bounds. A tile that
+// starts exactly at bounds.stop is not included.
+pub fn tiles_overlapping(level_tiles: &[TileID], bounds: Interval) -> Vec<TileID> {
+    level_tiles
+        .iter()
+        .filter(|tile_id| tile_id.0.overlaps(bounds))
+        .copied()
+        .collect()
+}
+
+// Expand visible outward to the boundaries of the tiles of the given level
+// of tile_set that it overlaps, e.g., to tell what was fetched for a view
+// beyond what's on screen. If no tile overlaps (or there is no such level),
+// visible is returned as is.
+pub fn snap_to_tiles(visible: Interval, tile_set: &[Vec<TileID>], level: usize) -> Interval {
+    let level_tiles = match tile_set.get(level) {
+        Some(level_tiles) => level_tiles,
+        None => return visible,
+    };
+    level_tiles
+        .iter()
+        .map(|tile_id| tile_id.0)
+        .filter(|tile| tile.overlaps(visible))
+        .reduce(Interval::union)
+        .unwrap_or(visible)
+}
+
+// Plain-text description of a task, e.g., for pasting into a bug report:
+// the title, the entry and interval it occupies, then one line per field
+pub fn format_task_details(item: &ItemMeta, interval: Interval, entry_id: &EntryID) -> String {
+    let mut result = format!("{}\nEntry: {entry_id}\nInterval: {interval}", item.title);
+    for (name, field) in &item.fields {
+        let line = match field {
+            Field::I64(value) => format!("{name}: {value}"),
+            Field::U64(value) => format!("{name}: {value}"),
+            Field::String(value) => format!("{name}: {value}"),
+            Field::Interval(value) => format!("{name}: {value}"),
+            Field::Empty => name.clone(),
+        };
+        result.push('\n');
+        result.push_str(&line);
+    }
+    result
+}
+
+// How aggregate_summary combines the points that fall in one sample
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Downsample {
+    // The average utilization (and time) of the points
+    Mean,
+    // The point of highest utilization, so that short peaks still show
+    Max,
+}
+
+// Combine tiles into one covering all of them with at most samples points,
+// e.g., to serve a coarse tile built from finer ones. Where several tiles
+// have a point at the same time (e.g., the curves of several entries), the
+// utilization is averaged. If that leaves more than samples points, the
+// tile is split into samples equal buckets, each reduced to one point.
+pub fn aggregate_summary(
+    tiles: &[SummaryTile],
+    samples: usize,
+    downsample: Downsample,
+) -> SummaryTile {
+    let tile_id = tiles
+        .iter()
+        .map(|tile| tile.tile_id.0)
+        .reduce(Interval::union)
+        .unwrap_or_default();
+
+    let mut points: Vec<_> = tiles
+        .iter()
+        .flat_map(|tile| tile.utilization.iter().copied())
+        .collect();
+    points.sort_by_key(|point| point.time);
+
+    let mut utilization: Vec<UtilPoint> = Vec::new();
+    let mut count = 0;
+    for point in points {
+        match utilization.last_mut() {
+            Some(last) if last.time == point.time => {
+                // Running mean of the points at this time
+                count += 1;
+                last.util += (point.util - last.util) / count as f32;
+            }
+            _ => {
+                utilization.push(point);
+                count = 1;
+            }
+        }
+    }
+
+    let samples = samples.max(1);
+    if utilization.len() > samples {
+        utilization = downsample_points(&utilization, tile_id, samples, downsample);
+    }
+
+    SummaryTile {
+        tile_id: TileID(tile_id),
+        utilization,
+    }
+}
+
+// points are sorted by time, and within interval. Empty buckets get no
+// point.
+fn downsample_points(
+    points: &[UtilPoint],
+    interval: Interval,
+    samples: usize,
+    downsample: Downsample,
+) -> Vec<UtilPoint> {
+    let duration = interval.duration_ns().max(1) as i128;
+    let bucket = |point: &UtilPoint| {
+        let offset = (point.time - interval.start).0 as i128;
+        ((offset * samples as i128 / duration) as usize).min(samples - 1)
+    };
+
+    let mut result = Vec::with_capacity(samples);
+    let mut rest = points;
+    while let Some(first) = rest.first() {
+        let index = bucket(first);
+        let len = rest.partition_point(|point| bucket(point) == index);
+        let (points, tail) = rest.split_at(len);
+        rest = tail;
+        result.push(match downsample {
+            Downsample::Mean => {
+                let n = points.len() as i128;
+                let time = points
+                    .iter()
+                    .map(|point| point.time.0 as i128)
+                    .sum::<i128>()
+                    / n;
+                let util = points.iter().map(|point| point.util).sum::<f32>() / n as f32;
+                UtilPoint {
+                    time: Timestamp(time as i64),
+                    util,
+                }
+            }
+            // The first of equally high points
+            Downsample::Max => points
+                .iter()
+                .copied()
+                .reduce(|max, point| if point.util > max.util { point } else { max })
+                .unwrap(),
+        });
+    }
+    result
+}
+
+// The curves are compared at the times of whichever one has fewer points,
+// interpolating the other linearly.
+pub fn diff_summary(a: &SummaryTile, b: &SummaryTile) -> SummaryTileDiff {
+    let (short, long, sign) = if a.utilization.len() <= b.utilization.len() {
+        (&a.utilization, &b.utilization, 1.0)
+    } else {
+        (&b.utilization, &a.utilization, -1.0)
+    };
+    let deltas = short
+        .iter()
+        .map(|point| UtilPoint {
+            time: point.time,
+            util: sign * (util_at(long, point.time) - point.util),
+        })
+        .collect();
+    SummaryTileDiff {
+        tile_id: a.tile_id,
+        deltas,
+    }
+}
+
+// Evenly spaced samples of the utilization across the tile, e.g., to match
+// the resolution of the display
+pub fn resample_summary(tile: &SummaryTile, samples: usize) -> SummaryTile {
+    if tile.utilization.is_empty() {
+        // Nothing to interpolate
+        return tile.clone();
+    }
+    let interval = tile.tile_id.0;
+    let utilization = (0..samples)
+        .map(|i| {
+            let time = if samples > 1 {
+                interval.lerp(i as f32 / (samples - 1) as f32)
+            } else {
+                interval.start
+            };
+            UtilPoint {
+                time,
+                util: util_at(&tile.utilization, time),
+            }
+        })
+        .collect();
+    SummaryTile {
+        tile_id: tile.tile_id,
+        utilization,
+    }
+}
+
+// Only the items overlapping clip, e.g., the visible part of the tile. Rows
+// are kept (if empty) so that row indices don't change.
+pub fn clip_slot_tile(tile: &SlotTile, clip: Interval) -> SlotTile {
+    SlotTile {
+        tile_id: tile.tile_id,
+        items: tile
+            .items
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .filter(|item| clip.overlaps(item.interval))
+                    .cloned()
+                    .collect()
+            })
+            .collect(),
+    }
+}
+
+// The metas of the items that clip_slot_tile keeps. The rows of meta_tile
+// must match those of tile item for item, as they do for any DataSource.
+pub fn clip_slot_meta_tile(
+    tile: &SlotTile,
+    meta_tile: &SlotMetaTile,
+    clip: Interval,
+) -> SlotMetaTile {
+    SlotMetaTile {
+        tile_id: meta_tile.tile_id,
+        items: tile
+            .items
+            .iter()
+            .zip(&meta_tile.items)
+            .map(|(row, meta_row)| {
+                row.iter()
+                    .zip(meta_row)
+                    .filter(|(item, _)| clip.overlaps(item.interval))
+                    .map(|(_, meta)| meta.clone())
+                    .collect()
+            })
+            .collect(),
+    }
+}
+
+// Adds the items of tile to tasks, joining pieces of the same item that
+// were cut at tile boundaries
+fn stitch_tasks(tasks: &mut BTreeMap<ItemUID, Interval>, tile: &SlotTile) {
+    for item in tile.items.iter().flatten() {
+        tasks
+            .entry(item.item_uid)
+            .and_modify(|task: &mut Interval| *task = task.union(item.interval))
+            .or_insert(item.interval);
+    }
+}
+
+// Drop items that stop before they start, e.g., from clock skew in the
+// profiler. Zero-duration items are kept: they're valid instants.
+pub fn sanitize_items(mut items: Vec<Item>) -> Vec<Item> {
+    let before = items.len();
+    items.retain(|item| item.interval.start <= item.interval.stop);
+    let dropped = before - items.len();
+    if dropped > 0 {
+        log::warn!("dropped {dropped} items with inverted intervals");
+    }
+    items
+}
+
+// Coalesce runs of consecutive items shorter than min_ns that share a color
+// (i.e., are of the same kind), e.g., so that sub-pixel items don't render
+// as noise. items and metas are corresponding rows of a SlotTile and
+// SlotMetaTile. A merged item spans its run and keeps the UID of the first
+// item in it.
+pub fn merge_small_items(
+    items: &[Item],
+    metas: &[ItemMeta],
+    min_ns: i64,
+) -> (Vec<Item>, Vec<ItemMeta>) {
+    let is_small = |item: &Item| item.interval.duration_ns() < min_ns;
+
+    let mut merged_items = Vec::new();
+    let mut merged_metas = Vec::new();
+    let mut i = 0;
+    while i < items.len() {
+        let first = &items[i];
+        let mut j = i + 1;
+        if is_small(first) {
+            while j < items.len() && is_small(&items[j]) && items[j].color == first.color {
+                j += 1;
+            }
+        }
+        if j - i == 1 {
+            merged_items.push(first.clone());
+            merged_metas.push(metas[i].clone());
+        } else {
+            let interval = Interval::new(first.interval.start, items[j - 1].interval.stop);
+            let busy: i64 = items[i..j].iter().map(|x| x.interval.duration_ns()).sum();
+            merged_items.push(Item {
+                item_uid: first.item_uid,
+                interval,
+                color: first.color,
+            });
+            merged_metas.push(ItemMeta {
+                item_uid: first.item_uid,
+                title: format!("merged ({} tasks)", j - i),
+                fields: vec![
+                    ("Interval".to_owned(), Field::Interval(interval)),
+                    (
+                        "Total Duration".to_owned(),
+                        Field::String(Timestamp(busy).to_string()),
+                    ),
+                ],
+            });
+        }
+        i = j;
+    }
+    (merged_items, merged_metas)
+}
+
+// Color for an item based only on its name, so that the same kind of task
+// gets the same color in every profile, regardless of the order in which
+// tasks are seen. Colors are drawn from the Tableau 10 palette.
+pub fn stable_color(name: &str) -> Color32 {
+    const PALETTE: [Color32; 10] = [
+        Color32::from_rgb(0x4e, 0x79, 0xa7),
+        Color32::from_rgb(0xf2, 0x8e, 0x2b),
+        Color32::from_rgb(0xe1, 0x57, 0x59),
+        Color32::from_rgb(0x76, 0xb7, 0xb2),
+        Color32::from_rgb(0x59, 0xa1, 0x4f),
+        Color32::from_rgb(0xed, 0xc9, 0x48),
+        Color32::from_rgb(0xb0, 0x7a, 0xa1),
+        Color32::from_rgb(0xff, 0x9d, 0xa7),
+        Color32::from_rgb(0x9c, 0x75, 0x5f),
+        Color32::from_rgb(0xba, 0xb0, 0xac),
+    ];
+    // Note: FNV rather than the std hasher, which is not guaranteed to be
+    // stable across builds
+    let mut hasher = FnvHasher::default();
+    hasher.write(name.as_bytes());
+    PALETTE[(hasher.finish() % PALETTE.len() as u64) as usize]
+}
+
+// Linear interpolation of a utilization curve, clamped at either end
+fn util_at(utilization: &[UtilPoint], time: Timestamp) -> f32 {
+    let next = utilization.partition_point(|point| point.time < time);
+    match (
+        next.checked_sub(1).map(|i| utilization[i]),
+        utilization.get(next).copied(),
+    ) {
+        (Some(prev), Some(next)) => {
+            let interval = Interval::new(prev.time, next.time);
+            prev.util + (next.util - prev.util) * interval.unlerp(time)
+        }
+        (Some(point), None) | (None, Some(point)) => point.util,
+        (None, None) => 0.0,
+    }
+}
+
+// Formats as the path of indices from the root, e.g., "0/2/summary". The
+// root is the empty string.
+impl fmt::Display for EntryID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for level in 0..self.level() {
+            if level > 0 {
+                write!(f, "/")?;
+            }
+            match self.index(level).unwrap() {
+                EntryIndex::Summary => write!(f, "summary")?,
+                EntryIndex::Slot(index) => write!(f, "{}", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for EntryID {
+    type Err = EntryIDParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = EntryID::root();
+        if s.is_empty() {
+            return Ok(result);
+        }
+        for index in s.split('/') {
+            result = match index {
+                "summary" => result.summary(),
+                _ => {
+                    // Must also fit in the i64 that EntryID stores
+                    let value: i64 = index
+                        .parse()
+                        .ok()
+                        .filter(|value| *value >= 0)
+                        .ok_or_else(|| EntryIDParseError::InvalidIndex(index.to_owned()))?;
+                    result.child(value as u64)
+                }
+            };
+        }
+        Ok(result)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryIDParseError {
+    InvalidIndex(String),
+}
+
+impl fmt::Display for EntryIDParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntryIDParseError::InvalidIndex(index) => write!(f, "invalid index: {index:?}"),
+        }
+    }
+}
+
 impl EntryInfo {
+    // A slot's kind, or for a panel, the kind all of its slots share
+    pub fn kind(&self) -> Option<EntryKind> {
+        match self {
+            EntryInfo::Slot { kind, .. } => Some(*kind),
+            EntryInfo::Panel { slots, .. } => {
+                let first = slots.first()?.kind()?;
+                if slots.iter().all(|slot| slot.kind() == Some(first)) {
+                    Some(first)
+                } else {
+                    None
+                }
+            }
+            EntryInfo::Summary { .. } => None,
+        }
+    }
+
     pub fn get(&self, entry_id: &EntryID) -> Option<&EntryInfo> {
         let mut result = self;
         for i in 0..entry_id.level() {
@@ -203,3 +1237,498 @@ impl EntryInfo {
         unreachable!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::server::tests::capture_logs;
+    use crate::mock::tests::{info, interval, item_interval, sample, slot_id, summary_id, ITEMS};
+    use crate::mock::MockDataSource;
+
+    fn item(uid: u64, start: i64, stop: i64) -> Item {
+        Item {
+            item_uid: ItemUID(uid),
+            interval: Interval::new(Timestamp(start), Timestamp(stop)),
+            color: Color32::RED,
+        }
+    }
+
+    fn meta(uid: u64) -> ItemMeta {
+        ItemMeta {
+            item_uid: ItemUID(uid),
+            title: format!("Task {uid}"),
+            fields: Vec::new(),
+        }
+    }
+
+    // Tasks 0 (the whole profile), 1 (200 to 800) and 2 (400 to 600) nest
+    // inside each other, one per row
+    fn nested() -> MockDataSource {
+        let tile_id = TileID(interval());
+        MockDataSource::new(info(), interval())
+            .with_slot_tile(
+                slot_id(),
+                SlotTile {
+                    tile_id,
+                    items: vec![
+                        vec![item(0, 0, 1000)],
+                        vec![item(1, 200, 800)],
+                        vec![item(2, 400, 600)],
+                    ],
+                },
+            )
+            .with_slot_meta_tile(
+                slot_id(),
+                SlotMetaTile {
+                    tile_id,
+                    items: vec![vec![meta(0)], vec![meta(1)], vec![meta(2)]],
+                },
+            )
+    }
+
+    fn probe_uid(source: &mut dyn DataSource, at: i64) -> Option<u64> {
+        source
+            .probe(&slot_id(), Timestamp(at))
+            .map(|meta| meta.item_uid.0)
+    }
+
+    #[test]
+    fn probe_returns_innermost_task() {
+        let mut source = nested();
+        assert_eq!(probe_uid(&mut source, 100), Some(0));
+        assert_eq!(probe_uid(&mut source, 300), Some(1));
+        assert_eq!(probe_uid(&mut source, 500), Some(2));
+        assert_eq!(probe_uid(&mut source, 600), Some(1));
+    }
+
+    #[test]
+    fn probe_outside_profile_is_none() {
+        let mut source = sample();
+        assert_eq!(probe_uid(&mut source, 70), None);
+        assert_eq!(probe_uid(&mut source, -1), None);
+        assert_eq!(probe_uid(&mut source, 1000), None);
+        assert_eq!(probe_uid(&mut source, i64::MAX), None);
+        assert_eq!(probe_uid(&mut source, i64::MIN), None);
+    }
+
+    #[test]
+    fn resample_summary_interpolates() {
+        let tile = sample().fetch_summary_tile(&summary_id(), TileID(interval()));
+        let resampled = resample_summary(&tile, 21);
+        assert_eq!(resampled.tile_id, tile.tile_id);
+        assert_eq!(resampled.utilization.len(), 21);
+        let first = resampled.utilization.first().unwrap();
+        let last = resampled.utilization.last().unwrap();
+        assert_eq!((first.time, last.time), (Timestamp(0), Timestamp(1000)));
+        // Halfway between 0 at 0 ns and 1 at 100 ns
+        assert_eq!(resampled.utilization[1].time, Timestamp(50));
+        assert!((resampled.utilization[1].util - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn snap_to_tiles_expands_to_tile_bounds() {
+        let span = |start: i64, stop: i64| Interval::new(Timestamp(start), Timestamp(stop));
+        let coarse = vec![TileID(span(0, 1000))];
+        let fine = (0..10)
+            .map(|i| TileID(span(i * 100, (i + 1) * 100)))
+            .collect();
+        let tile_set = vec![coarse, fine];
+
+        assert_eq!(snap_to_tiles(span(120, 180), &tile_set, 1), span(100, 200));
+        assert_eq!(snap_to_tiles(span(150, 250), &tile_set, 1), span(100, 300));
+        assert_eq!(snap_to_tiles(span(150, 250), &tile_set, 0), span(0, 1000));
+        // Nothing to snap to
+        assert_eq!(
+            snap_to_tiles(span(1200, 1300), &tile_set, 1),
+            span(1200, 1300)
+        );
+        assert_eq!(snap_to_tiles(span(150, 250), &tile_set, 2), span(150, 250));
+    }
+
+    fn search(source: &mut dyn DataSource, query: &str) -> Vec<(EntryID, Option<Interval>)> {
+        source
+            .search(query)
+            .into_iter()
+            .map(|hit| (hit.entry_id, hit.interval))
+            .collect()
+    }
+
+    #[test]
+    fn search_matches_entry_names() {
+        let mut source = sample();
+        let kind_id = EntryID::root().child(0).child(0);
+        assert_eq!(
+            search(&mut source, "cpu"),
+            vec![(kind_id, None), (slot_id(), None)]
+        );
+        assert_eq!(search(&mut source, "Cpu 0"), vec![(slot_id(), None)]);
+        assert!(search(&mut source, "gpu").is_empty());
+    }
+
+    #[test]
+    fn search_matches_item_titles() {
+        let mut source = sample();
+        assert_eq!(
+            search(&mut source, "TASK 3"),
+            vec![(slot_id(), Some(item_interval(3)))]
+        );
+        assert_eq!(search(&mut source, "task").len(), ITEMS as usize);
+    }
+
+    #[test]
+    fn manifest_lists_entries_and_tiles() {
+        let mut source = sample();
+        let manifest = source.export_manifest();
+        assert_eq!(manifest["version"], MANIFEST_VERSION);
+        let info: EntryInfo = serde_json::from_value(manifest["info"].clone()).unwrap();
+        assert_eq!(info.nodes(), 1);
+
+        let tiles: Vec<(EntryID, Vec<TileID>)> =
+            serde_json::from_value(manifest["tiles"].clone()).unwrap();
+        let entries: Vec<_> = tiles.iter().map(|(entry_id, _)| entry_id.clone()).collect();
+        assert_eq!(entries, vec![summary_id(), slot_id()]);
+        for (_, tile_ids) in &tiles {
+            assert_eq!(tile_ids, &vec![TileID(interval())]);
+        }
+    }
+
+    #[test]
+    fn entry_ids_round_trip_through_strings() {
+        let entry_ids = [
+            EntryID::root(),
+            slot_id(),
+            summary_id(),
+            EntryID::root().child(12).child(3),
+        ];
+        for entry_id in entry_ids {
+            assert_eq!(entry_id.to_string().parse(), Ok(entry_id));
+        }
+        assert_eq!(
+            "0/x".parse::<EntryID>(),
+            Err(EntryIDParseError::InvalidIndex("x".to_owned()))
+        );
+    }
+
+    #[test]
+    fn tiles_overlapping_excludes_tiles_at_the_stop() {
+        let span = |start: i64, stop: i64| TileID(Interval::new(Timestamp(start), Timestamp(stop)));
+        let tiles = vec![span(0, 100), span(100, 200), span(200, 300)];
+        let overlapping = |start, stop| tiles_overlapping(&tiles, span(start, stop).0);
+
+        // Ends exactly where the second tile starts
+        assert_eq!(overlapping(0, 100), vec![span(0, 100)]);
+        assert_eq!(overlapping(50, 200), vec![span(0, 100), span(100, 200)]);
+        // Starts exactly where the first tile stops
+        assert_eq!(overlapping(100, 150), vec![span(100, 200)]);
+        assert_eq!(overlapping(99, 101), vec![span(0, 100), span(100, 200)]);
+        assert!(overlapping(300, 400).is_empty());
+    }
+
+    #[test]
+    fn diff_resamples_to_the_shorter_tile() {
+        let tile_id = TileID(interval());
+        let point = |time, util| UtilPoint {
+            time: Timestamp(time),
+            util,
+        };
+        let coarse = SummaryTile {
+            tile_id,
+            utilization: vec![point(0, 0.5), point(250, 0.5), point(1000, 1.0)],
+        };
+        let mut other =
+            MockDataSource::new(info(), interval()).with_summary_tile(summary_id(), coarse);
+        let deltas = |diff: SummaryTileDiff| -> Vec<(i64, f32)> {
+            assert_eq!(diff.tile_id, tile_id);
+            diff.deltas
+                .iter()
+                .map(|point| (point.time.0, point.util))
+                .collect()
+        };
+
+        // sample alternates between 0 and 1 every 100 ns, so it is 0.5 at
+        // 250 ns by interpolation
+        let diff = sample().diff_against(&mut other, &summary_id(), tile_id);
+        assert_eq!(deltas(diff), vec![(0, 0.5), (250, 0.0), (1000, 1.0)]);
+        let diff = other.diff_against(&mut sample(), &summary_id(), tile_id);
+        assert_eq!(deltas(diff), vec![(0, -0.5), (250, 0.0), (1000, -1.0)]);
+    }
+
+    #[test]
+    fn malformed_entry_ids_are_rejected() {
+        let deep = (0..32).fold(EntryID::root(), |entry_id, i| entry_id.child(i));
+        assert_eq!(deep.to_string().parse(), Ok(deep));
+
+        let invalid = |s: &str| s.parse::<EntryID>().unwrap_err().to_string();
+        assert_eq!(invalid("0//1"), "invalid index: \"\"");
+        assert_eq!(invalid("0/"), "invalid index: \"\"");
+        assert_eq!(invalid("-1"), "invalid index: \"-1\"");
+        assert_eq!(invalid("0/cpu"), "invalid index: \"cpu\"");
+        // Doesn't fit in an i64
+        assert_eq!(
+            invalid("9223372036854775808"),
+            "invalid index: \"9223372036854775808\""
+        );
+    }
+
+    #[test]
+    fn small_items_of_the_same_kind_are_merged() {
+        let colored = |uid, start, stop, color| Item {
+            color,
+            ..item(uid, start, stop)
+        };
+        let items = vec![
+            item(0, 0, 5),
+            item(1, 5, 8),
+            item(2, 10, 12),
+            // Different kind: ends the run
+            colored(3, 12, 14, Color32::BLUE),
+            // Large enough to be kept as is
+            item(4, 20, 100),
+            item(5, 100, 102),
+        ];
+        let metas: Vec<_> = (0..items.len() as u64).map(meta).collect();
+        let (merged, merged_metas) = merge_small_items(&items, &metas, 10);
+
+        let spans: Vec<_> = merged
+            .iter()
+            .map(|x| (x.item_uid.0, x.interval.start.0, x.interval.stop.0))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![(0, 0, 12), (3, 12, 14), (4, 20, 100), (5, 100, 102)]
+        );
+        assert_eq!(merged_metas.len(), merged.len());
+        assert_eq!(merged_metas[0].title, "merged (3 tasks)");
+        match &merged_metas[0].fields[1] {
+            (name, Field::String(busy)) => {
+                assert_eq!(name, "Total Duration");
+                assert_eq!(*busy, Timestamp(10).to_string());
+            }
+            field => panic!("unexpected field {field:?}"),
+        }
+        assert_eq!(merged_metas[1].title, "Task 3");
+        assert_eq!(merged_metas[2].title, "Task 4");
+    }
+
+    #[test]
+    fn stable_colors_do_not_depend_on_order() {
+        let names = ["copy", "fill", "compute", "mapper", "runtime"];
+        let colors = |names: Vec<&'static str>| {
+            names
+                .into_iter()
+                .map(|name| (name, stable_color(name)))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let forward = colors(names.to_vec());
+        let backward = colors(names.iter().rev().copied().collect());
+        assert_eq!(forward, backward);
+        assert_eq!(stable_color("copy"), forward["copy"]);
+        // Different names aren't all lumped into one color
+        assert!(forward.values().any(|color| *color != forward["copy"]));
+    }
+
+    #[test]
+    fn step_through_tasks_forward_and_backward() {
+        // Tasks 0 (100 to 200), 1 (300 to 700, split across both tiles) and
+        // 2 (800 to 900), with gaps in between
+        let first = TileID(Interval::new(Timestamp(0), Timestamp(500)));
+        let second = TileID(Interval::new(Timestamp(500), Timestamp(1000)));
+        let mut source = MockDataSource::new(info(), interval())
+            .with_slot_tile(
+                slot_id(),
+                SlotTile {
+                    tile_id: first,
+                    items: vec![vec![item(0, 100, 200), item(1, 300, 500)]],
+                },
+            )
+            .with_slot_tile(
+                slot_id(),
+                SlotTile {
+                    tile_id: second,
+                    items: vec![vec![item(1, 500, 700), item(2, 800, 900)]],
+                },
+            );
+        let task = |start, stop| Some(Interval::new(Timestamp(start), Timestamp(stop)));
+
+        let mut next = |t| source.next_task_after(&slot_id(), Timestamp(t));
+        assert_eq!(next(0), task(100, 200));
+        assert_eq!(next(100), task(300, 700));
+        assert_eq!(next(300), task(800, 900));
+        assert_eq!(next(800), None);
+        assert_eq!(next(1000), None);
+
+        let mut prev = |t| source.prev_task_before(&slot_id(), Timestamp(t));
+        assert_eq!(prev(1000), task(800, 900));
+        assert_eq!(prev(800), task(300, 700));
+        assert_eq!(prev(300), task(100, 200));
+        assert_eq!(prev(100), None);
+        assert_eq!(prev(0), None);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn absolute_times_add_the_wall_clock_origin() {
+        use chrono::{TimeZone, Utc};
+
+        // 2023-01-02 03:04:05 UTC
+        let origin = TimeOrigin {
+            epoch: "process start".to_owned(),
+            wall_clock: Some(Timestamp(1_672_628_645_000_000_000)),
+        };
+        let t = Timestamp(1_500_000_000);
+        // 2023-01-02 03:04:06.5 UTC
+        let expected = Utc.timestamp_nanos(1_672_628_646_500_000_000);
+        assert_eq!(absolute_time(t, &origin), Some(expected));
+        assert_eq!(absolute_time(t, &TimeOrigin::default()), None);
+    }
+
+    #[test]
+    fn task_details_list_every_field() {
+        let item = ItemMeta {
+            item_uid: ItemUID(7),
+            title: "mapper task".to_owned(),
+            fields: vec![
+                ("Count".to_owned(), Field::U64(3)),
+                (
+                    "Provenance".to_owned(),
+                    Field::String("main.cc:12".to_owned()),
+                ),
+                ("Ready".to_owned(), Field::Empty),
+            ],
+        };
+        let entry_id = EntryID::root().child(0).child(1).child(2);
+        let interval = Interval::new(Timestamp(1_000_000), Timestamp(2_500_000));
+        assert_eq!(
+            format_task_details(&item, interval, &entry_id),
+            "mapper task\n\
+             Entry: 0/1/2\n\
+             Interval: from 1.000 to 2.500 ms (duration: 1.500 ms)\n\
+             Count: 3\n\
+             Provenance: main.cc:12\n\
+             Ready"
+        );
+    }
+
+    #[test]
+    fn inverted_items_are_dropped() {
+        let items = vec![
+            item(0, 0, 100),
+            item(1, 300, 200),
+            item(2, 400, 400),
+            item(3, 500, 600),
+        ];
+        let mut sanitized = Vec::new();
+        let logs = capture_logs(|| sanitized = sanitize_items(items));
+        let uids: Vec<_> = sanitized.iter().map(|item| item.item_uid.0).collect();
+        // Zero-duration items are instants, so they stay
+        assert_eq!(uids, vec![0, 2, 3]);
+        assert_eq!(logs, vec!["dropped 1 items with inverted intervals"]);
+    }
+
+    #[test]
+    fn interned_and_flat_meta_tiles_deserialize_alike() {
+        let tile = sample().fetch_slot_meta_tile(&slot_id(), TileID(interval()));
+        let describe = |tile: &SlotMetaTile| -> Vec<String> {
+            let items = tile.items.iter().flatten();
+            items
+                .map(|item| format!("{} {:?}", item.title, item.fields))
+                .collect()
+        };
+
+        let interned = serde_json::to_value(&tile).unwrap();
+        // "Task 0" to "Task 9", and "Interval" once for all of them
+        assert_eq!(
+            interned["strings"].as_array().unwrap().len(),
+            ITEMS as usize + 1
+        );
+        // The same tile, in the form used before strings were interned
+        let rows: Vec<Vec<_>> = tile
+            .items
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|item| {
+                        serde_json::json!({
+                            "item_uid": item.item_uid,
+                            "title": item.title,
+                            "fields": item.fields,
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+        let flat = serde_json::json!({ "tile_id": tile.tile_id, "items": rows });
+        let from_interned: SlotMetaTile = serde_json::from_value(interned.clone()).unwrap();
+        let from_flat: SlotMetaTile = serde_json::from_value(flat).unwrap();
+        assert_eq!(describe(&from_interned), describe(&tile));
+        assert_eq!(describe(&from_flat), describe(&tile));
+
+        let binary = bincode::serialize(&tile).unwrap();
+        let from_binary: SlotMetaTile = bincode::deserialize(&binary).unwrap();
+        assert_eq!(describe(&from_binary), describe(&tile));
+
+        let mut corrupt = interned;
+        corrupt["strings"] = serde_json::json!([]);
+        let error = serde_json::from_value::<SlotMetaTile>(corrupt).unwrap_err();
+        assert!(error.to_string().contains("out of range"), "{error}");
+    }
+
+    #[test]
+    fn aggregated_summaries_average_shared_times() {
+        let tile = |start: i64, stop: i64, points: &[(i64, f32)]| SummaryTile {
+            tile_id: TileID(Interval::new(Timestamp(start), Timestamp(stop))),
+            utilization: points
+                .iter()
+                .map(|&(time, util)| UtilPoint {
+                    time: Timestamp(time),
+                    util,
+                })
+                .collect(),
+        };
+        // Two curves over the same times, then one over a later tile
+        let tiles = [
+            tile(0, 500, &[(0, 0.0), (250, 1.0), (500, 0.5)]),
+            tile(0, 500, &[(0, 1.0), (250, 0.0), (500, 0.5)]),
+            tile(500, 1000, &[(750, 0.25), (1000, 0.0)]),
+        ];
+        let aggregate = aggregate_summary(&tiles, 10, Downsample::Mean);
+        assert_eq!(aggregate.tile_id, TileID(interval()));
+        let points: Vec<_> = aggregate
+            .utilization
+            .iter()
+            .map(|point| (point.time.0, point.util))
+            .collect();
+        assert_eq!(
+            points,
+            vec![(0, 0.5), (250, 0.5), (500, 0.5), (750, 0.25), (1000, 0.0)]
+        );
+    }
+
+    #[test]
+    fn aggregated_summaries_are_downsampled() {
+        let tiles = [sample().fetch_summary_tile(&summary_id(), TileID(interval()))];
+        let tile = &tiles[0];
+        // 0, 1, 0, ... every 100 ns, so [0, 500) averages 0.4 and [500, 1000]
+        // averages 0.5
+        let mean = aggregate_summary(&tiles, 2, Downsample::Mean);
+        assert_eq!(mean.tile_id, tile.tile_id);
+        let points: Vec<_> = mean
+            .utilization
+            .iter()
+            .map(|point| (point.time.0, point.util))
+            .collect();
+        assert_eq!(points, vec![(200, 0.4), (750, 0.5)]);
+
+        let max = aggregate_summary(&tiles, 2, Downsample::Max);
+        let points: Vec<_> = max
+            .utilization
+            .iter()
+            .map(|point| (point.time.0, point.util))
+            .collect();
+        assert_eq!(points, vec![(100, 1.0), (500, 1.0)]);
+
+        // Already few enough points
+        let all = aggregate_summary(&tiles, 100, Downsample::Max);
+        assert_eq!(all.utilization, tile.utilization);
+    }
+}
@@ -0,0 +1,802 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::data::{
+    DataSource, DataSourceError, EntryID, EntryInfo, ItemMeta, SearchHit, SlotMetaTile, SlotTile,
+    SummaryTile, TileID, TimeOrigin,
+};
+use crate::timestamp::{Interval, Timestamp};
+
+// Results of background fetches are kept (so that polling again returns
+// them) until there are more than this many, oldest first
+const MAX_READY: usize = 1024;
+
+// Background fetches handed to the thread at a time. The rest wait here, in
+// the order they were started, so that they can still be dropped (e.g., the
+// view moved on) without the thread working through them first.
+const MAX_IN_FLIGHT: usize = 4;
+
+// Reported for fetches cut short by the background thread exiting, e.g.,
+// because the DataSource panicked
+const EXITED: &str = "data source thread exited";
+
+// Jobs send what they fetched in the background through the Sender, which
+// only the background thread holds, so that it's disconnected once the
+// thread exits
+type Job = Box<dyn FnOnce(&mut dyn DataSource, &Sender<Completed>) + Send>;
+
+// Set with notify_on_fetch, and shared with the background thread
+type Notify = Arc<Mutex<Option<Box<dyn Fn() + Send + Sync>>>>;
+
+fn notify(on_fetched: &Notify) {
+    if let Ok(on_fetched) = on_fetched.lock() {
+        if let Some(on_fetched) = on_fetched.as_ref() {
+            on_fetched();
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum FetchKey {
+    Interval,
+    SummaryTiles(EntryID, Interval),
+    SlotTiles(EntryID, Interval),
+    SlotMetaTile(EntryID, TileID, Interval),
+}
+
+#[derive(Debug, Clone)]
+enum Fetched {
+    Interval(Interval),
+    SummaryTiles(Vec<SummaryTile>),
+    SlotTiles(Vec<SlotTile>),
+    SlotMetaTile(SlotMetaTile),
+}
+
+impl FetchKey {
+    // What failed fetches of this key return
+    fn empty(&self) -> Fetched {
+        match self {
+            FetchKey::Interval => Fetched::Interval(Interval::default()),
+            FetchKey::SummaryTiles(..) => Fetched::SummaryTiles(Vec::new()),
+            FetchKey::SlotTiles(..) => Fetched::SlotTiles(Vec::new()),
+            FetchKey::SlotMetaTile(_, tile_id, _) => Fetched::SlotMetaTile(SlotMetaTile {
+                tile_id: *tile_id,
+                items: Vec::new(),
+            }),
+        }
+    }
+}
+
+// A finished background fetch, and what went wrong during it, if anything
+struct Completed {
+    key: FetchKey,
+    fetched: Fetched,
+    errors: Vec<DataSourceError>,
+    last_error: Option<String>,
+}
+
+/// Runs a DataSource on a background thread, so that fetching never blocks
+/// the caller (e.g., the UI thread) as long as it sticks to the poll_*
+/// methods. Those start fetching in the background and return None until
+/// the result is in.
+///
+/// Only a few background fetches are handed to the thread at a time (see
+/// MAX_IN_FLIGHT). Other methods wait for the background thread, behind the
+/// fetches in flight, except for last_error and get_errors, which report what
+/// the background fetches ran into, and interval, which only waits the first
+/// time and is refreshed in the background after that.
+///
+/// If the background thread exits (e.g., the DataSource panicked), the
+/// fetches it had yet to complete are reported as failed, and everything
+/// from then on returns empty results, like a source that can't be reached.
+///
+/// This has to be the outermost layer, since wrappers (e.g., caches) don't
+/// forward the poll_* methods.
+pub struct DeferredDataSource {
+    jobs: Sender<Job>,
+    completed: Receiver<Completed>,
+    on_fetched: Notify,
+    exited: bool,
+    // Keys being fetched, whether in flight or queued
+    pending: BTreeSet<FetchKey>,
+    in_flight: usize,
+    queued: VecDeque<(FetchKey, Job)>,
+    ready: BTreeMap<FetchKey, Fetched>,
+    // Keys of ready, oldest first
+    ready_order: VecDeque<FetchKey>,
+    interval: Option<Interval>,
+    last_error: Option<String>,
+    errors: Vec<DataSourceError>,
+}
+
+impl DeferredDataSource {
+    // make is called on the background thread, so the DataSource it returns
+    // needn't be Send
+    pub fn new(make: impl FnOnce() -> Box<dyn DataSource> + Send + 'static) -> Self {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let (completed_sender, completed) = mpsc::channel();
+        let on_fetched = Notify::default();
+        let on_exit = on_fetched.clone();
+        thread::Builder::new()
+            .name("data source".to_owned())
+            .spawn(move || {
+                let _ = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                    let mut data_source = make();
+                    // Whether data_source has idle work (e.g., prefetches)
+                    // to do between jobs
+                    let mut busy = false;
+                    loop {
+                        let job = if busy {
+                            match receiver.try_recv() {
+                                Ok(job) => job,
+                                Err(TryRecvError::Empty) => {
+                                    busy = data_source.idle();
+                                    continue;
+                                }
+                                Err(TryRecvError::Disconnected) => break,
+                            }
+                        } else {
+                            match receiver.recv() {
+                                Ok(job) => job,
+                                Err(_) => break,
+                            }
+                        };
+                        job(data_source.as_mut(), &completed_sender);
+                        busy = true;
+                    }
+                }));
+                // Whoever is waiting on the fetches left will find out that
+                // they failed once they look again
+                drop(completed_sender);
+                notify(&on_exit);
+            })
+            .expect("unable to start data source thread");
+        Self {
+            jobs,
+            completed,
+            on_fetched,
+            exited: false,
+            pending: BTreeSet::new(),
+            in_flight: 0,
+            queued: VecDeque::new(),
+            ready: BTreeMap::new(),
+            ready_order: VecDeque::new(),
+            interval: None,
+            last_error: None,
+            errors: Vec::new(),
+        }
+    }
+
+    // Whether any background fetch has yet to complete
+    pub fn is_fetching(&mut self) -> bool {
+        self.collect();
+        !self.pending.is_empty()
+    }
+
+    // Run f on the background thread and wait for the result. None if the
+    // thread has exited.
+    fn call<T: Send + 'static>(
+        &mut self,
+        f: impl FnOnce(&mut dyn DataSource) -> T + Send + 'static,
+    ) -> Option<T> {
+        if self.exited {
+            return None;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let sent = self.jobs.send(Box::new(move |data_source, _| {
+            let result = f(data_source);
+            let errors = data_source.get_errors();
+            let last_error = data_source.last_error();
+            let _ = sender.send((result, errors, last_error));
+        }));
+        // The job (and so sender) is dropped without sending if the thread
+        // exits before or while running it
+        match sent.ok().and_then(|_| receiver.recv().ok()) {
+            Some((result, errors, last_error)) => {
+                self.errors.extend(errors);
+                self.last_error = last_error;
+                Some(result)
+            }
+            None => {
+                self.exit();
+                None
+            }
+        }
+    }
+
+    // The background thread has exited, so fail whatever it hadn't fetched
+    fn exit(&mut self) {
+        if self.exited {
+            return;
+        }
+        self.exited = true;
+        // Fetches completed before the thread exited still count
+        while let Ok(completed) = self.completed.try_recv() {
+            self.receive(completed);
+        }
+        self.queued.clear();
+        self.in_flight = 0;
+        log::error!("{EXITED}");
+        self.last_error = Some(EXITED.to_owned());
+        for key in std::mem::take(&mut self.pending) {
+            let (entry_id, tile_id) = match key {
+                FetchKey::Interval => continue,
+                FetchKey::SummaryTiles(entry_id, interval) => (entry_id, TileID(interval)),
+                FetchKey::SlotTiles(entry_id, interval) => (entry_id, TileID(interval)),
+                FetchKey::SlotMetaTile(entry_id, tile_id, _) => (entry_id, tile_id),
+            };
+            self.errors.push(DataSourceError {
+                entry_id,
+                tile_id,
+                message: EXITED.to_owned(),
+            });
+        }
+    }
+
+    // Start fetching key in the background, unless that's already underway
+    fn start(
+        &mut self,
+        key: FetchKey,
+        fetch: impl FnOnce(&mut dyn DataSource) -> Fetched + Send + 'static,
+    ) {
+        if self.exited || !self.pending.insert(key.clone()) {
+            return;
+        }
+        let job_key = key.clone();
+        let on_fetched = self.on_fetched.clone();
+        let job: Job = Box::new(move |data_source, completed| {
+            let fetched = fetch(data_source);
+            let _ = completed.send(Completed {
+                key: job_key,
+                fetched,
+                errors: data_source.get_errors(),
+                last_error: data_source.last_error(),
+            });
+            notify(&on_fetched);
+        });
+        self.queued.push_back((key, job));
+        self.dispatch();
+    }
+
+    // Hand queued fetches to the thread while there's room
+    fn dispatch(&mut self) {
+        while self.in_flight < MAX_IN_FLIGHT && !self.exited {
+            let job = match self.queued.pop_front() {
+                Some((_, job)) => job,
+                None => break,
+            };
+            if self.jobs.send(job).is_err() {
+                self.exit();
+                break;
+            }
+            self.in_flight += 1;
+        }
+    }
+
+    fn receive(&mut self, completed: Completed) {
+        self.in_flight -= 1;
+        self.pending.remove(&completed.key);
+        self.errors.extend(completed.errors);
+        self.last_error = completed.last_error;
+        if let Fetched::Interval(interval) = completed.fetched {
+            self.interval = Some(interval);
+            return;
+        }
+        self.ready_order.push_back(completed.key.clone());
+        self.ready.insert(completed.key, completed.fetched);
+    }
+
+    // Pick up the results of background fetches that have completed
+    fn collect(&mut self) {
+        loop {
+            match self.completed.try_recv() {
+                Ok(completed) => self.receive(completed),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.exit();
+                    break;
+                }
+            }
+        }
+        self.dispatch();
+        while self.ready.len() > MAX_READY {
+            let oldest = self.ready_order.pop_front().unwrap();
+            self.ready.remove(&oldest);
+        }
+    }
+
+    fn poll(
+        &mut self,
+        key: FetchKey,
+        fetch: impl FnOnce(&mut dyn DataSource) -> Fetched + Send + 'static,
+    ) -> Option<Fetched> {
+        self.collect();
+        if let Some(fetched) = self.ready.get(&key) {
+            return Some(fetched.clone());
+        }
+        if self.exited {
+            return Some(key.empty());
+        }
+        self.start(key, fetch);
+        None
+    }
+}
+
+impl DataSource for DeferredDataSource {
+    fn interval(&mut self) -> Interval {
+        self.collect();
+        match self.interval {
+            Some(interval) => {
+                self.start(FetchKey::Interval, |data_source| {
+                    Fetched::Interval(data_source.interval())
+                });
+                interval
+            }
+            None => {
+                let interval = self
+                    .call(|data_source| data_source.interval())
+                    .unwrap_or_default();
+                self.interval = Some(interval);
+                interval
+            }
+        }
+    }
+
+    fn fetch_info(&mut self) -> EntryInfo {
+        self.call(|data_source| data_source.fetch_info())
+            .unwrap_or_else(|| EntryInfo::Panel {
+                short_name: "root".to_owned(),
+                long_name: "root".to_owned(),
+                summary: None,
+                slots: Vec::new(),
+            })
+    }
+
+    fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+        let entry_id = entry_id.clone();
+        self.call(move |data_source| data_source.request_tiles(&entry_id, request_interval))
+            .unwrap_or_default()
+    }
+
+    fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+        let entry_id = entry_id.clone();
+        self.call(move |data_source| data_source.fetch_summary_tile(&entry_id, tile_id))
+            .unwrap_or_else(|| SummaryTile {
+                tile_id,
+                utilization: Vec::new(),
+            })
+    }
+
+    fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+        let entry_id = entry_id.clone();
+        self.call(move |data_source| data_source.fetch_slot_tile(&entry_id, tile_id))
+            .unwrap_or_else(|| SlotTile {
+                tile_id,
+                items: Vec::new(),
+            })
+    }
+
+    fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+        let entry_id = entry_id.clone();
+        self.call(move |data_source| data_source.fetch_slot_meta_tile(&entry_id, tile_id))
+            .unwrap_or_else(|| SlotMetaTile {
+                tile_id,
+                items: Vec::new(),
+            })
+    }
+
+    fn fetch_clipped_slot_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Interval,
+    ) -> SlotTile {
+        let entry_id = entry_id.clone();
+        self.call(move |data_source| data_source.fetch_clipped_slot_tile(&entry_id, tile_id, clip))
+            .unwrap_or_else(|| SlotTile {
+                tile_id,
+                items: Vec::new(),
+            })
+    }
+
+    fn fetch_clipped_slot_meta_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Interval,
+    ) -> SlotMetaTile {
+        let entry_id = entry_id.clone();
+        self.call(move |data_source| {
+            data_source.fetch_clipped_slot_meta_tile(&entry_id, tile_id, clip)
+        })
+        .unwrap_or_else(|| SlotMetaTile {
+            tile_id,
+            items: Vec::new(),
+        })
+    }
+
+    fn last_error(&self) -> Option<String> {
+        self.last_error.clone()
+    }
+
+    fn get_errors(&mut self) -> Vec<DataSourceError> {
+        self.collect();
+        std::mem::take(&mut self.errors)
+    }
+
+    fn time_origin(&mut self) -> TimeOrigin {
+        self.call(|data_source| data_source.time_origin())
+            .unwrap_or_default()
+    }
+
+    fn tile_byte_estimate(&mut self, entry_id: &EntryID, tile_id: TileID) -> Option<usize> {
+        let entry_id = entry_id.clone();
+        self.call(move |data_source| data_source.tile_byte_estimate(&entry_id, tile_id))
+            .unwrap_or_default()
+    }
+
+    // Applies to fetches started from now on, without waiting
+    // on_fetched is called on the background thread
+    fn notify_on_fetch(&mut self, on_fetched: Box<dyn Fn() + Send + Sync>) -> bool {
+        if let Ok(mut notify) = self.on_fetched.lock() {
+            *notify = Some(on_fetched);
+        }
+        true
+    }
+
+    // Fetches in flight still complete, since the thread can't be
+    // interrupted, but the queued ones are dropped
+    fn cancel_queued(&mut self) {
+        for (key, _) in self.queued.drain(..) {
+            self.pending.remove(&key);
+        }
+    }
+
+    fn set_summary_samples(&mut self, samples: usize) {
+        let _ = self.jobs.send(Box::new(move |data_source, _| {
+            data_source.set_summary_samples(samples)
+        }));
+    }
+
+    fn poll_summary_tiles(
+        &mut self,
+        entry_id: &EntryID,
+        interval: Interval,
+    ) -> Option<Vec<SummaryTile>> {
+        let key = FetchKey::SummaryTiles(entry_id.clone(), interval);
+        let entry_id = entry_id.clone();
+        let fetched = self.poll(key, move |data_source| {
+            let tiles = data_source.poll_summary_tiles(&entry_id, interval);
+            Fetched::SummaryTiles(tiles.unwrap_or_default())
+        });
+        match fetched? {
+            Fetched::SummaryTiles(tiles) => Some(tiles),
+            _ => unreachable!(),
+        }
+    }
+
+    fn poll_slot_tiles(&mut self, entry_id: &EntryID, interval: Interval) -> Option<Vec<SlotTile>> {
+        let key = FetchKey::SlotTiles(entry_id.clone(), interval);
+        let entry_id = entry_id.clone();
+        let fetched = self.poll(key, move |data_source| {
+            let tiles = data_source.poll_slot_tiles(&entry_id, interval);
+            Fetched::SlotTiles(tiles.unwrap_or_default())
+        });
+        match fetched? {
+            Fetched::SlotTiles(tiles) => Some(tiles),
+            _ => unreachable!(),
+        }
+    }
+
+    fn poll_slot_meta_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Interval,
+    ) -> Option<SlotMetaTile> {
+        let key = FetchKey::SlotMetaTile(entry_id.clone(), tile_id, clip);
+        let entry_id = entry_id.clone();
+        let fetched = self.poll(key, move |data_source| {
+            let tile = data_source.fetch_clipped_slot_meta_tile(&entry_id, tile_id, clip);
+            Fetched::SlotMetaTile(tile)
+        });
+        match fetched? {
+            Fetched::SlotMetaTile(tile) => Some(tile),
+            _ => unreachable!(),
+        }
+    }
+
+    fn search(&mut self, query: &str) -> Vec<SearchHit> {
+        let query = query.to_owned();
+        self.call(move |data_source| data_source.search(&query))
+            .unwrap_or_default()
+    }
+
+    fn probe(&mut self, entry_id: &EntryID, at: Timestamp) -> Option<ItemMeta> {
+        let entry_id = entry_id.clone();
+        self.call(move |data_source| data_source.probe(&entry_id, at))
+            .unwrap_or_default()
+    }
+
+    fn next_task_after(&mut self, entry_id: &EntryID, t: Timestamp) -> Option<Interval> {
+        let entry_id = entry_id.clone();
+        self.call(move |data_source| data_source.next_task_after(&entry_id, t))
+            .unwrap_or_default()
+    }
+
+    fn prev_task_before(&mut self, entry_id: &EntryID, t: Timestamp) -> Option<Interval> {
+        let entry_id = entry_id.clone();
+        self.call(move |data_source| data_source.prev_task_before(&entry_id, t))
+            .unwrap_or_default()
+    }
+
+    fn export_manifest(&mut self) -> serde_json::Value {
+        self.call(|data_source| data_source.export_manifest())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::tests::{interval, sample, slot_id, summary_id, ITEMS};
+    use crate::mock::MockDataSource;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    const DELAY: Duration = Duration::from_millis(200);
+
+    // Takes DELAY to fetch any tile, and reports an error for every tile
+    struct SlowDataSource {
+        inner: MockDataSource,
+        errors: Vec<DataSourceError>,
+        last_error: Option<String>,
+    }
+
+    impl SlowDataSource {
+        fn fetched(&mut self, entry_id: &EntryID, tile_id: TileID) {
+            thread::sleep(DELAY);
+            self.errors.push(DataSourceError {
+                entry_id: entry_id.clone(),
+                tile_id,
+                message: "slow".to_owned(),
+            });
+            self.last_error = Some("slow".to_owned());
+        }
+    }
+
+    impl DataSource for SlowDataSource {
+        fn interval(&mut self) -> Interval {
+            self.inner.interval()
+        }
+        fn fetch_info(&mut self) -> EntryInfo {
+            self.inner.fetch_info()
+        }
+        fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+            self.inner.request_tiles(entry_id, request_interval)
+        }
+        fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+            self.fetched(entry_id, tile_id);
+            self.inner.fetch_summary_tile(entry_id, tile_id)
+        }
+        fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+            self.fetched(entry_id, tile_id);
+            self.inner.fetch_slot_tile(entry_id, tile_id)
+        }
+        fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+            self.fetched(entry_id, tile_id);
+            self.inner.fetch_slot_meta_tile(entry_id, tile_id)
+        }
+        fn last_error(&self) -> Option<String> {
+            self.last_error.clone()
+        }
+        fn get_errors(&mut self) -> Vec<DataSourceError> {
+            std::mem::take(&mut self.errors)
+        }
+    }
+
+    fn slow() -> DeferredDataSource {
+        DeferredDataSource::new(|| {
+            Box::new(SlowDataSource {
+                inner: sample(),
+                errors: Vec::new(),
+                last_error: None,
+            })
+        })
+    }
+
+    // Polls until f returns something, giving up well after DELAY
+    fn wait_for<T>(
+        source: &mut DeferredDataSource,
+        mut f: impl FnMut(&mut DeferredDataSource) -> Option<T>,
+    ) -> T {
+        let start = Instant::now();
+        loop {
+            if let Some(result) = f(source) {
+                return result;
+            }
+            assert!(start.elapsed() < DELAY * 20, "fetch never completed");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn polling_returns_before_slow_fetches() {
+        let mut source = slow();
+        assert_eq!(source.interval(), interval());
+
+        let start = Instant::now();
+        assert!(source.poll_slot_tiles(&slot_id(), interval()).is_none());
+        assert!(source
+            .poll_summary_tiles(&summary_id(), interval())
+            .is_none());
+        assert!(source.poll_slot_tiles(&slot_id(), interval()).is_none());
+        assert!(start.elapsed() < DELAY / 4);
+        assert!(source.is_fetching());
+
+        let tiles = wait_for(&mut source, |s| s.poll_slot_tiles(&slot_id(), interval()));
+        assert_eq!(tiles.len(), 1);
+        assert_eq!(tiles[0].items[0].len(), ITEMS as usize);
+        let tiles = wait_for(&mut source, |s| {
+            s.poll_summary_tiles(&summary_id(), interval())
+        });
+        assert_eq!(tiles.len(), 1);
+        assert!(!source.is_fetching());
+
+        // Ready results stay put, so polling again doesn't refetch
+        let start = Instant::now();
+        assert!(source.poll_slot_tiles(&slot_id(), interval()).is_some());
+        assert!(start.elapsed() < DELAY / 4);
+    }
+
+    #[test]
+    fn background_errors_are_reported() {
+        let mut source = slow();
+        let tile_id = TileID(interval());
+        wait_for(&mut source, |s| {
+            s.poll_slot_meta_tile(&slot_id(), tile_id, interval())
+        });
+        assert_eq!(source.last_error().as_deref(), Some("slow"));
+        // The slot tile and its metas were both fetched to clip the metas
+        let errors = source.get_errors();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|error| error.entry_id == slot_id()));
+        assert!(source.get_errors().is_empty());
+    }
+
+    // Panics on any slot tile fetch, taking the background thread with it
+    struct PanickingDataSource(MockDataSource);
+
+    impl DataSource for PanickingDataSource {
+        fn interval(&mut self) -> Interval {
+            self.0.interval()
+        }
+        fn fetch_info(&mut self) -> EntryInfo {
+            self.0.fetch_info()
+        }
+        fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+            self.0.request_tiles(entry_id, request_interval)
+        }
+        fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+            self.0.fetch_summary_tile(entry_id, tile_id)
+        }
+        fn fetch_slot_tile(&mut self, _entry_id: &EntryID, _tile_id: TileID) -> SlotTile {
+            panic!("malformed tile")
+        }
+        fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+            self.0.fetch_slot_meta_tile(entry_id, tile_id)
+        }
+    }
+
+    #[test]
+    fn fetches_fail_once_the_thread_exits() {
+        let mut source = DeferredDataSource::new(|| Box::new(PanickingDataSource(sample())));
+        assert_eq!(source.interval(), interval());
+
+        let tiles = wait_for(&mut source, |s| s.poll_slot_tiles(&slot_id(), interval()));
+        assert!(tiles.is_empty());
+        assert!(!source.is_fetching());
+        assert_eq!(source.last_error().as_deref(), Some(EXITED));
+        let errors = source.get_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].entry_id, slot_id());
+        assert_eq!(errors[0].tile_id, TileID(interval()));
+
+        // Everything else comes back empty rather than waiting or panicking
+        assert!(source
+            .poll_summary_tiles(&summary_id(), interval())
+            .map_or(false, |tiles| tiles.is_empty()));
+        assert!(source.request_tiles(&slot_id(), interval()).is_empty());
+        assert!(source.get_errors().is_empty());
+    }
+
+    #[test]
+    fn fetches_past_the_limit_wait_their_turn() {
+        let mut source = slow();
+        let tile_interval = |i: i64| Interval::new(Timestamp(i * 100), Timestamp((i + 1) * 100));
+        for i in 0..=MAX_IN_FLIGHT as i64 {
+            assert!(source
+                .poll_slot_tiles(&slot_id(), tile_interval(i))
+                .is_none());
+        }
+        assert_eq!(source.in_flight, MAX_IN_FLIGHT);
+        assert_eq!(source.queued.len(), 1);
+        assert_eq!(source.pending.len(), MAX_IN_FLIGHT + 1);
+
+        // The extra fetch goes out once one in flight completes
+        let last = MAX_IN_FLIGHT as i64;
+        wait_for(&mut source, |s| {
+            s.poll_slot_tiles(&slot_id(), tile_interval(last))
+        });
+        assert!(source.queued.is_empty());
+        assert!(!source.is_fetching());
+    }
+
+    #[test]
+    fn cancelled_fetches_are_dropped_until_polled_again() {
+        let mut source = slow();
+        let tile_interval = |i: i64| Interval::new(Timestamp(i * 100), Timestamp((i + 1) * 100));
+        let stale = MAX_IN_FLIGHT as i64;
+        for i in 0..=stale + 1 {
+            assert!(source
+                .poll_slot_tiles(&slot_id(), tile_interval(i))
+                .is_none());
+        }
+        assert_eq!(source.queued.len(), 2);
+
+        source.cancel_queued();
+        assert!(source.queued.is_empty());
+        assert_eq!(source.pending.len(), MAX_IN_FLIGHT);
+
+        // Still wanted, so it goes to the back of the line, and the other
+        // one is never fetched
+        assert!(source
+            .poll_slot_tiles(&slot_id(), tile_interval(stale))
+            .is_none());
+        assert_eq!(source.queued.len(), 1);
+        wait_for(&mut source, |s| {
+            s.poll_slot_tiles(&slot_id(), tile_interval(stale))
+        });
+        assert!(!source.is_fetching());
+        let key = FetchKey::SlotTiles(slot_id(), tile_interval(stale + 1));
+        assert!(!source.ready.contains_key(&key));
+    }
+
+    #[test]
+    fn completed_fetches_notify() {
+        let mut source = slow();
+        let notified = Arc::new(AtomicUsize::new(0));
+        let count = notified.clone();
+        assert!(source.notify_on_fetch(Box::new(move || {
+            count.fetch_add(1, Ordering::SeqCst);
+        })));
+
+        assert!(source.poll_slot_tiles(&slot_id(), interval()).is_none());
+        assert_eq!(notified.load(Ordering::SeqCst), 0);
+        let start = Instant::now();
+        while notified.load(Ordering::SeqCst) == 0 {
+            assert!(start.elapsed() < DELAY * 20, "never notified");
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(source.poll_slot_tiles(&slot_id(), interval()).is_some());
+
+        // Likewise when the thread exits, so the failure gets noticed
+        let mut source = DeferredDataSource::new(|| Box::new(PanickingDataSource(sample())));
+        let notified = Arc::new(AtomicUsize::new(0));
+        let count = notified.clone();
+        source.notify_on_fetch(Box::new(move || {
+            count.fetch_add(1, Ordering::SeqCst);
+        }));
+        assert!(source.poll_slot_tiles(&slot_id(), interval()).is_none());
+        let start = Instant::now();
+        while notified.load(Ordering::SeqCst) == 0 {
+            assert!(start.elapsed() < DELAY * 20, "never notified");
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(source.poll_slot_tiles(&slot_id(), interval()).is_some());
+        assert_eq!(source.last_error().as_deref(), Some(EXITED));
+    }
+}
@@ -0,0 +1,376 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use fnv::FnvHasher;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::data::{
+    DataSource, DataSourceError, EntryID, EntryInfo, SlotMetaTile, SlotTile, SummaryTile, TileID,
+    TimeOrigin,
+};
+use crate::layer::DataSourceLayer;
+use crate::timestamp::Interval;
+
+const TILE_EXTENSION: &str = "tile.gz";
+const TEMP_EXTENSION: &str = "tmp";
+
+#[derive(Debug)]
+struct DiskEntry {
+    bytes: u64,
+    last_used: u64,
+}
+
+/// Wraps a DataSource (typically an HTTPDataSource) and keeps fetched tiles
+/// in a directory on disk, so that they survive restarts. Each tile is stored
+/// bincode-serialized and gzipped in a file named after a hash of the server
+/// URL, the entry and the tile.
+///
+/// Files are evicted in least-recently-used order once their total size
+/// exceeds the byte budget. Use is only tracked while the cache is open; on
+/// open, files are ordered by when they were written.
+///
+/// Tiles that the inner source reported an error for are never written, so a
+/// transient failure isn't remembered across sessions.
+pub struct PersistentTileCache {
+    data_source: Box<dyn DataSource>,
+    dir: PathBuf,
+    server_url: String,
+    byte_budget: u64,
+    bytes: u64,
+    tick: u64,
+    entries: BTreeMap<String, DiskEntry>, // file name -> entry
+    lru: BTreeMap<u64, String>,           // last_used -> file name
+    // Errors drained from the inner source, not yet drained by get_errors
+    errors: Vec<DataSourceError>,
+}
+
+impl PersistentTileCache {
+    // Files already in dir (e.g., from an earlier session) are picked up
+    pub fn new(
+        data_source: Box<dyn DataSource>,
+        dir: impl Into<PathBuf>,
+        server_url: &str,
+        byte_budget: u64,
+    ) -> io::Result<Self> {
+        let dir = dir.into();
+        let files = Self::scan(&dir)?;
+        Ok(Self::with_files(
+            data_source,
+            dir,
+            server_url,
+            byte_budget,
+            files,
+        ))
+    }
+
+    // The cached tiles in dir, oldest first, as (file name, size)
+    fn scan(dir: &Path) -> io::Result<Vec<(String, u64)>> {
+        fs::create_dir_all(dir)?;
+
+        let mut files = Vec::new();
+        for dir_entry in fs::read_dir(dir)? {
+            let dir_entry = dir_entry?;
+            let path = dir_entry.path();
+            let name = dir_entry.file_name().to_string_lossy().into_owned();
+            if name.ends_with(TEMP_EXTENSION) {
+                // Left behind by an interrupted write
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+            if !name.ends_with(TILE_EXTENSION) {
+                continue;
+            }
+            let metadata = dir_entry.metadata()?;
+            files.push((metadata.modified().ok(), name, metadata.len()));
+        }
+        files.sort();
+        Ok(files
+            .into_iter()
+            .map(|(_, name, bytes)| (name, bytes))
+            .collect())
+    }
+
+    fn with_files(
+        data_source: Box<dyn DataSource>,
+        dir: PathBuf,
+        server_url: &str,
+        byte_budget: u64,
+        files: Vec<(String, u64)>,
+    ) -> Self {
+        let mut result = Self {
+            data_source,
+            dir,
+            server_url: server_url.to_owned(),
+            byte_budget,
+            bytes: 0,
+            tick: 0,
+            entries: BTreeMap::new(),
+            lru: BTreeMap::new(),
+            errors: Vec::new(),
+        };
+        for (name, bytes) in files {
+            result.track(name, bytes);
+        }
+        result.evict();
+        result
+    }
+
+    fn file_name(&self, kind: &str, entry_id: &EntryID, tile_id: TileID) -> (String, String) {
+        // The key is stored in the file too, to detect hash collisions
+        let key = format!(
+            "{}\0{kind}\0{entry_id}\0{}_{}",
+            self.server_url, tile_id.0.start.0, tile_id.0.stop.0
+        );
+        let mut hasher = FnvHasher::default();
+        hasher.write(key.as_bytes());
+        (format!("{:016x}.{TILE_EXTENSION}", hasher.finish()), key)
+    }
+
+    fn track(&mut self, name: String, bytes: u64) {
+        self.tick += 1;
+        if let Some(old) = self.entries.remove(&name) {
+            self.lru.remove(&old.last_used);
+            self.bytes -= old.bytes;
+        }
+        self.lru.insert(self.tick, name.clone());
+        self.entries.insert(
+            name,
+            DiskEntry {
+                bytes,
+                last_used: self.tick,
+            },
+        );
+        self.bytes += bytes;
+    }
+
+    fn untrack(&mut self, name: &str) {
+        if let Some(entry) = self.entries.remove(name) {
+            self.lru.remove(&entry.last_used);
+            self.bytes -= entry.bytes;
+        }
+        let _ = fs::remove_file(self.dir.join(name));
+    }
+
+    fn evict(&mut self) {
+        while self.bytes > self.byte_budget {
+            let oldest = *self.lru.keys().next().unwrap();
+            let name = self.lru[&oldest].clone();
+            self.untrack(&name);
+        }
+    }
+
+    fn load<T: DeserializeOwned>(&mut self, name: &str, key: &str) -> Option<T> {
+        let bytes = self.entries.get(name)?.bytes;
+        let result: io::Result<(String, T)> = File::open(self.dir.join(name)).and_then(|file| {
+            let mut body = Vec::new();
+            GzDecoder::new(BufReader::new(file)).read_to_end(&mut body)?;
+            bincode::deserialize(&body).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+        });
+        match result {
+            Ok((stored_key, tile)) if stored_key == key => {
+                self.track(name.to_owned(), bytes);
+                log::trace!("disk cache hit: {name}");
+                Some(tile)
+            }
+            // A collision: leave the file for its owner
+            Ok(_) => None,
+            Err(e) => {
+                log::warn!("dropping unreadable cached tile {name}: {e}");
+                self.untrack(name);
+                None
+            }
+        }
+    }
+
+    fn store<T: Serialize>(&mut self, name: String, key: &str, tile: &T) {
+        let path = self.dir.join(&name);
+        let temp = path.with_extension(TEMP_EXTENSION);
+        let result = File::create(&temp).and_then(|file| {
+            let mut writer = GzEncoder::new(BufWriter::new(file), Compression::default());
+            let body = bincode::serialize(&(key, tile))
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            writer.write_all(&body)?;
+            writer.finish()?.flush()?;
+            fs::rename(&temp, &path)?;
+            Ok(fs::metadata(&path)?.len())
+        });
+        match result {
+            Ok(bytes) => {
+                self.track(name, bytes);
+                self.evict();
+            }
+            Err(e) => {
+                log::warn!("unable to cache tile {}: {e}", path.display());
+                let _ = fs::remove_file(&temp);
+            }
+        }
+    }
+
+    // Drains the inner source's errors, and reports whether any of them is
+    // for this tile
+    fn failed(&mut self, entry_id: &EntryID, tile_id: TileID) -> bool {
+        let errors = self.data_source.get_errors();
+        let failed = errors
+            .iter()
+            .any(|error| error.entry_id == *entry_id && error.tile_id == tile_id);
+        self.errors.extend(errors);
+        failed
+    }
+
+    fn fetch<T: Serialize + DeserializeOwned>(
+        &mut self,
+        kind: &str,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        fetch: impl FnOnce(&mut dyn DataSource) -> T,
+    ) -> T {
+        let (name, key) = self.file_name(kind, entry_id, tile_id);
+        if let Some(tile) = self.load(&name, &key) {
+            return tile;
+        }
+        let tile = fetch(self.data_source.as_mut());
+        if !self.failed(entry_id, tile_id) {
+            self.store(name, &key, &tile);
+        }
+        tile
+    }
+}
+
+impl DataSource for PersistentTileCache {
+    fn interval(&mut self) -> Interval {
+        self.data_source.interval()
+    }
+
+    fn fetch_info(&mut self) -> EntryInfo {
+        self.data_source.fetch_info()
+    }
+
+    fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+        self.data_source.request_tiles(entry_id, request_interval)
+    }
+
+    fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+        self.fetch("summary", entry_id, tile_id, |source| {
+            source.fetch_summary_tile(entry_id, tile_id)
+        })
+    }
+
+    fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+        self.fetch("slot", entry_id, tile_id, |source| {
+            source.fetch_slot_tile(entry_id, tile_id)
+        })
+    }
+
+    fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+        self.fetch("slot_meta", entry_id, tile_id, |source| {
+            source.fetch_slot_meta_tile(entry_id, tile_id)
+        })
+    }
+
+    fn last_error(&self) -> Option<String> {
+        self.data_source.last_error()
+    }
+
+    fn get_errors(&mut self) -> Vec<DataSourceError> {
+        let mut errors = std::mem::take(&mut self.errors);
+        errors.extend(self.data_source.get_errors());
+        errors
+    }
+
+    fn time_origin(&mut self) -> TimeOrigin {
+        self.data_source.time_origin()
+    }
+
+    fn tile_byte_estimate(&mut self, entry_id: &EntryID, tile_id: TileID) -> Option<usize> {
+        self.data_source.tile_byte_estimate(entry_id, tile_id)
+    }
+
+    fn set_summary_samples(&mut self, samples: usize) {
+        self.data_source.set_summary_samples(samples)
+    }
+
+    fn idle(&mut self) -> bool {
+        self.data_source.idle()
+    }
+}
+
+pub struct PersistentCacheLayer {
+    pub dir: PathBuf,
+    pub server_url: String,
+    pub byte_budget: u64,
+}
+
+impl PersistentCacheLayer {
+    pub fn new(dir: impl AsRef<Path>, server_url: &str, byte_budget: u64) -> Self {
+        Self {
+            dir: dir.as_ref().to_owned(),
+            server_url: server_url.to_owned(),
+            byte_budget,
+        }
+    }
+}
+
+impl DataSourceLayer for PersistentCacheLayer {
+    // If the cache directory can't be used, tiles are fetched uncached
+    fn wrap(self, inner: Box<dyn DataSource>) -> Box<dyn DataSource> {
+        match PersistentTileCache::scan(&self.dir) {
+            Ok(files) => Box::new(PersistentTileCache::with_files(
+                inner,
+                self.dir,
+                &self.server_url,
+                self.byte_budget,
+                files,
+            )),
+            Err(e) => {
+                log::warn!("disk cache disabled, {}: {e}", self.dir.display());
+                inner
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::server::tests::temp_dir;
+    use crate::mock::tests::{info, interval, sample, slot_id};
+    use crate::mock::MockDataSource;
+
+    const URL: &str = "http://localhost:8080";
+
+    #[test]
+    fn tiles_survive_reopening_the_cache() {
+        let dir = temp_dir("disk-cache-reopen");
+        let tile_id = TileID(interval());
+        let mut cache = PersistentTileCache::new(Box::new(sample()), &dir, URL, 1 << 20).unwrap();
+        assert_eq!(
+            cache.fetch_slot_tile(&slot_id(), tile_id).items[0].len(),
+            10
+        );
+        drop(cache);
+
+        // Nothing to fetch from, so the tile can only come from disk
+        let empty = || Box::new(MockDataSource::new(info(), interval()));
+        let mut cache = PersistentTileCache::new(empty(), &dir, URL, 1 << 20).unwrap();
+        assert_eq!(
+            cache.fetch_slot_tile(&slot_id(), tile_id).items[0].len(),
+            10
+        );
+        drop(cache);
+
+        // Tiles of other servers aren't mixed up with it
+        let mut cache =
+            PersistentTileCache::new(empty(), &dir, "http://other:8080", 1 << 20).unwrap();
+        assert!(cache.fetch_slot_tile(&slot_id(), tile_id).items.is_empty());
+        drop(cache);
+
+        // Nor kept past the budget
+        let mut cache = PersistentTileCache::new(empty(), &dir, URL, 0).unwrap();
+        assert!(cache.fetch_slot_tile(&slot_id(), tile_id).items.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,460 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use serde_json::json;
+
+use crate::data::{DataSource, EntryID, EntryInfo, ItemUID};
+use crate::timestamp::{Interval, Timestamp};
+
+// Writes one CSV row per item of the slot entry_id that overlaps interval,
+// with each item clipped to interval. Output follows RFC 4180 (CRLF line
+// endings, fields quoted only when needed).
+pub fn export_csv(
+    source: &mut dyn DataSource,
+    entry_id: &EntryID,
+    interval: Interval,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    write_row(
+        out,
+        &["start_ns", "stop_ns", "duration_ns", "title", "entry_id"],
+    )?;
+
+    let entry = entry_id.to_string();
+    // Items crossing tile boundaries appear in every tile they overlap
+    let mut written = BTreeSet::new();
+    for tile_id in source.request_tiles(entry_id, interval) {
+        // Item metadata has no interval, so pair it up with the corresponding
+        // item in the slot tile
+        let tile = source.fetch_slot_tile(entry_id, tile_id);
+        let meta_tile = source.fetch_slot_meta_tile(entry_id, tile_id);
+        for (row, meta_row) in tile.items.iter().zip(&meta_tile.items) {
+            for (item, meta) in row.iter().zip(meta_row) {
+                if !written.insert(item.item_uid) {
+                    continue;
+                }
+                let clipped = match item.interval.intersection(interval) {
+                    Some(clipped) => clipped,
+                    None => continue,
+                };
+                write_item_row(out, clipped, &meta.title, &entry)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Like export_csv, but walks interval in windows the length of chunk, so
+// that only one window's tiles are held in memory at a time. Items are
+// written in the first window they overlap, so only those crossing into the
+// next window need remembering.
+pub fn export_csv_streaming(
+    source: &mut dyn DataSource,
+    entry_id: &EntryID,
+    interval: Interval,
+    chunk: Interval,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    write_row(
+        out,
+        &["start_ns", "stop_ns", "duration_ns", "title", "entry_id"],
+    )?;
+
+    let entry = entry_id.to_string();
+    let step = chunk.duration_ns().max(1);
+    let mut written = BTreeMap::new();
+    let mut start = interval.start;
+    while start < interval.stop {
+        let stop = Timestamp(start.0.saturating_add(step).min(interval.stop.0));
+        let window = Interval::new(start, stop);
+        write_window(
+            source,
+            entry_id,
+            interval,
+            window,
+            &mut written,
+            &entry,
+            out,
+        )?;
+        start = stop;
+    }
+    Ok(())
+}
+
+// written holds the items written that continue past the window before this
+// one, and where they stop, and is left holding those that continue past
+// this one. Tiles can be coarser than the window, so items that don't
+// overlap it show up too; those are left for the window they do overlap.
+fn write_window(
+    source: &mut dyn DataSource,
+    entry_id: &EntryID,
+    interval: Interval,
+    window: Interval,
+    written: &mut BTreeMap<ItemUID, Timestamp>,
+    entry: &str,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    for tile_id in source.request_tiles(entry_id, window) {
+        let tile = source.fetch_slot_tile(entry_id, tile_id);
+        let meta_tile = source.fetch_slot_meta_tile(entry_id, tile_id);
+        for (row, meta_row) in tile.items.iter().zip(&meta_tile.items) {
+            for (item, meta) in row.iter().zip(meta_row) {
+                if !item.interval.overlaps(window) || written.contains_key(&item.item_uid) {
+                    continue;
+                }
+                let clipped = match item.interval.intersection(interval) {
+                    Some(clipped) => clipped,
+                    None => continue,
+                };
+                write_item_row(out, clipped, &meta.title, entry)?;
+                written.insert(item.item_uid, item.interval.stop);
+            }
+        }
+    }
+    written.retain(|_, stop| *stop > window.stop);
+    Ok(())
+}
+
+// Writes every item overlapping interval, clipped to it, in the Chrome Trace
+// Event format (as read by chrome://tracing and Perfetto). Each node becomes
+// a process, and each row of each slot becomes a thread, since items within
+// a thread are expected not to overlap.
+pub fn export_chrome_trace(
+    source: &mut dyn DataSource,
+    interval: Interval,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let info = source.fetch_info();
+
+    let mut events = Vec::new();
+    let mut stack = vec![(EntryID::root(), &info)];
+    // Threads are numbered within each process
+    let mut next_tid = Vec::new();
+    while let Some((entry_id, entry)) = stack.pop() {
+        match entry {
+            EntryInfo::Panel {
+                long_name, slots, ..
+            } => {
+                if entry_id.level() == 1 {
+                    events.push(json!({
+                        "ph": "M",
+                        "name": "process_name",
+                        "pid": entry_id.slot_index(0),
+                        "args": { "name": long_name },
+                    }));
+                }
+                for (i, slot) in slots.iter().enumerate().rev() {
+                    stack.push((entry_id.child(i as u64), slot));
+                }
+            }
+            EntryInfo::Slot {
+                long_name,
+                max_rows,
+                ..
+            } => {
+                // Slots go in the process of the node they're under, so skip
+                // any that aren't under one (e.g., the root is a slot)
+                let pid = match entry_id.slot_index(0) {
+                    Some(pid) => pid,
+                    None => continue,
+                };
+                if next_tid.len() <= pid as usize {
+                    next_tid.resize(pid as usize + 1, 0);
+                }
+                // Items crossing tile boundaries appear in every tile they
+                // overlap
+                let mut written = BTreeSet::new();
+                for tile_id in source.request_tiles(&entry_id, interval) {
+                    // Item metadata has no interval, so pair it up with the
+                    // corresponding item in the slot tile
+                    let tile = source.fetch_slot_tile(&entry_id, tile_id);
+                    let meta_tile = source.fetch_slot_meta_tile(&entry_id, tile_id);
+                    for (row, (items, metas)) in tile.items.iter().zip(&meta_tile.items).enumerate()
+                    {
+                        let tid = next_tid[pid as usize] + row as u64;
+                        for (item, meta) in items.iter().zip(metas) {
+                            if !written.insert(item.item_uid) {
+                                continue;
+                            }
+                            let clipped = match item.interval.intersection(interval) {
+                                Some(clipped) => clipped,
+                                None => continue,
+                            };
+                            events.push(json!({
+                                "ph": "X",
+                                "name": meta.title,
+                                "pid": pid,
+                                "tid": tid,
+                                "ts": clipped.start.0 as f64 / 1e3,
+                                "dur": clipped.duration_ns() as f64 / 1e3,
+                            }));
+                        }
+                    }
+                }
+                for row in 0..*max_rows {
+                    events.push(json!({
+                        "ph": "M",
+                        "name": "thread_name",
+                        "pid": pid,
+                        "tid": next_tid[pid as usize] + row,
+                        "args": { "name": format!("{long_name} (row {row})") },
+                    }));
+                }
+                next_tid[pid as usize] += *max_rows;
+            }
+            EntryInfo::Summary { .. } => {}
+        }
+    }
+
+    serde_json::to_writer(out, &json!({ "traceEvents": events }))?;
+    Ok(())
+}
+
+fn write_item_row(
+    out: &mut dyn Write,
+    interval: Interval,
+    title: &str,
+    entry: &str,
+) -> io::Result<()> {
+    write_row(
+        out,
+        &[
+            &interval.start.0.to_string(),
+            &interval.stop.0.to_string(),
+            &interval.duration_ns().to_string(),
+            title,
+            entry,
+        ],
+    )
+}
+
+fn write_row(out: &mut dyn Write, fields: &[&str]) -> io::Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+        if field.contains([',', '"', '\r', '\n']) {
+            write!(out, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            out.write_all(field.as_bytes())?;
+        }
+    }
+    out.write_all(b"\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Item, ItemMeta, SlotMetaTile, SlotTile, TileID};
+    use crate::mock::tests::{info, interval, sample, slot_id};
+    use crate::mock::MockDataSource;
+
+    // Item 0 spans the whole profile, but there is no tile in the middle,
+    // so it appears in windows at the start and end but not in between
+    fn sparse() -> MockDataSource {
+        let item = |uid, start, stop| Item {
+            item_uid: ItemUID(uid),
+            interval: Interval::new(Timestamp(start), Timestamp(stop)),
+            color: egui::Color32::RED,
+        };
+        let meta = |uid| ItemMeta {
+            item_uid: ItemUID(uid),
+            title: format!("Task {uid}"),
+            fields: Vec::new(),
+        };
+        let mut source = MockDataSource::new(info(), interval());
+        for (start, stop, other) in [(0, 200, 1), (600, 1000, 2)] {
+            let tile_id = TileID(Interval::new(Timestamp(start), Timestamp(stop)));
+            let items = vec![item(0, 0, 1000), item(other, start + 50, start + 100)];
+            source = source
+                .with_slot_tile(
+                    slot_id(),
+                    SlotTile {
+                        tile_id,
+                        items: vec![items],
+                    },
+                )
+                .with_slot_meta_tile(
+                    slot_id(),
+                    SlotMetaTile {
+                        tile_id,
+                        items: vec![vec![meta(0), meta(other)]],
+                    },
+                );
+        }
+        source
+    }
+
+    fn export(source: &mut dyn DataSource, chunk_ns: Option<i64>) -> String {
+        let mut out = Vec::new();
+        match chunk_ns {
+            Some(ns) => {
+                let chunk = Interval::new(Timestamp(0), Timestamp(ns));
+                export_csv_streaming(source, &slot_id(), interval(), chunk, &mut out)
+            }
+            None => export_csv(source, &slot_id(), interval(), &mut out),
+        }
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn items_are_written_once() {
+        let csv = export(&mut sparse(), None);
+        assert_eq!(csv.matches("Task 0").count(), 1);
+        assert_eq!(csv.lines().count(), 4);
+    }
+
+    #[test]
+    fn streaming_matches_one_shot_export() {
+        for make in [sample, sparse] {
+            let expected = export(&mut make(), None);
+            // Windows smaller than, and not aligned to, items and tiles
+            for chunk_ns in [1, 30, 75, 300, 1000] {
+                assert_eq!(
+                    export(&mut make(), Some(chunk_ns)),
+                    expected,
+                    "chunk of {} ns",
+                    chunk_ns
+                );
+            }
+        }
+    }
+
+    // Two tasks, and one that ends before the export starts
+    fn tasks() -> MockDataSource {
+        let tile_id = TileID(interval());
+        let tasks = [("early", 0, 50), ("Task, \"a\"", 100, 300), ("b", 600, 700)];
+        let items = tasks
+            .iter()
+            .enumerate()
+            .map(|(uid, (_, start, stop))| Item {
+                item_uid: ItemUID(uid as u64),
+                interval: Interval::new(Timestamp(*start), Timestamp(*stop)),
+                color: egui::Color32::RED,
+            })
+            .collect();
+        let metas = tasks
+            .iter()
+            .enumerate()
+            .map(|(uid, (title, _, _))| ItemMeta {
+                item_uid: ItemUID(uid as u64),
+                title: title.to_string(),
+                fields: Vec::new(),
+            })
+            .collect();
+        MockDataSource::new(info(), interval())
+            .with_slot_tile(
+                slot_id(),
+                SlotTile {
+                    tile_id,
+                    items: vec![items],
+                },
+            )
+            .with_slot_meta_tile(
+                slot_id(),
+                SlotMetaTile {
+                    tile_id,
+                    items: vec![metas],
+                },
+            )
+    }
+
+    #[test]
+    fn csv_is_clipped_and_quoted() {
+        let mut out = Vec::new();
+        let clip = Interval::new(Timestamp(200), Timestamp(650));
+        export_csv(&mut tasks(), &slot_id(), clip, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "start_ns,stop_ns,duration_ns,title,entry_id\r\n\
+             200,300,100,\"Task, \"\"a\"\"\",0/0/0\r\n\
+             600,650,50,b,0/0/0\r\n"
+        );
+    }
+
+    #[test]
+    fn chrome_trace_times_are_in_microseconds() {
+        let mut out = Vec::new();
+        let clip = Interval::new(Timestamp(200), Timestamp(650));
+        export_chrome_trace(&mut tasks(), clip, &mut out).unwrap();
+        let trace: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let events: Vec<_> = trace["traceEvents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|event| event["ph"] == "X")
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            *events[0],
+            json!({ "ph": "X", "name": "Task, \"a\"", "pid": 0, "tid": 0, "ts": 0.2, "dur": 0.1 })
+        );
+        assert_eq!(
+            *events[1],
+            json!({ "ph": "X", "name": "b", "pid": 0, "tid": 0, "ts": 0.6, "dur": 0.05 })
+        );
+    }
+
+    #[test]
+    fn chrome_trace_items_are_written_once() {
+        let mut out = Vec::new();
+        export_chrome_trace(&mut sparse(), interval(), &mut out).unwrap();
+        let trace: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let names: Vec<_> = trace["traceEvents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|event| event["ph"] == "X")
+            .map(|event| event["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["Task 0", "Task 1", "Task 2"]);
+    }
+
+    #[test]
+    fn chrome_trace_skips_slots_outside_nodes() {
+        let info = EntryInfo::Slot {
+            short_name: "s".to_owned(),
+            long_name: "slot".to_owned(),
+            max_rows: 1,
+            kind: Default::default(),
+        };
+        let mut out = Vec::new();
+        export_chrome_trace(
+            &mut MockDataSource::new(info, interval()),
+            interval(),
+            &mut out,
+        )
+        .unwrap();
+        let trace: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(trace["traceEvents"], json!([]));
+    }
+
+    #[test]
+    fn streaming_only_remembers_items_crossing_the_window() {
+        let mut source = tasks();
+        let mut written = BTreeMap::new();
+        let mut out = Vec::new();
+        let window = |start, stop| Interval::new(Timestamp(start), Timestamp(stop));
+        let mut write = |start, stop| {
+            let window = window(start, stop);
+            write_window(
+                &mut source,
+                &slot_id(),
+                interval(),
+                window,
+                &mut written,
+                "",
+                &mut out,
+            )
+            .unwrap();
+            written.keys().map(|uid| uid.0).collect::<Vec<_>>()
+        };
+        // "Task, \"a\"" (100 to 300) continues into the next window
+        assert_eq!(write(0, 200), [1]);
+        assert!(write(200, 400).is_empty());
+        assert!(write(400, 1000).is_empty());
+        let csv = String::from_utf8(out).unwrap();
+        assert_eq!(csv.lines().count(), 3);
+    }
+}
@@ -0,0 +1,290 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::data::{
+    tiles_overlapping, DataSource, DataSourceError, EntryID, EntryIndex, EntryInfo, SlotMetaTile,
+    SlotTile, SummaryTile, TileID, TimeOrigin,
+};
+use crate::timestamp::{human_readable_interval, Interval};
+
+// Layout on disk (mirrors the HTTP endpoints):
+//
+//   <root>/manifest.json
+//   <root>/summary_tile/<entry path>/<start>_<stop>.json
+//   <root>/slot_tile/<entry path>/<start>_<stop>.json
+//   <root>/slot_meta_tile/<entry path>/<start>_<stop>.json
+//
+// where the entry path has one directory per level of the EntryID (the slot
+// index, or "summary"), and start/stop are the tile bounds in nanoseconds.
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FileManifest {
+    pub info: EntryInfo,
+    // Also accepts the plain nanosecond form of older manifests
+    #[serde(with = "human_readable_interval")]
+    pub interval: Interval,
+    // Every tile available for each entry
+    pub tiles: Vec<(EntryID, Vec<TileID>)>,
+    // Missing from older manifests
+    #[serde(default)]
+    pub time_origin: TimeOrigin,
+}
+
+pub struct FileDataSource {
+    pub root: PathBuf,
+    manifest: FileManifest,
+    // Why the most recent tile failed to load, or None if it loaded. Tiles
+    // that fail to load are returned empty, so this (and get_errors) is the
+    // only record of what went wrong.
+    pub last_error: Option<String>,
+    // Failed tile reads not yet drained by get_errors
+    errors: Vec<DataSourceError>,
+}
+
+impl FileDataSource {
+    // Only the manifest is read up front, tiles are loaded on demand
+    pub fn new(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        let manifest = read_json(&root.join(MANIFEST_FILE))?;
+        Ok(Self {
+            root,
+            manifest,
+            last_error: None,
+            errors: Vec::new(),
+        })
+    }
+
+    pub fn tile_path(root: &Path, kind: &str, entry_id: &EntryID, tile_id: TileID) -> PathBuf {
+        let mut path = root.join(kind);
+        for level in 0..entry_id.level() {
+            match entry_id.index(level).unwrap() {
+                EntryIndex::Summary => path.push("summary"),
+                EntryIndex::Slot(index) => path.push(index.to_string()),
+            }
+        }
+        path.push(format!("{}_{}.json", tile_id.0.start.0, tile_id.0.stop.0));
+        path
+    }
+
+    fn read_tile<T: DeserializeOwned>(
+        &mut self,
+        kind: &str,
+        entry_id: &EntryID,
+        tile_id: TileID,
+    ) -> Option<T> {
+        let path = Self::tile_path(&self.root, kind, entry_id, tile_id);
+        match read_json(&path) {
+            Ok(tile) => {
+                self.last_error = None;
+                Some(tile)
+            }
+            Err(e) => {
+                let message = format!("{}: {}", path.display(), e);
+                self.errors.push(DataSourceError {
+                    entry_id: entry_id.clone(),
+                    tile_id,
+                    message: message.clone(),
+                });
+                self.last_error = Some(message);
+                None
+            }
+        }
+    }
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> io::Result<T> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+fn check_fetched(source: &mut dyn DataSource) -> io::Result<()> {
+    match source.get_errors().into_iter().next() {
+        Some(error) => Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "unable to fetch tile from {} to {} ns of entry {}: {}",
+                error.tile_id.0.start.0, error.tile_id.0.stop.0, error.entry_id, error.message
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let writer = BufWriter::new(File::create(path)?);
+    Ok(serde_json::to_writer(writer, value)?)
+}
+
+// Fetch every tile of source and write it to out_dir in the layout read by
+// FileDataSource, so that the profile can be viewed without a server.
+// progress is called with the number of entries written so far and the
+// total. The manifest is written last, so an interrupted snapshot can't be
+// opened. Fails on the first tile source can't fetch, rather than writing it
+// out empty.
+pub fn snapshot(
+    source: &mut dyn DataSource,
+    out_dir: &Path,
+    mut progress: impl FnMut(usize, usize),
+) -> io::Result<()> {
+    let value = source.export_manifest();
+    let manifest: FileManifest = serde_json::from_value(value.clone())?;
+    // Only failures from here on concern the snapshot
+    source.get_errors();
+    let total = manifest.tiles.len();
+    for (i, (entry_id, tile_ids)) in manifest.tiles.iter().enumerate() {
+        for &tile_id in tile_ids {
+            if let Some(EntryIndex::Summary) = entry_id.last_index() {
+                let tile = source.fetch_summary_tile(entry_id, tile_id);
+                check_fetched(source)?;
+                let path = FileDataSource::tile_path(out_dir, "summary_tile", entry_id, tile_id);
+                write_json(&path, &tile)?;
+            } else {
+                let tile = source.fetch_slot_tile(entry_id, tile_id);
+                check_fetched(source)?;
+                let path = FileDataSource::tile_path(out_dir, "slot_tile", entry_id, tile_id);
+                write_json(&path, &tile)?;
+                let tile = source.fetch_slot_meta_tile(entry_id, tile_id);
+                check_fetched(source)?;
+                let path = FileDataSource::tile_path(out_dir, "slot_meta_tile", entry_id, tile_id);
+                write_json(&path, &tile)?;
+            }
+        }
+        progress(i + 1, total);
+    }
+    write_json(&out_dir.join(MANIFEST_FILE), &value)
+}
+
+impl DataSource for FileDataSource {
+    fn interval(&mut self) -> Interval {
+        self.manifest.interval
+    }
+
+    fn fetch_info(&mut self) -> EntryInfo {
+        self.manifest.info.clone()
+    }
+
+    fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+        self.manifest
+            .tiles
+            .iter()
+            .find(|(id, _)| id == entry_id)
+            .map(|(_, tiles)| tiles_overlapping(tiles, request_interval))
+            .unwrap_or_default()
+    }
+
+    fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+        self.read_tile("summary_tile", entry_id, tile_id)
+            .unwrap_or_else(|| SummaryTile {
+                tile_id,
+                utilization: Vec::new(),
+            })
+    }
+
+    fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+        self.read_tile("slot_tile", entry_id, tile_id)
+            .unwrap_or_else(|| SlotTile {
+                tile_id,
+                items: Vec::new(),
+            })
+    }
+
+    fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+        self.read_tile("slot_meta_tile", entry_id, tile_id)
+            .unwrap_or_else(|| SlotMetaTile {
+                tile_id,
+                items: Vec::new(),
+            })
+    }
+
+    fn last_error(&self) -> Option<String> {
+        self.last_error.clone()
+    }
+
+    fn get_errors(&mut self) -> Vec<DataSourceError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    fn time_origin(&mut self) -> TimeOrigin {
+        self.manifest.time_origin.clone()
+    }
+
+    // Tiles are served as stored, so this is exact
+    fn tile_byte_estimate(&mut self, entry_id: &EntryID, tile_id: TileID) -> Option<usize> {
+        let kind = match entry_id.index(entry_id.level().checked_sub(1)?)? {
+            EntryIndex::Summary => "summary_tile",
+            EntryIndex::Slot(_) => "slot_tile",
+        };
+        let path = Self::tile_path(&self.root, kind, entry_id, tile_id);
+        let metadata = fs::metadata(path).ok()?;
+        usize::try_from(metadata.len()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::server::tests::temp_dir;
+    use crate::mock::tests::{interval, sample, slot_id, summary_id};
+
+    #[test]
+    fn snapshot_round_trips() {
+        let dir = temp_dir("snapshot-round-trip");
+        let mut progress = Vec::new();
+        snapshot(&mut sample(), &dir, |done, total| {
+            progress.push((done, total))
+        })
+        .unwrap();
+        assert_eq!(progress.last(), Some(&(2, 2)));
+
+        let mut source = FileDataSource::new(&dir).unwrap();
+        let mut expected = sample();
+        assert_eq!(source.interval(), expected.interval());
+        let tile_id = TileID(interval());
+        assert_eq!(source.request_tiles(&slot_id(), interval()), vec![tile_id]);
+        let tile = source.fetch_slot_tile(&slot_id(), tile_id);
+        let expected_tile = expected.fetch_slot_tile(&slot_id(), tile_id);
+        assert_eq!(tile.items[0].len(), expected_tile.items[0].len());
+        let metas = source.fetch_slot_meta_tile(&slot_id(), tile_id);
+        assert_eq!(metas.items[0][3].title, "Task 3");
+        let summary = source.fetch_summary_tile(&summary_id(), tile_id);
+        assert_eq!(summary.utilization.len(), 11);
+        assert_eq!(source.last_error(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn snapshot_fails_on_missing_tiles() {
+        let dir = temp_dir("snapshot-missing-tile");
+        snapshot(&mut sample(), &dir, |_, _| {}).unwrap();
+        let tile_id = TileID(interval());
+        fs::remove_file(FileDataSource::tile_path(
+            &dir,
+            "slot_meta_tile",
+            &slot_id(),
+            tile_id,
+        ))
+        .unwrap();
+
+        let mut source = FileDataSource::new(&dir).unwrap();
+        let copy = temp_dir("snapshot-missing-tile-copy");
+        let error = snapshot(&mut source, &copy, |_, _| {}).unwrap_err();
+        assert!(
+            error.to_string().contains("unable to fetch tile"),
+            "{error}"
+        );
+        // Nothing half-written can be opened
+        assert!(FileDataSource::new(&copy).is_err());
+        assert!(!FileDataSource::tile_path(&copy, "slot_meta_tile", &slot_id(), tile_id).exists());
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_dir_all(&copy).unwrap();
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::BTreeMap;
+
+use crate::data::{
+    DataSource, DataSourceError, EntryID, EntryInfo, ItemMeta, SearchHit, SlotMetaTile, SlotTile,
+    SummaryTile, TileID, TimeOrigin,
+};
+use crate::layer::DataSourceLayer;
+use crate::timestamp::{Interval, Timestamp};
+
+/// Hides the slots of a DataSource that don't match a predicate, e.g., to
+/// show only GPU processors.
+///
+/// The predicate is only applied to slots. Panels are kept as long as some
+/// slot below them is, so that the depth of the entry hierarchy doesn't
+/// change. Summaries are passed through unchanged, and so still include
+/// hidden slots.
+///
+/// Surviving entries are renumbered, so EntryIDs seen by the caller are
+/// translated back before being forwarded.
+pub struct FilteredDataSource<F: Fn(&EntryID, &EntryInfo) -> bool> {
+    data_source: Box<dyn DataSource>,
+    predicate: F,
+    info: Option<EntryInfo>,
+    // Filtered EntryID -> EntryID in data_source
+    ids: BTreeMap<EntryID, EntryID>,
+}
+
+impl<F: Fn(&EntryID, &EntryInfo) -> bool> FilteredDataSource<F> {
+    pub fn new(data_source: Box<dyn DataSource>, predicate: F) -> Self {
+        Self {
+            data_source,
+            predicate,
+            info: None,
+            ids: BTreeMap::new(),
+        }
+    }
+
+    fn filter(
+        &mut self,
+        info: &EntryInfo,
+        entry_id: EntryID,
+        new_id: EntryID,
+    ) -> Option<EntryInfo> {
+        let result = match info {
+            EntryInfo::Panel {
+                short_name,
+                long_name,
+                summary,
+                slots,
+            } => {
+                let mut new_slots = Vec::new();
+                for (i, slot) in slots.iter().enumerate() {
+                    let child = new_id.child(new_slots.len() as u64);
+                    if let Some(slot) = self.filter(slot, entry_id.child(i as u64), child) {
+                        new_slots.push(slot);
+                    }
+                }
+                if new_slots.is_empty() && entry_id.level() > 0 {
+                    return None;
+                }
+                if summary.is_some() {
+                    self.ids.insert(new_id.summary(), entry_id.summary());
+                }
+                EntryInfo::Panel {
+                    short_name: short_name.clone(),
+                    long_name: long_name.clone(),
+                    summary: summary.clone(),
+                    slots: new_slots,
+                }
+            }
+            EntryInfo::Slot { .. } => {
+                if !(self.predicate)(&entry_id, info) {
+                    return None;
+                }
+                info.clone()
+            }
+            EntryInfo::Summary { .. } => unreachable!(),
+        };
+        self.ids.insert(new_id, entry_id);
+        Some(result)
+    }
+
+    fn info(&mut self) -> &EntryInfo {
+        if self.info.is_none() {
+            let info = self.data_source.fetch_info();
+            let filtered = self.filter(&info, EntryID::root(), EntryID::root());
+            self.info = filtered;
+        }
+        self.info.as_ref().unwrap()
+    }
+
+    fn translate(&mut self, entry_id: &EntryID) -> Option<EntryID> {
+        self.info();
+        self.ids.get(entry_id).cloned()
+    }
+
+    // The reverse of translate. None for entries that are filtered out.
+    fn translate_back(&mut self, entry_id: &EntryID) -> Option<EntryID> {
+        self.info();
+        let (new_id, _) = self.ids.iter().find(|(_, id)| *id == entry_id)?;
+        Some(new_id.clone())
+    }
+}
+
+impl<F: Fn(&EntryID, &EntryInfo) -> bool> DataSource for FilteredDataSource<F> {
+    fn interval(&mut self) -> Interval {
+        self.data_source.interval()
+    }
+
+    fn fetch_info(&mut self) -> EntryInfo {
+        self.info().clone()
+    }
+
+    fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+        match self.translate(entry_id) {
+            Some(entry_id) => self.data_source.request_tiles(&entry_id, request_interval),
+            None => Vec::new(),
+        }
+    }
+
+    fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+        match self.translate(entry_id) {
+            Some(entry_id) => self.data_source.fetch_summary_tile(&entry_id, tile_id),
+            None => SummaryTile {
+                tile_id,
+                utilization: Vec::new(),
+            },
+        }
+    }
+
+    fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+        match self.translate(entry_id) {
+            Some(entry_id) => self.data_source.fetch_slot_tile(&entry_id, tile_id),
+            None => SlotTile {
+                tile_id,
+                items: Vec::new(),
+            },
+        }
+    }
+
+    fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+        match self.translate(entry_id) {
+            Some(entry_id) => self.data_source.fetch_slot_meta_tile(&entry_id, tile_id),
+            None => SlotMetaTile {
+                tile_id,
+                items: Vec::new(),
+            },
+        }
+    }
+
+    fn fetch_clipped_slot_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Interval,
+    ) -> SlotTile {
+        match self.translate(entry_id) {
+            Some(entry_id) => self
+                .data_source
+                .fetch_clipped_slot_tile(&entry_id, tile_id, clip),
+            None => SlotTile {
+                tile_id,
+                items: Vec::new(),
+            },
+        }
+    }
+
+    fn fetch_clipped_slot_meta_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Interval,
+    ) -> SlotMetaTile {
+        match self.translate(entry_id) {
+            Some(entry_id) => self
+                .data_source
+                .fetch_clipped_slot_meta_tile(&entry_id, tile_id, clip),
+            None => SlotMetaTile {
+                tile_id,
+                items: Vec::new(),
+            },
+        }
+    }
+
+    fn search(&mut self, query: &str) -> Vec<SearchHit> {
+        // Hits refer to the original EntryIDs, translate them back, dropping
+        // any in hidden slots
+        let hits = self.data_source.search(query);
+        hits.into_iter()
+            .filter_map(|mut hit| {
+                hit.entry_id = self.translate_back(&hit.entry_id)?;
+                Some(hit)
+            })
+            .collect()
+    }
+
+    fn probe(&mut self, entry_id: &EntryID, at: Timestamp) -> Option<ItemMeta> {
+        let entry_id = self.translate(entry_id)?;
+        self.data_source.probe(&entry_id, at)
+    }
+
+    fn next_task_after(&mut self, entry_id: &EntryID, t: Timestamp) -> Option<Interval> {
+        let entry_id = self.translate(entry_id)?;
+        self.data_source.next_task_after(&entry_id, t)
+    }
+
+    fn prev_task_before(&mut self, entry_id: &EntryID, t: Timestamp) -> Option<Interval> {
+        let entry_id = self.translate(entry_id)?;
+        self.data_source.prev_task_before(&entry_id, t)
+    }
+
+    fn last_error(&self) -> Option<String> {
+        self.data_source.last_error()
+    }
+
+    fn get_errors(&mut self) -> Vec<DataSourceError> {
+        // Errors refer to the original EntryIDs, translate them back
+        let errors = self.data_source.get_errors();
+        errors
+            .into_iter()
+            .filter_map(|mut error| {
+                error.entry_id = self.translate_back(&error.entry_id)?;
+                Some(error)
+            })
+            .collect()
+    }
+
+    fn time_origin(&mut self) -> TimeOrigin {
+        self.data_source.time_origin()
+    }
+
+    fn tile_byte_estimate(&mut self, entry_id: &EntryID, tile_id: TileID) -> Option<usize> {
+        let entry_id = self.translate(entry_id)?;
+        self.data_source.tile_byte_estimate(&entry_id, tile_id)
+    }
+
+    fn set_summary_samples(&mut self, samples: usize) {
+        self.data_source.set_summary_samples(samples)
+    }
+
+    fn idle(&mut self) -> bool {
+        self.data_source.idle()
+    }
+}
+
+pub struct FilterLayer<F: Fn(&EntryID, &EntryInfo) -> bool> {
+    pub predicate: F,
+}
+
+impl<F: Fn(&EntryID, &EntryInfo) -> bool> FilterLayer<F> {
+    pub fn new(predicate: F) -> Self {
+        Self { predicate }
+    }
+}
+
+impl<F: Fn(&EntryID, &EntryInfo) -> bool + 'static> DataSourceLayer for FilterLayer<F> {
+    fn wrap(self, inner: Box<dyn DataSource>) -> Box<dyn DataSource> {
+        Box::new(FilteredDataSource::new(inner, self.predicate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Color32, EntryKind, Item, ItemUID};
+    use crate::mock::tests::{interval, item_interval, slot_id, IndexedDataSource};
+    use crate::mock::MockDataSource;
+
+    fn filtered() -> impl DataSource {
+        FilteredDataSource::new(
+            Box::new(IndexedDataSource),
+            |_, info| matches!(info, EntryInfo::Slot { short_name, .. } if short_name != "c0"),
+        )
+    }
+
+    #[test]
+    fn lookups_are_translated() {
+        // c0 is hidden, so c1 is renumbered to take its place
+        let mut source = filtered();
+        let hits = source.search("");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].entry_id, slot_id());
+        assert_eq!(hits[0].interval, Some(item_interval(1)));
+
+        let meta = source.probe(&slot_id(), Timestamp(120)).unwrap();
+        assert_eq!(meta.title, IndexedDataSource::shown_id().to_string());
+        assert_eq!(
+            source.next_task_after(&slot_id(), Timestamp(0)),
+            Some(item_interval(1))
+        );
+        assert_eq!(
+            source.prev_task_before(&slot_id(), Timestamp(1000)),
+            Some(item_interval(1))
+        );
+    }
+
+    #[test]
+    fn hidden_slots_are_not_found() {
+        let mut source = filtered();
+        let hidden = EntryID::root().child(0).child(0).child(1);
+        assert!(source.probe(&hidden, Timestamp(120)).is_none());
+        assert!(source.next_task_after(&hidden, Timestamp(0)).is_none());
+    }
+
+    // The short names of every slot in info
+    fn slot_names(info: &EntryInfo) -> Vec<String> {
+        match info {
+            EntryInfo::Panel { slots, .. } => slots.iter().flat_map(slot_names).collect(),
+            EntryInfo::Slot { short_name, .. } => vec![short_name.clone()],
+            EntryInfo::Summary { .. } => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn info_and_tiles_only_cover_matching_slots() {
+        // Slots c0, c1 and c2 of one kind of one node, where ci has i + 1
+        // items
+        let slot = |i: u64| EntryInfo::Slot {
+            short_name: format!("c{i}"),
+            long_name: format!("CPU {i}"),
+            max_rows: 1,
+            kind: EntryKind::Processor,
+        };
+        let panel = |slots| EntryInfo::Panel {
+            short_name: String::new(),
+            long_name: String::new(),
+            summary: None,
+            slots,
+        };
+        let info = panel(vec![panel(vec![panel((0..3).map(slot).collect())])]);
+        let kind_id = EntryID::root().child(0).child(0);
+        let tile_id = TileID(interval());
+        let item = |i: i64| Item {
+            item_uid: ItemUID(i as u64),
+            interval: item_interval(i),
+            color: Color32::RED,
+        };
+        let source = (0..3).fold(MockDataSource::new(info, interval()), |source, i| {
+            let items = (0..=i as i64).map(item).collect();
+            let tile = SlotTile {
+                tile_id,
+                items: vec![items],
+            };
+            source.with_slot_tile(kind_id.child(i), tile)
+        });
+        let mut source = FilteredDataSource::new(
+            Box::new(source),
+            |_, info| matches!(info, EntryInfo::Slot { short_name, .. } if short_name == "c2"),
+        );
+
+        assert_eq!(slot_names(&source.fetch_info()), vec!["c2"]);
+        // c2 is now the first slot, and there's no second one
+        let tile = source.fetch_slot_tile(&kind_id.child(0), tile_id);
+        assert_eq!(tile.items[0].len(), 3);
+        assert!(source
+            .request_tiles(&kind_id.child(1), interval())
+            .is_empty());
+        let tile = source.fetch_slot_tile(&kind_id.child(1), tile_id);
+        assert!(tile.items.is_empty());
+    }
+}
@@ -1,89 +1,1001 @@
 use crate::{
-    data::{DataSource, EntryID, EntryInfo, SlotMetaTile, SlotTile, SummaryTile, TileID},
-    http::server::FetchRequest,
-    timestamp::Interval,
+    data::{
+        DataSource, DataSourceError, EntryID, EntryInfo, ItemMeta, SlotMetaTile, SlotTile,
+        SummaryTile, TileID, TimeOrigin,
+    },
+    http::server::{ErrorResponse, FetchRequest, ProbeRequest},
+    multiplex::MultiplexDataSource,
+    timestamp::{Interval, Timestamp},
 };
 
-use super::server::FetchTilesRequest;
+use flate2::{write::GzEncoder, Compression};
+use serde::{de::DeserializeOwned, Serialize};
+use url::Url;
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use super::fetch::{self, DefaultTransport, FetchConfig, Request, Response, Transport};
+use super::server::{FetchTilesRequest, BINARY_CONTENT_TYPE, MAX_BATCH_SIZE, MAX_SUMMARY_SAMPLES};
+use super::websocket::WebSocketDataSource;
+
+// Most recently used probe results kept. Hovering over the same spot asks
+// for the same item every frame, but the mouse rarely comes back to an
+// exact timestamp once it has moved on.
+const MAX_CACHED_PROBES: usize = 64;
+
+// Most recently used tile size estimates kept
+const MAX_CACHED_TILE_SIZES: usize = 1024;
+
+// Endpoints that older servers don't have. Those answer with a bare 404
+// (without an ErrorResponse), which means "unsupported" rather than failed.
+const OPTIONAL_PATHS: &[&str] = &["time_origin", "tile_size"];
+
+// Smaller request bodies aren't worth compressing
+const MIN_COMPRESSED_BODY: usize = 1024;
+
+// Total size of the bodies kept for ETags by default
+const DEFAULT_ETAG_BYTE_BUDGET: usize = 32 << 20;
+
+// The capacity most recently used results, least recently used first
+struct RecentResults<K, V> {
+    capacity: usize,
+    entries: VecDeque<(K, V)>,
+}
+
+impl<K: PartialEq, V: Clone> RecentResults<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::new(),
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        let index = self.entries.iter().position(|(cached, _)| cached == key)?;
+        let entry = self.entries.remove(index).unwrap();
+        let value = entry.1.clone();
+        self.entries.push_back(entry);
+        Some(value)
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((key, value));
+    }
+}
+
+struct CachedBody {
+    etag: String,
+    is_binary: bool,
+    body: Vec<u8>,
+    last_used: u64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    // Some requests have failed, but fewer than max_failures in a row
+    Reconnecting,
+    Failed,
+}
 
 pub struct HTTPDataSource {
     pub host: String,
     pub port: u16,
-    pub client: reqwest::blocking::Client,
+    pub transport: Box<dyn Transport>,
+    pub config: FetchConfig,
+    // Why the most recent request failed, or None if it succeeded. Failed
+    // fetches return empty results rather than panicking, so this (and
+    // get_errors) is the only record of what went wrong.
+    pub last_error: Option<String>,
+    // Failed tile fetches not yet drained by get_errors
+    errors: Vec<DataSourceError>,
+    // Sent as "Authorization: Bearer <token>" on every request
+    pub token: Option<String>,
+    // Ask the server for bincode-encoded tiles instead of JSON
+    pub binary: bool,
+    // Ask the server to resample summary tiles to this many points, e.g.,
+    // to match the width of the display in pixels
+    pub summary_samples: Option<usize>,
+    // Bodies of responses that carried an ETag, so that a 304 can be served
+    // locally. Keyed by the request as logged by fetch. Evicted in
+    // least-recently-used order once they add up to more than
+    // etag_byte_budget.
+    etags: BTreeMap<String, CachedBody>,
+    etag_lru: BTreeMap<u64, String>, // last_used -> key
+    etag_bytes: usize,
+    tick: u64,
+    pub etag_byte_budget: usize,
+    probes: RecentResults<(EntryID, Timestamp), Option<ItemMeta>>,
+    tile_sizes: RecentResults<(EntryID, TileID), Option<usize>>,
+    // Fetched once, since it never changes
+    time_origin: Option<TimeOrigin>,
+    // OPTIONAL_PATHS the server turned out not to have
+    unsupported: BTreeSet<String>,
+    // Consecutive requests that got no response (or a server error) before
+    // the state becomes Failed
+    pub max_failures: u32,
+    failures: u32,
+    state: ConnectionState,
+    // Called whenever the connection state changes
+    on_connection_change: Option<Box<dyn Fn(ConnectionState)>>,
+    // Gzip large request bodies (e.g., batches), if the server accepts it
+    pub compress_requests: bool,
+    // Whether the last response listed gzip in Accept-Encoding
+    server_accepts_gzip: bool,
+}
+
+// Parses a comma-separated list of server URLs, e.g.,
+// "http://localhost:8080,http://localhost:8081". The port defaults to 80.
+// ws:// URLs fetch tiles over a WebSocket instead (see WebSocketDataSource).
+pub fn parse_urls(urls: &str) -> Result<Vec<Url>, String> {
+    let mut result = Vec::new();
+    for url in urls.split(',').map(str::trim) {
+        if url.is_empty() {
+            continue;
+        }
+        let parsed = Url::parse(url).map_err(|e| format!("invalid server URL {url:?}: {e}"))?;
+        if parsed.scheme() != "http" && parsed.scheme() != "ws" {
+            return Err(format!(
+                "invalid server URL {url:?}: expected http:// or ws://, not {}://",
+                parsed.scheme()
+            ));
+        }
+        if parsed.host_str().is_none() {
+            return Err(format!("invalid server URL {url:?}: no host"));
+        }
+        result.push(parsed);
+    }
+    if result.is_empty() {
+        return Err("no server URL given".to_owned());
+    }
+    Ok(result)
+}
+
+// One HTTPDataSource (or WebSocketDataSource) per URL, multiplexed if there
+// are several
+pub fn connect(urls: &[Url]) -> Box<dyn DataSource> {
+    let mut data_sources: Vec<Box<dyn DataSource>> = urls
+        .iter()
+        .map(|url| -> Box<dyn DataSource> {
+            if url.scheme() == "ws" {
+                Box::new(WebSocketDataSource::from_url(url))
+            } else {
+                Box::new(HTTPDataSource::from_url(url))
+            }
+        })
+        .collect();
+    if data_sources.len() == 1 {
+        data_sources.pop().unwrap()
+    } else {
+        Box::new(MultiplexDataSource::new(data_sources))
+    }
 }
 
 impl HTTPDataSource {
+    // The URL must have a host, as checked by parse_urls
+    pub fn from_url(url: &Url) -> Self {
+        Self::new(
+            url.host_str().unwrap().to_owned(),
+            url.port_or_known_default().unwrap_or(80),
+        )
+    }
+
     pub fn new(host: String, port: u16) -> Self {
         Self {
             host,
             port,
-            client: reqwest::blocking::ClientBuilder::new()
-                .timeout(std::time::Duration::from_secs(5))
-                .gzip(true)
-                .brotli(true)
-                .build()
-                .unwrap(),
+            transport: Box::<DefaultTransport>::default(),
+            config: FetchConfig::default(),
+            last_error: None,
+            errors: Vec::new(),
+            token: None,
+            binary: false,
+            summary_samples: None,
+            etags: BTreeMap::new(),
+            etag_lru: BTreeMap::new(),
+            etag_bytes: 0,
+            tick: 0,
+            etag_byte_budget: DEFAULT_ETAG_BYTE_BUDGET,
+            probes: RecentResults::new(MAX_CACHED_PROBES),
+            tile_sizes: RecentResults::new(MAX_CACHED_TILE_SIZES),
+            time_origin: None,
+            unsupported: BTreeSet::new(),
+            max_failures: 3,
+            failures: 0,
+            state: ConnectionState::Connected,
+            on_connection_change: None,
+            compress_requests: true,
+            server_accepts_gzip: false,
         }
     }
+
+    pub fn with_transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Box::new(transport);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    pub fn with_token(mut self, token: String) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    pub fn with_summary_samples(mut self, samples: usize) -> Self {
+        self.set_summary_samples(samples);
+        self
+    }
+
+    pub fn with_etag_byte_budget(mut self, etag_byte_budget: usize) -> Self {
+        self.etag_byte_budget = etag_byte_budget;
+        self
+    }
+
+    pub fn with_max_failures(mut self, max_failures: u32) -> Self {
+        self.max_failures = max_failures;
+        self
+    }
+
+    pub fn with_connection_callback(
+        mut self,
+        callback: impl Fn(ConnectionState) + 'static,
+    ) -> Self {
+        self.on_connection_change = Some(Box::new(callback));
+        self
+    }
+
+    pub fn with_request_compression(mut self, compress_requests: bool) -> Self {
+        self.compress_requests = compress_requests;
+        self
+    }
+
+    pub fn connection_state(&self) -> ConnectionState {
+        self.state
+    }
+
+    // Fetch many slot tiles with as few round trips as possible. Results are
+    // in the same order as requests. Requests are split into batches of at
+    // most MAX_BATCH_SIZE to keep each payload bounded.
+    pub fn fetch_slot_tile_batch(&mut self, requests: &[FetchRequest]) -> Vec<SlotTile> {
+        let mut result = Vec::with_capacity(requests.len());
+        for chunk in requests.chunks(MAX_BATCH_SIZE) {
+            let request = self.get_json("slot_tile_batch", chunk);
+            let detail = format!("{} tiles", chunk.len());
+            match self.fetch::<Vec<SlotTile>>("slot_tile_batch", &detail, request) {
+                Some(tiles) if tiles.len() == chunk.len() => result.extend(tiles),
+                _ => {
+                    for request in chunk {
+                        self.record_error(&request.entry_id, request.tile_id);
+                        result.push(SlotTile {
+                            tile_id: request.tile_id,
+                            items: Vec::new(),
+                        });
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    // The server only sends the items overlapping clip, if any
+    fn slot_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Option<Interval>,
+    ) -> SlotTile {
+        self.fetch_tile("slot_tile", entry_id, tile_id, None, clip)
+            .unwrap_or_else(|| SlotTile {
+                tile_id,
+                items: Vec::new(),
+            })
+    }
+
+    fn slot_meta_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Option<Interval>,
+    ) -> SlotMetaTile {
+        self.fetch_tile("slot_meta_tile", entry_id, tile_id, None, clip)
+            .unwrap_or_else(|| SlotMetaTile {
+                tile_id,
+                items: Vec::new(),
+            })
+    }
+
+    // Identifies the server, e.g., to key a PersistentTileCache
+    pub fn base_url(&self) -> String {
+        self.url("")
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("http://{}:{}/{}", self.host, self.port, path)
+    }
+
+    // A GET with a JSON body, gzipped if the server has said it accepts that
+    // and the body is big enough to benefit
+    fn get_json<T: Serialize + ?Sized>(&self, path: &str, body: &T) -> Request {
+        let json = serde_json::to_vec(body).expect("unable to serialize request");
+        let request = Request::get(self.url(path)).header("content-type", "application/json");
+        if !self.compress_requests || !self.server_accepts_gzip || json.len() < MIN_COMPRESSED_BODY
+        {
+            return request.body(json);
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&json).unwrap();
+        request
+            .header("content-encoding", "gzip")
+            .body(encoder.finish().unwrap())
+    }
+
+    // path and detail only identify the request in the log
+    fn fetch<T: DeserializeOwned>(
+        &mut self,
+        path: &str,
+        detail: &str,
+        mut request: Request,
+    ) -> Option<T> {
+        if let Some(token) = &self.token {
+            request = request.header("authorization", format!("Bearer {token}"));
+        }
+        if self.binary {
+            request = request.header("accept", BINARY_CONTENT_TYPE);
+        }
+        if self.unsupported.contains(path) {
+            return None;
+        }
+        let key = format!("/{path} {detail}");
+        if let Some(cached) = self.etags.get(&key) {
+            request = request.header("if-none-match", cached.etag.clone());
+        }
+        log::debug!("GET {key}");
+        let start = Instant::now();
+        let response = fetch::send(&self.config, self.transport.as_ref(), &request);
+        // Anything short of a server error means the server is reachable,
+        // even if the request itself was bad
+        self.update_connection(match &response {
+            Ok(response) => !response.is_server_error(),
+            Err(_) => false,
+        });
+        // Servers advertise which encodings they accept for request bodies
+        // in responses (RFC 7694). Error responses might come from a proxy.
+        if let Ok(response) = &response {
+            if !response.is_error() {
+                self.server_accepts_gzip = response
+                    .header_values("accept-encoding")
+                    .any(|value| value.contains("gzip"));
+            }
+            let unsupported = response.status == 404
+                && OPTIONAL_PATHS.contains(&path)
+                && serde_json::from_slice::<ErrorResponse>(&response.body).is_err();
+            if unsupported {
+                log::info!("server does not support /{path}");
+                self.unsupported.insert(path.to_owned());
+                return None;
+            }
+        }
+        let result = response
+            .and_then(Self::check_status)
+            .and_then(|response| self.read_body(&key, response))
+            .and_then(|(is_binary, body)| {
+                Self::decode(is_binary, &body).map(|value| (value, body.len()))
+            });
+        if let Ok((_, bytes)) = &result {
+            log::debug!("GET {key}: {bytes} bytes in {:?}", start.elapsed());
+        }
+        match result.map(|(value, _)| value) {
+            Ok(value) => {
+                self.last_error = None;
+                Some(value)
+            }
+            Err(e) => {
+                self.last_error = Some(e);
+                None
+            }
+        }
+    }
+
+    fn update_connection(&mut self, success: bool) {
+        let state = if success {
+            self.failures = 0;
+            ConnectionState::Connected
+        } else {
+            self.failures += 1;
+            if self.failures >= self.max_failures {
+                ConnectionState::Failed
+            } else {
+                ConnectionState::Reconnecting
+            }
+        };
+        if state != self.state {
+            self.state = state;
+            if let Some(callback) = &self.on_connection_change {
+                callback(state);
+            }
+        }
+    }
+
+    // Failed requests (e.g., a 404 for an unknown entry) come with an
+    // ErrorResponse saying why, at least when the server itself answered
+    fn check_status(response: Response) -> Result<Response, String> {
+        if !response.is_error() {
+            return Ok(response);
+        }
+        let status = response.status_line();
+        Err(
+            serde_json::from_slice::<ErrorResponse>(&response.body).map_or_else(
+                |_| status.clone(),
+                |body| format!("{status}: {}", body.error),
+            ),
+        )
+    }
+
+    // Returns whether the body is bincode, and the body itself
+    fn read_body(&mut self, key: &str, response: Response) -> Result<(bool, Vec<u8>), String> {
+        if response.status == 304 {
+            self.tick += 1;
+            let cached = self
+                .etags
+                .get_mut(key)
+                .ok_or_else(|| "not modified, but no cached response".to_owned())?;
+            self.etag_lru.remove(&cached.last_used);
+            self.etag_lru.insert(self.tick, key.to_owned());
+            cached.last_used = self.tick;
+            return Ok((cached.is_binary, cached.body.clone()));
+        }
+
+        // Servers only send bincode when asked to, so go by the content type
+        // of the response rather than by self.binary
+        let is_binary = response
+            .header("content-type")
+            .map_or(false, |value| value == BINARY_CONTENT_TYPE);
+        let etag = response.header("etag").map(str::to_owned);
+        let body = response.body;
+        if let Some(etag) = etag {
+            self.cache_body(key, etag, is_binary, &body);
+        }
+        Ok((is_binary, body))
+    }
+
+    fn cache_body(&mut self, key: &str, etag: String, is_binary: bool, body: &[u8]) {
+        if let Some(old) = self.etags.remove(key) {
+            self.etag_lru.remove(&old.last_used);
+            self.etag_bytes -= old.body.len();
+        }
+        // Bodies that would evict everything else and still not fit are
+        // not kept
+        if body.len() <= self.etag_byte_budget {
+            self.tick += 1;
+            self.etag_lru.insert(self.tick, key.to_owned());
+            self.etag_bytes += body.len();
+            self.etags.insert(
+                key.to_owned(),
+                CachedBody {
+                    etag,
+                    is_binary,
+                    body: body.to_vec(),
+                    last_used: self.tick,
+                },
+            );
+        }
+
+        while self.etag_bytes > self.etag_byte_budget {
+            let oldest = *self.etag_lru.keys().next().unwrap();
+            let key = self.etag_lru.remove(&oldest).unwrap();
+            let cached = self.etags.remove(&key).unwrap();
+            self.etag_bytes -= cached.body.len();
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(is_binary: bool, body: &[u8]) -> Result<T, String> {
+        if is_binary {
+            bincode::deserialize(body).map_err(|e| e.to_string())
+        } else {
+            serde_json::from_slice(body).map_err(|e| e.to_string())
+        }
+    }
+
+    fn fetch_tile<T: DeserializeOwned>(
+        &mut self,
+        path: &str,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        samples: Option<usize>,
+        clip: Option<Interval>,
+    ) -> Option<T> {
+        let body = FetchRequest {
+            entry_id: entry_id.clone(),
+            tile_id,
+            samples,
+            clip,
+        };
+        let request = self.get_json(path, &body);
+        let result = self.fetch(path, &format!("{body:?}"), request);
+        if result.is_none() {
+            self.record_error(entry_id, tile_id);
+        }
+        result
+    }
+
+    fn record_error(&mut self, entry_id: &EntryID, tile_id: TileID) {
+        let message = self
+            .last_error
+            .clone()
+            .unwrap_or_else(|| "unexpected response".to_owned());
+        self.errors.push(DataSourceError {
+            entry_id: entry_id.clone(),
+            tile_id,
+            message,
+        });
+    }
 }
 
 impl DataSource for HTTPDataSource {
     fn interval(&mut self) -> Interval {
-        let resp = self
-            .client
-            .get(format!("http://{}:{}/interval", self.host, self.port))
-            .send();
-        resp.unwrap().json::<Interval>().unwrap()
+        let request = Request::get(self.url("interval"));
+        self.fetch("interval", "", request).unwrap_or_default()
     }
     fn fetch_info(&mut self) -> EntryInfo {
-        let resp = self
-            .client
-            .get(format!("http://{}:{}/info", self.host, self.port))
-            .send();
-        resp.unwrap().json::<EntryInfo>().unwrap()
+        let request = Request::get(self.url("info"));
+        self.fetch("info", "", request)
+            .unwrap_or_else(|| EntryInfo::Panel {
+                short_name: "root".to_owned(),
+                long_name: "root".to_owned(),
+                summary: None,
+                slots: Vec::new(),
+            })
     }
     fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
-        let resp = self
-            .client
-            .get(format!("http://{}:{}/tiles", self.host, self.port))
-            .json(&FetchTilesRequest {
-                entry_id: entry_id.clone(),
-                interval: request_interval,
-            })
-            .send();
-        resp.unwrap().json::<Vec<TileID>>().unwrap()
+        let body = FetchTilesRequest {
+            entry_id: entry_id.clone(),
+            interval: request_interval,
+        };
+        let request = self.get_json("tiles", &body);
+        self.fetch("tiles", &format!("{body:?}"), request)
+            .unwrap_or_default()
     }
     fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
-        let resp = self
-            .client
-            .get(format!("http://{}:{}/summary_tile", self.host, self.port))
-            .json(&FetchRequest {
-                entry_id: entry_id.clone(),
-                tile_id: tile_id.clone(),
+        let samples = self.summary_samples;
+        self.fetch_tile("summary_tile", entry_id, tile_id, samples, None)
+            .unwrap_or_else(|| SummaryTile {
+                tile_id,
+                utilization: Vec::new(),
             })
-            .send();
-        resp.unwrap().json::<SummaryTile>().unwrap()
     }
     fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
-        let resp = self
-            .client
-            .get(format!("http://{}:{}/slot_tile", self.host, self.port))
-            .json(&FetchRequest {
-                entry_id: entry_id.clone(),
-                tile_id: tile_id.clone(),
-            })
-            .send();
-        resp.unwrap().json::<SlotTile>().unwrap()
+        self.slot_tile(entry_id, tile_id, None)
     }
     fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
-        let resp = self
-            .client
-            .get(format!("http://{}:{}/slot_meta_tile", self.host, self.port))
-            .json(&FetchRequest {
+        self.slot_meta_tile(entry_id, tile_id, None)
+    }
+    fn fetch_clipped_slot_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Interval,
+    ) -> SlotTile {
+        self.slot_tile(entry_id, tile_id, Some(clip))
+    }
+    fn fetch_clipped_slot_meta_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Interval,
+    ) -> SlotMetaTile {
+        self.slot_meta_tile(entry_id, tile_id, Some(clip))
+    }
+    // All the tiles in as few round trips as possible, rather than one each
+    fn poll_slot_tiles(&mut self, entry_id: &EntryID, interval: Interval) -> Option<Vec<SlotTile>> {
+        let requests: Vec<_> = self
+            .request_tiles(entry_id, interval)
+            .into_iter()
+            .map(|tile_id| FetchRequest {
                 entry_id: entry_id.clone(),
-                tile_id: tile_id.clone(),
+                tile_id,
+                samples: None,
+                clip: Some(interval),
+            })
+            .collect();
+        Some(self.fetch_slot_tile_batch(&requests))
+    }
+    fn probe(&mut self, entry_id: &EntryID, at: Timestamp) -> Option<ItemMeta> {
+        let key = (entry_id.clone(), at);
+        if let Some(item) = self.probes.get(&key) {
+            return item;
+        }
+        let body = ProbeRequest {
+            entry_id: entry_id.clone(),
+            at,
+        };
+        let request = self.get_json("probe", &body);
+        // Failures are not cached, so that they are retried
+        let item = self.fetch::<Option<ItemMeta>>("probe", &format!("{body:?}"), request)?;
+        self.probes.insert(key, item.clone());
+        item
+    }
+    fn last_error(&self) -> Option<String> {
+        self.last_error.clone()
+    }
+    fn get_errors(&mut self) -> Vec<DataSourceError> {
+        std::mem::take(&mut self.errors)
+    }
+    // Servers without /time_origin have the default origin
+    fn time_origin(&mut self) -> TimeOrigin {
+        if let Some(origin) = &self.time_origin {
+            return origin.clone();
+        }
+        let request = Request::get(self.url("time_origin"));
+        let origin = self.fetch("time_origin", "", request);
+        // Failures are not cached, so that they are retried
+        if origin.is_some() || self.unsupported.contains("time_origin") {
+            self.time_origin = Some(origin.clone().unwrap_or_default());
+        }
+        origin.unwrap_or_default()
+    }
+    // Clamped to what the server accepts
+    fn set_summary_samples(&mut self, samples: usize) {
+        self.summary_samples = Some(samples.clamp(1, MAX_SUMMARY_SAMPLES));
+    }
+    fn tile_byte_estimate(&mut self, entry_id: &EntryID, tile_id: TileID) -> Option<usize> {
+        let body = FetchRequest {
+            entry_id: entry_id.clone(),
+            tile_id,
+            samples: None,
+            clip: None,
+        };
+        let key = (entry_id.clone(), tile_id);
+        if let Some(bytes) = self.tile_sizes.get(&key) {
+            return bytes;
+        }
+        let request = self.get_json("tile_size", &body);
+        // Failures are not cached, so that they are retried
+        let bytes = self.fetch::<Option<usize>>("tile_size", &format!("{body:?}"), request)?;
+        self.tile_sizes.insert(key, bytes);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::fetch::SendError;
+    use crate::http::server::tests::{capture_logs, serve};
+    use crate::mock::tests::{interval, sample, slot_id, summary_id};
+
+    use std::cell::{Cell, RefCell};
+    use std::net::TcpListener;
+    use std::rc::Rc;
+
+    #[test]
+    fn probes_are_cached_and_bounded() {
+        let mut client = serve(sample());
+        let probe = client.probe(&slot_id(), Timestamp(120)).unwrap();
+        assert_eq!(probe.item_uid.0, 1);
+        assert_eq!(client.probes.entries.len(), 1);
+        assert_eq!(
+            client.probe(&slot_id(), Timestamp(120)).unwrap().item_uid.0,
+            1
+        );
+        assert_eq!(client.probes.entries.len(), 1);
+        for at in 0..MAX_CACHED_PROBES as i64 * 2 {
+            client.probe(&slot_id(), Timestamp(at));
+        }
+        assert_eq!(client.probes.entries.len(), MAX_CACHED_PROBES);
+        assert_eq!(client.probe(&slot_id(), Timestamp(i64::MAX)), None);
+        assert_eq!(client.last_error, None);
+    }
+
+    #[test]
+    fn last_error_is_cleared_by_success() {
+        let mut client = serve(sample());
+        let bad_tile = TileID(Interval::new(Timestamp(-10), Timestamp(10)));
+        client.fetch_slot_tile(&slot_id(), bad_tile);
+        assert!(client.last_error().unwrap().starts_with("400"));
+        client.fetch_slot_tile(&slot_id(), TileID(interval()));
+        assert_eq!(client.last_error(), None);
+        // The failure is still reported once
+        let errors = client.get_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].tile_id, bad_tile);
+        assert!(client.get_errors().is_empty());
+    }
+
+    #[test]
+    fn etag_bodies_are_bounded() {
+        let mut client = serve(sample());
+        let tile_id = TileID(interval());
+        let items = client.fetch_slot_tile(&slot_id(), tile_id).items;
+        let tile_bytes = client.etag_bytes;
+        assert!(tile_bytes > 0);
+        assert_eq!(client.etags.len(), 1);
+
+        // Served from the cached body
+        let again = client.fetch_slot_tile(&slot_id(), tile_id).items;
+        assert_eq!(again[0].len(), items[0].len());
+        assert_eq!(client.etag_bytes, tile_bytes);
+
+        // Only the most recently used body fits
+        client.etag_byte_budget = tile_bytes;
+        client.fetch_slot_meta_tile(&slot_id(), tile_id);
+        assert!(client.etag_bytes <= tile_bytes);
+        assert_eq!(client.etags.len(), 1);
+        assert_eq!(client.etag_lru.len(), 1);
+
+        // Bodies too big for the budget aren't kept at all
+        client.etag_byte_budget = 1;
+        client.fetch_summary_tile(&summary_id(), tile_id);
+        assert_eq!(client.etag_bytes, 0);
+        assert!(client.etags.is_empty());
+        assert_eq!(client.last_error, None);
+    }
+
+    // Answers everything with a bare 404, like servers that predate an
+    // endpoint, and counts the requests
+    struct NotFound(Rc<Cell<usize>>);
+
+    impl Transport for NotFound {
+        fn send(&self, _: &Request, _: Duration) -> Result<Response, SendError> {
+            self.0.set(self.0.get() + 1);
+            Ok(Response {
+                status: 404,
+                status_text: "Not Found".to_owned(),
+                headers: Vec::new(),
+                body: Vec::new(),
             })
-            .send();
-        resp.unwrap().json::<SlotMetaTile>().unwrap()
+        }
+    }
+
+    #[test]
+    fn missing_optional_endpoints_are_unsupported() {
+        let requests = Rc::new(Cell::new(0));
+        let mut client = HTTPDataSource::new("localhost".to_owned(), 1)
+            .with_transport(NotFound(requests.clone()));
+        assert_eq!(client.time_origin(), TimeOrigin::default());
+        assert_eq!(
+            client.tile_byte_estimate(&slot_id(), TileID(interval())),
+            None
+        );
+        assert_eq!(client.last_error, None);
+        assert_eq!(requests.get(), 2);
+
+        // Not asked again
+        client.time_origin();
+        client.tile_byte_estimate(&slot_id(), TileID(interval()));
+        assert_eq!(requests.get(), 2);
+
+        // Required endpoints are still errors
+        client.interval();
+        assert_eq!(client.last_error.as_deref(), Some("404 Not Found"));
+    }
+
+    #[test]
+    fn time_origin_and_tile_sizes_are_cached() {
+        let origin = TimeOrigin {
+            epoch: "process start".to_owned(),
+            wall_clock: Some(Timestamp(1_000)),
+        };
+        let mut client = serve(sample().with_time_origin(origin.clone()));
+        assert_eq!(client.time_origin(), origin);
+        assert_eq!(client.time_origin, Some(origin));
+        let bytes = client.tile_byte_estimate(&slot_id(), TileID(interval()));
+        assert!(bytes.is_some());
+        assert_eq!(client.tile_sizes.entries.len(), 1);
+        assert_eq!(
+            client.tile_byte_estimate(&slot_id(), TileID(interval())),
+            bytes
+        );
+        assert_eq!(client.tile_sizes.entries.len(), 1);
+        assert_eq!(client.last_error, None);
+    }
+
+    #[test]
+    fn unreachable_servers_are_errors() {
+        // Nothing listens on a port that was just freed
+        let port = TcpListener::bind(("127.0.0.1", 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut client = HTTPDataSource::new("127.0.0.1".to_owned(), port);
+        client.config.base_delay = Duration::from_millis(1);
+        let tile_id = TileID(interval());
+        let tile = client.fetch_slot_tile(&slot_id(), tile_id);
+        assert_eq!(tile.tile_id, tile_id);
+        assert!(tile.items.is_empty());
+        let error = client.last_error().unwrap();
+        assert!(
+            error.to_lowercase().contains("connection refused"),
+            "{error}"
+        );
+        let errors = client.get_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].tile_id, tile_id);
+    }
+
+    #[test]
+    fn slow_servers_time_out() {
+        // Connections are queued by the OS, but never answered
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut client = HTTPDataSource::new("127.0.0.1".to_owned(), port)
+            .with_timeout(Duration::from_millis(200));
+        client.fetch_slot_tile(&slot_id(), TileID(interval()));
+        assert_eq!(
+            client.last_error().as_deref(),
+            Some("request timed out after 0.2s")
+        );
+    }
+
+    #[test]
+    fn each_failed_tile_is_reported_once() {
+        let port = TcpListener::bind(("127.0.0.1", 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut client = HTTPDataSource::new("127.0.0.1".to_owned(), port);
+        client.config.retries = 0;
+        // Not about any tile
+        client.interval();
+        assert!(client.last_error().is_some());
+        assert!(client.get_errors().is_empty());
+
+        let summary_tile = TileID(Interval::new(Timestamp(0), Timestamp(500)));
+        let slot_tile = TileID(interval());
+        client.fetch_summary_tile(&summary_id(), summary_tile);
+        client.fetch_slot_meta_tile(&slot_id(), slot_tile);
+        let errors = client.get_errors();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            (&errors[0].entry_id, errors[0].tile_id),
+            (&summary_id(), summary_tile)
+        );
+        assert_eq!(
+            (&errors[1].entry_id, errors[1].tile_id),
+            (&slot_id(), slot_tile)
+        );
+        for error in &errors {
+            assert!(
+                error.message.to_lowercase().contains("refused"),
+                "{error:?}"
+            );
+        }
+        assert!(client.get_errors().is_empty());
+    }
+
+    #[test]
+    fn requests_are_logged_with_tile_and_size() {
+        let mut client = serve(sample());
+        let tile_id = TileID(interval());
+        let logs = capture_logs(|| {
+            client.fetch_slot_tile(&slot_id(), tile_id);
+        });
+        let tile = format!("{tile_id:?}");
+        assert!(
+            logs.iter()
+                .any(|line| line.starts_with("GET /slot_tile ") && line.contains(&tile)),
+            "{logs:?}"
+        );
+        // The body is also what the ETag cache holds
+        let size = format!(": {} bytes in ", client.etag_bytes);
+        assert!(
+            logs.iter()
+                .any(|line| line.contains(&tile) && line.contains(&size)),
+            "{logs:?}"
+        );
+    }
+
+    // Fails to send while down, and otherwise answers with interval()
+    struct Flaky(Rc<Cell<bool>>);
+
+    impl Transport for Flaky {
+        fn send(&self, _: &Request, _: Duration) -> Result<Response, SendError> {
+            if self.0.get() {
+                return Err(SendError::Failed("connection refused".to_owned()));
+            }
+            Ok(Response {
+                status: 200,
+                status_text: "OK".to_owned(),
+                headers: Vec::new(),
+                body: serde_json::to_vec(&interval()).unwrap(),
+            })
+        }
+    }
+
+    #[test]
+    fn connection_state_follows_failures() {
+        let down = Rc::new(Cell::new(true));
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let mut client = HTTPDataSource::new("localhost".to_owned(), 1)
+            .with_transport(Flaky(down.clone()))
+            .with_max_failures(3)
+            .with_connection_callback({
+                let changes = changes.clone();
+                move |state| changes.borrow_mut().push(state)
+            });
+        client.config.retries = 0;
+        assert_eq!(client.connection_state(), ConnectionState::Connected);
+
+        let mut states = Vec::new();
+        for _ in 0..4 {
+            client.interval();
+            states.push(client.connection_state());
+        }
+        assert_eq!(
+            states,
+            vec![
+                ConnectionState::Reconnecting,
+                ConnectionState::Reconnecting,
+                ConnectionState::Failed,
+                ConnectionState::Failed,
+            ]
+        );
+
+        down.set(false);
+        assert_eq!(client.interval(), interval());
+        assert_eq!(client.connection_state(), ConnectionState::Connected);
+        // Only changes are reported
+        assert_eq!(
+            *changes.borrow(),
+            vec![
+                ConnectionState::Reconnecting,
+                ConnectionState::Failed,
+                ConnectionState::Connected,
+            ]
+        );
+    }
+
+    #[test]
+    fn tile_byte_estimates_match_serialized_sizes() {
+        let mut client = serve(sample());
+        let tile_id = TileID(interval());
+        let tile = client.fetch_slot_tile(&slot_id(), tile_id);
+        let actual = serde_json::to_vec(&tile).unwrap().len();
+        let estimate = client.tile_byte_estimate(&slot_id(), tile_id).unwrap();
+        assert!(
+            estimate.abs_diff(actual) <= actual / 10,
+            "{estimate} vs {actual}"
+        );
+
+        // Unknown tiles have no size
+        let other = TileID(Interval::new(Timestamp(0), Timestamp(500)));
+        assert_eq!(client.tile_byte_estimate(&slot_id(), other), None);
+    }
+
+    #[test]
+    fn server_urls_are_parsed_and_validated() {
+        let urls = parse_urls("http://localhost:8080, http://example.com,").unwrap();
+        let hosts: Vec<_> = urls
+            .iter()
+            .map(|url| {
+                let client = HTTPDataSource::from_url(url);
+                (client.host, client.port)
+            })
+            .collect();
+        assert_eq!(
+            hosts,
+            vec![
+                ("localhost".to_owned(), 8080),
+                ("example.com".to_owned(), 80)
+            ]
+        );
+
+        let error = parse_urls("http://localhost:8080,localhost:8081").unwrap_err();
+        assert!(
+            error.starts_with("invalid server URL \"localhost:8081\""),
+            "{error}"
+        );
+        let error = parse_urls("https://example.com").unwrap_err();
+        assert!(error.ends_with("not https://"), "{error}");
+        assert_eq!(parse_urls(" , "), Err("no server URL given".to_owned()));
     }
 }
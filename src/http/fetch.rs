@@ -0,0 +1,496 @@
+use std::fmt;
+use std::time::Duration;
+
+#[cfg(not(any(feature = "reqwest-client", feature = "ureq-client")))]
+compile_error!("HTTPDataSource needs either the reqwest-client or the ureq-client feature");
+
+#[derive(Debug, Copy, Clone)]
+pub struct FetchConfig {
+    // Number of times a failed send is retried before giving up. Timeouts
+    // aren't retried.
+    pub retries: u32,
+    // Delay before the first retry, doubled after every attempt
+    pub base_delay: Duration,
+    // Applied to each individual attempt
+    pub timeout: Duration,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            base_delay: Duration::from_millis(100),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+// A GET, as sent by HTTPDataSource. Most paths take their arguments as a
+// JSON body.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub url: String,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Option<Vec<u8>>,
+}
+
+impl Request {
+    pub fn get(url: String) -> Self {
+        Self {
+            url,
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    pub fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    pub fn body(mut self, body: Vec<u8>) -> Self {
+        self.body = Some(body);
+        self
+    }
+}
+
+// Whatever the server answered, including error statuses. The body has
+// already been decompressed.
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    // E.g., "Not Found", if known
+    pub status_text: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn is_error(&self) -> bool {
+        self.status >= 400
+    }
+
+    pub fn is_server_error(&self) -> bool {
+        self.status >= 500
+    }
+
+    // Header names are case-insensitive, and a header may be repeated
+    pub fn header_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // E.g., "404 Not Found"
+    pub fn status_line(&self) -> String {
+        format!("{} {}", self.status, self.status_text)
+            .trim_end()
+            .to_owned()
+    }
+}
+
+// Why a request got no response at all
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendError {
+    // Retrying would likely just wait as long again
+    TimedOut(String),
+    Failed(String),
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::TimedOut(message) | SendError::Failed(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+/// Sends requests for HTTPDataSource, so that the HTTP client it uses can be
+/// swapped out. ReqwestTransport is the default; the ureq-client feature
+/// switches to UreqTransport, which doesn't pull in an async runtime.
+///
+/// If both the reqwest-client and ureq-client features are enabled (e.g.,
+/// by --all-features, or by two crates depending on this one), ureq is used
+/// and reqwest is only compiled. Disable default features to drop reqwest.
+pub trait Transport {
+    // A single attempt. Only failing to get a response at all (e.g., a
+    // timeout) is an error; error statuses are returned as responses.
+    fn send(&self, request: &Request, timeout: Duration) -> Result<Response, SendError>;
+}
+
+// ureq wins if both features are enabled, since it has to be asked for.
+// This isn't a compile_error! because features are additive: enabling ureq
+// in one crate mustn't break the build of another that uses the default.
+#[cfg(feature = "ureq-client")]
+pub type DefaultTransport = UreqTransport;
+#[cfg(all(feature = "reqwest-client", not(feature = "ureq-client")))]
+pub type DefaultTransport = ReqwestTransport;
+
+#[cfg(feature = "reqwest-client")]
+pub struct ReqwestTransport {
+    pub client: reqwest::blocking::Client,
+}
+
+#[cfg(feature = "reqwest-client")]
+impl Default for ReqwestTransport {
+    fn default() -> Self {
+        Self {
+            // Note: reqwest sets Accept-Encoding for the enabled codecs and
+            // transparently decodes the response. Setting the header by hand
+            // would turn the decoding off.
+            client: reqwest::blocking::ClientBuilder::new()
+                .gzip(true)
+                .deflate(true)
+                .brotli(true)
+                .build()
+                .unwrap(),
+        }
+    }
+}
+
+#[cfg(feature = "reqwest-client")]
+impl Transport for ReqwestTransport {
+    fn send(&self, request: &Request, timeout: Duration) -> Result<Response, SendError> {
+        let mut builder = self.client.get(&request.url).timeout(timeout);
+        for (name, value) in &request.headers {
+            builder = builder.header(*name, value);
+        }
+        if let Some(body) = &request.body {
+            builder = builder.body(body.clone());
+        }
+        let describe = |e: reqwest::Error| {
+            if e.is_timeout() {
+                SendError::TimedOut(format!(
+                    "request timed out after {}s",
+                    timeout.as_secs_f64()
+                ))
+            } else {
+                SendError::Failed(e.to_string())
+            }
+        };
+        let response = builder.send().map_err(describe)?;
+        let status = response.status();
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_owned())))
+            .collect();
+        let body = response.bytes().map_err(describe)?.to_vec();
+        Ok(Response {
+            status: status.as_u16(),
+            status_text: status.canonical_reason().unwrap_or_default().to_owned(),
+            headers,
+            body,
+        })
+    }
+}
+
+#[cfg(feature = "ureq-client")]
+pub struct UreqTransport {
+    pub agent: ureq::Agent,
+}
+
+#[cfg(feature = "ureq-client")]
+impl Default for UreqTransport {
+    fn default() -> Self {
+        // Note: with the gzip and brotli features, ureq sets Accept-Encoding
+        // and decodes the response itself
+        Self {
+            agent: ureq::AgentBuilder::new().build(),
+        }
+    }
+}
+
+#[cfg(feature = "ureq-client")]
+impl Transport for UreqTransport {
+    fn send(&self, request: &Request, timeout: Duration) -> Result<Response, SendError> {
+        use std::io::{ErrorKind, Read};
+
+        let mut builder = self.agent.get(&request.url).timeout(timeout);
+        for (name, value) in &request.headers {
+            builder = builder.set(name, value);
+        }
+        let result = match &request.body {
+            Some(body) => builder.send_bytes(body),
+            None => builder.call(),
+        };
+        // ureq reports error statuses as errors, but they're still responses
+        let response = match result {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(ureq::Error::Transport(e)) => {
+                // ureq surfaces timeouts as I/O errors
+                let timed_out = std::error::Error::source(&e)
+                    .and_then(|source| source.downcast_ref::<std::io::Error>())
+                    .map_or(false, |e| {
+                        matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock)
+                    });
+                return Err(if timed_out {
+                    SendError::TimedOut(format!(
+                        "request timed out after {}s",
+                        timeout.as_secs_f64()
+                    ))
+                } else {
+                    SendError::Failed(e.to_string())
+                });
+            }
+        };
+        let status = response.status();
+        let status_text = response.status_text().to_owned();
+        let mut headers = Vec::new();
+        for name in response.headers_names() {
+            for value in response.all(&name) {
+                headers.push((name.clone(), value.to_owned()));
+            }
+        }
+        let mut body = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut body)
+            .map_err(|e| match e.kind() {
+                ErrorKind::TimedOut | ErrorKind::WouldBlock => SendError::TimedOut(format!(
+                    "request timed out after {}s",
+                    timeout.as_secs_f64()
+                )),
+                _ => SendError::Failed(e.to_string()),
+            })?;
+        Ok(Response {
+            status,
+            status_text,
+            headers,
+            body,
+        })
+    }
+}
+
+// Send the request, retrying with exponential backoff (e.g. 100ms, 200ms,
+// 400ms) if it fails to send. Timeouts are not retried, so a server that
+// doesn't answer costs a single timeout rather than several. Only the final
+// error is returned.
+pub fn send(
+    config: &FetchConfig,
+    transport: &dyn Transport,
+    request: &Request,
+) -> Result<Response, String> {
+    let mut delay = config.base_delay;
+    let mut attempt = 0;
+    loop {
+        match transport.send(request, config.timeout) {
+            Ok(response) => return Ok(response),
+            Err(e @ SendError::TimedOut(_)) => return Err(e.to_string()),
+            Err(e) if attempt >= config.retries => return Err(e.to_string()),
+            Err(_) => {
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{DataSource, SlotTile, TileID};
+    use crate::mock::tests::{interval, sample, slot_id};
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::cell::Cell;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    // Fails every request with error, counting the attempts
+    struct Failing {
+        error: SendError,
+        attempts: Cell<u32>,
+    }
+
+    impl Transport for Failing {
+        fn send(&self, _: &Request, _: Duration) -> Result<Response, SendError> {
+            self.attempts.set(self.attempts.get() + 1);
+            Err(self.error.clone())
+        }
+    }
+
+    fn attempts(error: SendError) -> u32 {
+        let config = FetchConfig {
+            retries: 3,
+            base_delay: Duration::from_millis(1),
+            timeout: Duration::from_millis(1),
+        };
+        let transport = Failing {
+            error: error.clone(),
+            attempts: Cell::new(0),
+        };
+        let result = send(
+            &config,
+            &transport,
+            &Request::get("http://localhost/".to_owned()),
+        );
+        assert_eq!(result.unwrap_err(), error.to_string());
+        transport.attempts.get()
+    }
+
+    #[test]
+    fn failures_are_retried() {
+        assert_eq!(attempts(SendError::Failed("refused".to_owned())), 4);
+    }
+
+    #[test]
+    fn timeouts_are_not_retried() {
+        assert_eq!(attempts(SendError::TimedOut("timed out".to_owned())), 1);
+    }
+
+    // Fails the first failures requests, then answers with body
+    struct Flaky {
+        failures: u32,
+        attempts: Cell<u32>,
+    }
+
+    impl Transport for Flaky {
+        fn send(&self, _: &Request, _: Duration) -> Result<Response, SendError> {
+            self.attempts.set(self.attempts.get() + 1);
+            if self.attempts.get() <= self.failures {
+                return Err(SendError::Failed("refused".to_owned()));
+            }
+            Ok(Response {
+                status: 200,
+                status_text: "OK".to_owned(),
+                headers: Vec::new(),
+                body: b"body".to_vec(),
+            })
+        }
+    }
+
+    #[test]
+    fn retries_stop_at_the_first_success() {
+        let config = FetchConfig {
+            base_delay: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let transport = Flaky {
+            failures: 2,
+            attempts: Cell::new(0),
+        };
+        let request = Request::get("http://localhost/".to_owned());
+        let response = send(&config, &transport, &request).unwrap();
+        assert_eq!(response.body, b"body");
+        assert_eq!(transport.attempts.get(), 3);
+    }
+
+    // Serves a single connection with response, or never responds if it is
+    // None. Returns the URL and a handle to the raw request received.
+    fn mock_server(response: Option<Vec<u8>>) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let response = match response {
+                Some(response) => response,
+                None => {
+                    // Hold the connection open until the client gives up
+                    let _ = stream.read_to_end(&mut Vec::new());
+                    return String::new();
+                }
+            };
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            loop {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).to_lowercase();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length:"))
+                        .map_or(0, |length| length.trim().parse().unwrap());
+                    if request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+            }
+            stream.write_all(&response).unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (url, handle)
+    }
+
+    fn check_response(transport: &dyn Transport) {
+        let (url, server) = mock_server(Some(
+            b"HTTP/1.1 404 Not Found\r\nX-Reply: a\r\nContent-Length: 5\r\n\r\nhello".to_vec(),
+        ));
+        let request = Request::get(format!("{url}/info"))
+            .header("X-Request", "1")
+            .body(b"{}".to_vec());
+        let response = transport.send(&request, Duration::from_secs(5)).unwrap();
+        assert_eq!(response.status_line(), "404 Not Found");
+        assert_eq!(response.header("x-reply"), Some("a"));
+        assert_eq!(response.body, b"hello");
+
+        let received = server.join().unwrap();
+        assert!(received.starts_with("GET /info "));
+        assert!(received.to_lowercase().contains("x-request: 1"));
+        assert!(received.ends_with("{}"));
+    }
+
+    fn check_timeout(transport: &dyn Transport) {
+        let (url, _) = mock_server(None);
+        let request = Request::get(format!("{url}/info"));
+        let result = transport.send(&request, Duration::from_millis(100));
+        assert!(matches!(result, Err(SendError::TimedOut(_))), "{result:?}");
+    }
+
+    // Compressed responses are decoded transparently
+    fn check_gzip(transport: &dyn Transport) {
+        let tile = sample().fetch_slot_tile(&slot_id(), TileID(interval()));
+        let json = serde_json::to_vec(&tile).unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&json).unwrap();
+        let body = encoder.finish().unwrap();
+        let mut response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        response.extend(body);
+        let (url, server) = mock_server(Some(response));
+
+        let request = Request::get(format!("{url}/slot_tile"));
+        let response = transport.send(&request, Duration::from_secs(5)).unwrap();
+        assert_eq!(response.body.len(), json.len());
+        let decoded: SlotTile = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(decoded.tile_id, tile.tile_id);
+        assert_eq!(decoded.items[0].len(), tile.items[0].len());
+
+        let received = server.join().unwrap().to_lowercase();
+        assert!(received.contains("accept-encoding: gzip"), "{received}");
+    }
+
+    #[cfg(feature = "reqwest-client")]
+    #[test]
+    fn reqwest_sends_requests() {
+        check_response(&ReqwestTransport::default());
+        check_timeout(&ReqwestTransport::default());
+        check_gzip(&ReqwestTransport::default());
+    }
+
+    #[cfg(feature = "ureq-client")]
+    #[test]
+    fn ureq_sends_requests() {
+        check_response(&UreqTransport::default());
+        check_timeout(&UreqTransport::default());
+        check_gzip(&UreqTransport::default());
+    }
+}
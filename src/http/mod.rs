@@ -1,2 +1,6 @@
 pub mod client;
+pub mod fetch;
+pub mod rate_limit;
+pub mod schema;
 pub mod server;
+pub mod websocket;
@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Once this many clients are tracked, clients whose buckets have refilled
+// completely are forgotten, since they would be allowed a full burst anyway.
+// That takes a pass over every client, so it's done at most once every
+// MAX_TRACKED_CLIENTS requests, keeping the cost per request constant.
+const MAX_TRACKED_CLIENTS: usize = 1024;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Default)]
+struct Buckets {
+    buckets: BTreeMap<IpAddr, Bucket>,
+    // Requests since clients were last forgotten
+    since_pruned: usize,
+}
+
+// Token bucket per client IP: each client may send up to burst requests at
+// once, and the bucket refills at requests_per_second
+pub struct RateLimiter {
+    requests_per_second: f64,
+    burst: u32,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        assert!(requests_per_second > 0.0);
+        assert!(burst > 0);
+        Self {
+            requests_per_second,
+            burst,
+            buckets: Mutex::new(Buckets::default()),
+        }
+    }
+
+    // Takes a token for ip. If none are left, returns how long until the
+    // next one is available.
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut state = self.buckets.lock().unwrap();
+        let Buckets {
+            buckets,
+            since_pruned,
+        } = &mut *state;
+        *since_pruned += 1;
+        if buckets.len() >= MAX_TRACKED_CLIENTS && *since_pruned >= MAX_TRACKED_CLIENTS {
+            buckets.retain(|_, bucket| self.refill(bucket, now) < self.burst as f64);
+            *since_pruned = 0;
+        }
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: self.burst as f64,
+            updated: now,
+        });
+        let tokens = self.refill(bucket, now);
+        if tokens >= 1.0 {
+            bucket.tokens = tokens - 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - tokens) / self.requests_per_second,
+            ))
+        }
+    }
+
+    fn refill(&self, bucket: &mut Bucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.requests_per_second).min(self.burst as f64);
+        bucket.updated = now;
+        bucket.tokens
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn idle_clients_are_forgotten_now_and_then() {
+        let limiter = RateLimiter::new(1.0, 1);
+        let ip = |i: usize| IpAddr::V4(Ipv4Addr::from(i as u32));
+        let now = Instant::now();
+        {
+            let mut state = limiter.buckets.lock().unwrap();
+            for i in 1..=MAX_TRACKED_CLIENTS {
+                let idle = Bucket {
+                    tokens: 1.0,
+                    updated: now,
+                };
+                state.buckets.insert(ip(i), idle);
+            }
+        }
+        let tracked = || limiter.buckets.lock().unwrap().buckets.len();
+
+        // Not on every request, even though there are too many clients
+        assert!(limiter.check(ip(0)).is_ok());
+        assert_eq!(tracked(), MAX_TRACKED_CLIENTS + 1);
+        for _ in 2..MAX_TRACKED_CLIENTS {
+            let _ = limiter.check(ip(0));
+        }
+        assert_eq!(tracked(), MAX_TRACKED_CLIENTS + 1);
+
+        // Only the client that just used its token is left
+        let _ = limiter.check(ip(0));
+        assert_eq!(tracked(), 1);
+    }
+}
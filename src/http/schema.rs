@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+use crate::data::{SlotMetaTile, SlotTile, SummaryTile};
+use crate::http::server::FetchRequest;
+
+// Messages of the /ws endpoint, each sent as a JSON text frame. Clients
+// subscribe to tiles, and the server pushes each tile back once it has been
+// fetched, tagged with the id the client picked. Many subscriptions can be in
+// flight on one connection, and replies may arrive in any order.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TileKind {
+    Summary,
+    Slot,
+    SlotMeta,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    // request is interpreted as by the GET endpoint for kind, e.g., samples
+    // only applies to summary tiles
+    Subscribe {
+        id: u64,
+        kind: TileKind,
+        request: FetchRequest,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    SummaryTile { id: u64, tile: SummaryTile },
+    SlotTile { id: u64, tile: SlotTile },
+    SlotMetaTile { id: u64, tile: SlotMetaTile },
+    // Why a subscription failed, with the message the GET endpoint would
+    // have sent. id is None if the client's message couldn't be read.
+    Error { id: Option<u64>, error: String },
+}
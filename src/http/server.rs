@@ -1,31 +1,186 @@
-use crate::data::{DataSource, EntryID, EntryInfo, TileID};
-use crate::timestamp::Interval;
+use crate::data::{
+    aggregate_summary, clip_slot_meta_tile, clip_slot_tile, resample_summary, AsyncDataSource,
+    BoxFuture, DataSource, Downsample, EntryID, EntryIndex, EntryInfo, ItemMeta, SlotMetaTile,
+    SlotTile, SummaryTile, TileID, TimeOrigin,
+};
+use crate::timestamp::{Interval, Timestamp};
 
+use actix_codec::{Decoder, Encoder};
+use actix_http::ws;
 use actix_web::{
+    dev::{Server, ServerHandle, Service},
+    error::{ErrorInternalServerError, ErrorUnauthorized, InternalError},
+    http::{
+        header::{self, HeaderMap, HeaderValue},
+        Method, StatusCode,
+    },
     middleware,
+    rt::task,
     web::{self, Data},
-    App, HttpResponse, HttpServer, Responder, Result,
+    App, HttpRequest, HttpResponse, HttpServer, Responder, Result,
 };
+use bytes::{Bytes, BytesMut};
+use fnv::FnvHasher;
+use futures_util::{stream, StreamExt};
 use serde::{Deserialize, Serialize};
 
-use std::sync::{Arc, Mutex};
+use super::rate_limit::RateLimiter;
+use super::schema::{ClientMessage, ServerMessage, TileKind};
+
+use std::collections::VecDeque;
+use std::hash::Hasher;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+// Clients that send this in their Accept header get bincode-encoded tiles.
+// Everyone else gets JSON.
+pub const BINARY_CONTENT_TYPE: &str = "application/octet-stream";
+
+// Maximum number of tiles that can be requested from /slot_tile_batch at once
+pub const MAX_BATCH_SIZE: usize = 64;
+
+// Maximum number of points summary tiles can be resampled to, which is more
+// than any display is wide
+pub const MAX_SUMMARY_SAMPLES: usize = 8192;
+
+type SharedDataSource = Box<dyn DataSource + Sync + Send + 'static>;
 
 // dyn DataSource + Sync + Send + 'static> from
 // https://stackoverflow.com/questions/65645622/how-do-i-pass-a-trait-as-application-data-to-actix-web
 // to enable passing a datasource between threads
+//
+// DataSource methods take &mut self (sources fill their caches lazily), so
+// each data source can only serve one request at a time. Requests are spread
+// over independent replicas of the same profile so that they can be served
+// in parallel.
 pub struct AppState {
-    pub data_source: Mutex<Box<dyn DataSource + Sync + Send + 'static>>,
+    pub data_sources: Vec<Mutex<SharedDataSource>>,
+    next: AtomicUsize,
+}
+
+impl AppState {
+    // Prefers an idle replica, and otherwise waits on the next one in turn
+    pub fn lock(&self) -> MutexGuard<'_, SharedDataSource> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let count = self.data_sources.len();
+        for i in 0..count {
+            if let Ok(source) = self.data_sources[(start + i) % count].try_lock() {
+                return source;
+            }
+        }
+        self.data_sources[start % count].lock().unwrap()
+    }
+}
+
+type SharedAsyncDataSource = dyn AsyncDataSource + Sync + Send + 'static;
+
+// Serves synchronous data sources without blocking the server's workers, by
+// running each call on the blocking thread pool
+pub struct BlockingDataSource(pub Arc<AppState>);
+
+impl BlockingDataSource {
+    fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut dyn DataSource) -> T + Send + 'static,
+    ) -> BoxFuture<'static, T> {
+        let state = self.0.clone();
+        Box::pin(async move {
+            task::spawn_blocking(move || f(&mut **state.lock()))
+                .await
+                .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+        })
+    }
+}
+
+impl AsyncDataSource for BlockingDataSource {
+    fn interval(&self) -> BoxFuture<'_, Interval> {
+        self.run(|source| source.interval())
+    }
+    fn fetch_info(&self) -> BoxFuture<'_, EntryInfo> {
+        self.run(|source| source.fetch_info())
+    }
+    fn request_tiles<'a>(
+        &'a self,
+        entry_id: &'a EntryID,
+        request_interval: Interval,
+    ) -> BoxFuture<'a, Vec<TileID>> {
+        let entry_id = entry_id.clone();
+        self.run(move |source| source.request_tiles(&entry_id, request_interval))
+    }
+    fn fetch_summary_tile<'a>(
+        &'a self,
+        entry_id: &'a EntryID,
+        tile_id: TileID,
+    ) -> BoxFuture<'a, SummaryTile> {
+        let entry_id = entry_id.clone();
+        self.run(move |source| source.fetch_summary_tile(&entry_id, tile_id))
+    }
+    fn fetch_slot_tile<'a>(
+        &'a self,
+        entry_id: &'a EntryID,
+        tile_id: TileID,
+    ) -> BoxFuture<'a, SlotTile> {
+        let entry_id = entry_id.clone();
+        self.run(move |source| source.fetch_slot_tile(&entry_id, tile_id))
+    }
+    fn fetch_slot_meta_tile<'a>(
+        &'a self,
+        entry_id: &'a EntryID,
+        tile_id: TileID,
+    ) -> BoxFuture<'a, SlotMetaTile> {
+        let entry_id = entry_id.clone();
+        self.run(move |source| source.fetch_slot_meta_tile(&entry_id, tile_id))
+    }
+    fn probe<'a>(
+        &'a self,
+        entry_id: &'a EntryID,
+        at: Timestamp,
+    ) -> BoxFuture<'a, Option<ItemMeta>> {
+        let entry_id = entry_id.clone();
+        self.run(move |source| source.probe(&entry_id, at))
+    }
+    fn time_origin(&self) -> BoxFuture<'_, TimeOrigin> {
+        self.run(|source| source.time_origin())
+    }
+    fn tile_byte_estimate<'a>(
+        &'a self,
+        entry_id: &'a EntryID,
+        tile_id: TileID,
+    ) -> BoxFuture<'a, Option<usize>> {
+        let entry_id = entry_id.clone();
+        self.run(move |source| source.tile_byte_estimate(&entry_id, tile_id))
+    }
 }
 
 pub struct DataSourceHTTPServer {
     pub port: u16,
     pub host: String,
     pub state: AppState,
+    // Served instead of state when set
+    pub async_source: Option<Arc<SharedAsyncDataSource>>,
+    // When set, every request must carry "Authorization: Bearer <token>"
+    pub token: Option<String>,
+    // Used only when RUST_LOG is not set in the environment
+    pub log_level: String,
+    // When set, clients that exceed their rate get a 429
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    // Origins (e.g., "https://example.com", or "*" for any) that browsers
+    // may fetch from. When empty, no CORS headers are sent, so only
+    // same-origin pages can use the server.
+    pub allowed_origins: Vec<String>,
 }
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FetchRequest {
     pub entry_id: EntryID,
     pub tile_id: TileID,
+    // Summary tiles only: resample the utilization to this many points
+    #[serde(default)]
+    pub samples: Option<usize>,
+    // Slot and slot meta tiles only: return only the items overlapping this
+    // interval, e.g., the part of the tile that is visible
+    #[serde(default)]
+    pub clip: Option<Interval>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -34,6 +189,333 @@ pub struct FetchTilesRequest {
     pub interval: Interval,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProbeRequest {
+    pub entry_id: EntryID,
+    pub at: Timestamp,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HealthResponse {
+    pub status: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionResponse {
+    pub version: String,
+    pub git_hash: String,
+}
+
+// Body of 4xx responses from the data handlers
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+fn json_error(status: StatusCode, message: String) -> actix_web::Error {
+    let response = HttpResponse::build(status).json(ErrorResponse {
+        error: message.clone(),
+    });
+    InternalError::from_response(message, response).into()
+}
+
+// Like EntryInfo::get, but None (rather than a panic) when entry_id doesn't
+// match the shape of the hierarchy
+fn find_entry<'a>(info: &'a EntryInfo, entry_id: &EntryID) -> Option<&'a EntryInfo> {
+    let mut result = info;
+    for level in 0..entry_id.level() {
+        result = match (entry_id.index(level)?, result) {
+            (EntryIndex::Summary, EntryInfo::Panel { summary, .. }) => summary.as_deref()?,
+            (EntryIndex::Slot(i), EntryInfo::Panel { slots, .. }) => slots.get(i as usize)?,
+            _ => return None,
+        };
+    }
+    Some(result)
+}
+
+// Entries that aren't found make the server fetch the hierarchy again (in
+// case the profile has grown since), but at most this often, so that requests
+// for bogus entries can't keep it busy fetching
+const INFO_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+// Number of slot tiles served clipped that are kept for clipping their metas
+const MAX_RECENT_SLOT_TILES: usize = 64;
+
+// The entry hierarchy, for checking the EntryIDs of requests
+#[derive(Default)]
+struct EntryInfoCache(Mutex<CachedInfo>);
+
+#[derive(Default)]
+struct CachedInfo {
+    info: Option<Arc<EntryInfo>>,
+    // When info was last (re)fetched
+    fetched: Option<Instant>,
+}
+
+impl EntryInfoCache {
+    // 404s unless entry_id exists and is_kind holds for it, e.g., so that
+    // asking for the summary tile of a slot isn't answered with an empty tile
+    async fn check(
+        &self,
+        data: &SharedAsyncDataSource,
+        entry_id: &EntryID,
+        kind: &str,
+        is_kind: fn(&EntryInfo) -> bool,
+    ) -> Result<()> {
+        let found = |info: &EntryInfo| find_entry(info, entry_id).map_or(false, is_kind);
+        let refresh = {
+            let mut cached = self.0.lock().unwrap();
+            if cached.info.as_ref().map_or(false, |info| found(info)) {
+                return Ok(());
+            }
+            // Until the info has been fetched once, there's nothing to check
+            // against, so every request fetches it rather than 404 while
+            // another does
+            let refresh = cached.info.is_none()
+                || cached
+                    .fetched
+                    .map_or(true, |fetched| fetched.elapsed() >= INFO_REFRESH_INTERVAL);
+            if refresh {
+                // Set before fetching, so that concurrent misses don't all
+                // fetch too
+                cached.fetched = Some(Instant::now());
+            }
+            refresh
+        };
+        if refresh {
+            let info = Arc::new(data.fetch_info().await);
+            self.0.lock().unwrap().info = Some(info.clone());
+            if found(&info) {
+                return Ok(());
+            }
+        }
+        Err(json_error(
+            StatusCode::NOT_FOUND,
+            format!("no {kind} with entry id {entry_id}"),
+        ))
+    }
+}
+
+// Slot tiles recently served clipped, newest last. Clipping metas depends on
+// the intervals of the items, and clients that clip tiles ask for the metas
+// of the same tiles soon after, so this saves fetching them again.
+#[derive(Default)]
+struct RecentSlotTiles(Mutex<VecDeque<(EntryID, SlotTile)>>);
+
+impl RecentSlotTiles {
+    fn insert(&self, entry_id: &EntryID, tile: &SlotTile) {
+        let mut tiles = self.0.lock().unwrap();
+        tiles.retain(|(id, t)| !(id == entry_id && t.tile_id == tile.tile_id));
+        tiles.push_back((entry_id.clone(), tile.clone()));
+        if tiles.len() > MAX_RECENT_SLOT_TILES {
+            tiles.pop_front();
+        }
+    }
+
+    fn get(&self, entry_id: &EntryID, tile_id: TileID) -> Option<SlotTile> {
+        let tiles = self.0.lock().unwrap();
+        tiles
+            .iter()
+            .find(|(id, tile)| id == entry_id && tile.tile_id == tile_id)
+            .map(|(_, tile)| tile.clone())
+    }
+}
+
+fn is_any(_: &EntryInfo) -> bool {
+    true
+}
+
+fn is_slot(info: &EntryInfo) -> bool {
+    matches!(info, EntryInfo::Slot { .. })
+}
+
+fn is_summary(info: &EntryInfo) -> bool {
+    matches!(info, EntryInfo::Summary { .. })
+}
+
+// 400s unless tile_id lies within the profile, so that arbitrary intervals
+// never reach the fetch methods. Sources are free to split the profile into
+// tiles however they like (and differently for each request), so this can't
+// check that tile_id is one the source handed out.
+async fn check_tile(
+    data: &SharedAsyncDataSource,
+    entry_id: &EntryID,
+    tile_id: TileID,
+) -> Result<()> {
+    let bounds = data.interval().await;
+    let Interval { start, stop } = tile_id.0;
+    if start <= stop && bounds.start <= start && stop <= bounds.stop {
+        Ok(())
+    } else {
+        Err(json_error(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "no tile from {} to {} ns for entry id {entry_id}",
+                start.0, stop.0
+            ),
+        ))
+    }
+}
+
+async fn serve_summary_tile(
+    request: &FetchRequest,
+    data: &SharedAsyncDataSource,
+    entries: &EntryInfoCache,
+) -> Result<SummaryTile> {
+    let entry_id = &request.entry_id;
+    entries.check(data, entry_id, "summary", is_summary).await?;
+    let tile_id = request.tile_id;
+    check_tile(data, entry_id, tile_id).await?;
+    if let Some(samples) = request.samples {
+        if samples == 0 || samples > MAX_SUMMARY_SAMPLES {
+            return Err(json_error(
+                StatusCode::BAD_REQUEST,
+                format!("samples must be between 1 and {MAX_SUMMARY_SAMPLES}, not {samples}"),
+            ));
+        }
+    }
+    let mut tile = data.fetch_summary_tile(entry_id, tile_id).await;
+    if let Some(samples) = request.samples {
+        // Too many points are averaged over each sample rather than picked
+        // from, so that nothing between the samples is lost
+        tile = if tile.utilization.len() > samples {
+            aggregate_summary(&[tile], samples, Downsample::Mean)
+        } else {
+            resample_summary(&tile, samples)
+        };
+    }
+    Ok(tile)
+}
+
+async fn serve_slot_tile(
+    request: &FetchRequest,
+    data: &SharedAsyncDataSource,
+    entries: &EntryInfoCache,
+    recent: &RecentSlotTiles,
+) -> Result<SlotTile> {
+    let entry_id = &request.entry_id;
+    entries.check(data, entry_id, "slot", is_slot).await?;
+    let tile_id = request.tile_id;
+    check_tile(data, entry_id, tile_id).await?;
+    let mut tile = data.fetch_slot_tile(entry_id, tile_id).await;
+    if let Some(clip) = request.clip {
+        recent.insert(entry_id, &tile);
+        tile = clip_slot_tile(&tile, clip);
+    }
+    Ok(tile)
+}
+
+async fn serve_slot_meta_tile(
+    request: &FetchRequest,
+    data: &SharedAsyncDataSource,
+    entries: &EntryInfoCache,
+    recent: &RecentSlotTiles,
+) -> Result<SlotMetaTile> {
+    let entry_id = &request.entry_id;
+    entries.check(data, entry_id, "slot", is_slot).await?;
+    let tile_id = request.tile_id;
+    check_tile(data, entry_id, tile_id).await?;
+    let mut meta_tile = data.fetch_slot_meta_tile(entry_id, tile_id).await;
+    if let Some(clip) = request.clip {
+        // Which metas to keep depends on the items' intervals
+        let tile = match recent.get(entry_id, tile_id) {
+            Some(tile) => tile,
+            None => data.fetch_slot_tile(entry_id, tile_id).await,
+        };
+        meta_tile = clip_slot_meta_tile(&tile, &meta_tile, clip);
+    }
+    Ok(meta_tile)
+}
+
+// The server's end of a /ws connection. Messages are handled one at a time,
+// in the order they arrive.
+struct WebSocketConnection {
+    payload: web::Payload,
+    // Received but not yet decoded
+    buf: BytesMut,
+    codec: ws::Codec,
+    data: web::Data<SharedAsyncDataSource>,
+    entries: web::Data<EntryInfoCache>,
+    recent: web::Data<RecentSlotTiles>,
+    // Set once the close handshake has been answered
+    closed: bool,
+}
+
+impl WebSocketConnection {
+    // The next frame to send, encoded, or None once the connection is done
+    async fn next_reply(&mut self) -> Option<Result<Bytes>> {
+        while !self.closed {
+            let frame = match self.codec.decode(&mut self.buf) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => match self.payload.next().await? {
+                    Ok(chunk) => {
+                        self.buf.extend_from_slice(&chunk);
+                        continue;
+                    }
+                    Err(e) => return Some(Err(e.into())),
+                },
+                Err(e) => return Some(Err(e.into())),
+            };
+            let message = match frame {
+                ws::Frame::Text(text) => self.reply(&text).await,
+                ws::Frame::Ping(bytes) => ws::Message::Pong(bytes),
+                ws::Frame::Close(reason) => {
+                    self.closed = true;
+                    ws::Message::Close(reason)
+                }
+                ws::Frame::Pong(_) => continue,
+                ws::Frame::Binary(_) | ws::Frame::Continuation(_) => {
+                    Self::text_message(&ServerMessage::Error {
+                        id: None,
+                        error: "expected a single JSON text frame".to_owned(),
+                    })
+                }
+            };
+            let mut out = BytesMut::new();
+            return Some(
+                self.codec
+                    .encode(message, &mut out)
+                    .map(|()| out.freeze())
+                    .map_err(Into::into),
+            );
+        }
+        None
+    }
+
+    async fn reply(&self, text: &[u8]) -> ws::Message {
+        let (id, kind, request) = match serde_json::from_slice(text) {
+            Ok(ClientMessage::Subscribe { id, kind, request }) => (id, kind, request),
+            Err(e) => {
+                return Self::text_message(&ServerMessage::Error {
+                    id: None,
+                    error: e.to_string(),
+                })
+            }
+        };
+        let (data, entries, recent) = (self.data.get_ref(), &self.entries, &self.recent);
+        let reply = match kind {
+            TileKind::Summary => serve_summary_tile(&request, data, entries)
+                .await
+                .map(|tile| ServerMessage::SummaryTile { id, tile }),
+            TileKind::Slot => serve_slot_tile(&request, data, entries, recent)
+                .await
+                .map(|tile| ServerMessage::SlotTile { id, tile }),
+            TileKind::SlotMeta => serve_slot_meta_tile(&request, data, entries, recent)
+                .await
+                .map(|tile| ServerMessage::SlotMetaTile { id, tile }),
+        };
+        Self::text_message(&reply.unwrap_or_else(|e| ServerMessage::Error {
+            id: Some(id),
+            error: e.to_string(),
+        }))
+    }
+
+    fn text_message(message: &ServerMessage) -> ws::Message {
+        let json = serde_json::to_string(message).expect("unable to serialize message");
+        ws::Message::Text(json.into())
+    }
+}
+
 impl DataSourceHTTPServer {
     pub fn new(
         port: u16,
@@ -44,14 +526,138 @@ impl DataSourceHTTPServer {
             port,
             host,
             state: AppState {
-                data_source: Mutex::new(state),
+                data_sources: vec![Mutex::new(state)],
+                next: AtomicUsize::new(0),
+            },
+            async_source: None,
+            token: None,
+            log_level: "info".to_owned(),
+            rate_limiter: None,
+            allowed_origins: Vec::new(),
+        }
+    }
+
+    // For sources that can serve requests without blocking, e.g., because
+    // they forward them over the network
+    pub fn new_async(port: u16, host: String, source: Arc<SharedAsyncDataSource>) -> Self {
+        Self {
+            port,
+            host,
+            state: AppState {
+                data_sources: Vec::new(),
+                next: AtomicUsize::new(0),
             },
+            async_source: Some(source),
+            token: None,
+            log_level: "info".to_owned(),
+            rate_limiter: None,
+            allowed_origins: Vec::new(),
+        }
+    }
+
+    // replica must be a separate instance of the same profile as the data
+    // source passed to new, e.g., the same file opened again
+    pub fn with_replica(mut self, replica: SharedDataSource) -> Self {
+        self.state.data_sources.push(Mutex::new(replica));
+        self
+    }
+
+    pub fn with_token(mut self, token: String) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    pub fn with_log_level(mut self, log_level: String) -> Self {
+        self.log_level = log_level;
+        self
+    }
+
+    // Each client IP may send up to burst requests at once, after which it is
+    // limited to requests_per_second
+    pub fn with_rate_limit(mut self, requests_per_second: f64, burst: u32) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(requests_per_second, burst)));
+        self
+    }
+
+    pub fn with_allowed_origins(mut self, allowed_origins: Vec<String>) -> Self {
+        self.allowed_origins = allowed_origins;
+        self
+    }
+
+    // Tiles of a finished profile never change, so each response carries an
+    // ETag and clients that already have the tile get a 304
+    fn encode_tile<T: Serialize>(req: &HttpRequest, tile: &T) -> Result<HttpResponse> {
+        let accepts_binary = req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .map_or(false, |value| value.contains(BINARY_CONTENT_TYPE));
+        let (content_type, body) = if accepts_binary {
+            let body = bincode::serialize(tile).map_err(ErrorInternalServerError)?;
+            (BINARY_CONTENT_TYPE, body)
+        } else {
+            let body = serde_json::to_vec(tile).map_err(ErrorInternalServerError)?;
+            ("application/json", body)
+        };
+
+        // Note: FNV rather than the std hasher, which is not guaranteed to
+        // be stable across builds
+        let mut hasher = FnvHasher::default();
+        hasher.write(content_type.as_bytes());
+        hasher.write(&body);
+        let etag = format!("\"{:016x}\"", hasher.finish());
+
+        let not_modified = req
+            .headers()
+            .get(header::IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .map_or(false, |value| {
+                value
+                    .split(',')
+                    .any(|tag| tag.trim() == etag || tag.trim() == "*")
+            });
+        if not_modified {
+            return Ok(HttpResponse::NotModified()
+                .insert_header((header::ETAG, etag))
+                .finish());
         }
+        Ok(HttpResponse::Ok()
+            .content_type(content_type)
+            .insert_header((header::ETAG, etag))
+            .body(body))
     }
-    async fn get_entry_name(data: web::Data<AppState>) -> impl Responder {
-        let mutex = &data.data_source;
-        let mut source = mutex.lock().unwrap();
-        let e = match source.fetch_info() {
+
+    fn add_cors_headers(headers: &mut HeaderMap, origin: Option<HeaderValue>, vary: bool) {
+        if let Some(origin) = origin {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+            headers.insert(
+                header::ACCESS_CONTROL_EXPOSE_HEADERS,
+                HeaderValue::from_static("etag"),
+            );
+        }
+        // Caches must not serve a response allowed for one origin to another
+        if vary {
+            headers.append(header::VARY, HeaderValue::from_static("origin"));
+        }
+    }
+
+    // Note: must not lock the data source, so that a slow fetch can't make
+    // the health check hang
+    async fn health() -> impl Responder {
+        web::Json(HealthResponse {
+            status: "ok".to_owned(),
+        })
+    }
+
+    async fn version() -> impl Responder {
+        web::Json(VersionResponse {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            git_hash: option_env!("GIT_HASH").unwrap_or("unknown").to_owned(),
+        })
+    }
+
+    async fn get_entry_name(data: web::Data<SharedAsyncDataSource>) -> impl Responder {
+        let e = match data.fetch_info().await {
             EntryInfo::Panel { short_name, .. } => short_name.clone(),
             _ => "hello".to_string(),
         };
@@ -59,92 +665,1129 @@ impl DataSourceHTTPServer {
         HttpResponse::Ok().body(e)
     }
 
-    async fn fetch_info(data: web::Data<AppState>) -> Result<impl Responder> {
-        let mutex = &data.data_source;
-        let mut source = mutex.lock().unwrap();
-        let to_ret = source.fetch_info().clone();
+    async fn fetch_info(data: web::Data<SharedAsyncDataSource>) -> Result<impl Responder> {
+        let to_ret = data.fetch_info().await;
+        Ok(web::Json(to_ret))
+    }
+
+    async fn interval(data: web::Data<SharedAsyncDataSource>) -> Result<impl Responder> {
+        let to_ret = data.interval().await;
         Ok(web::Json(to_ret))
     }
 
-    async fn interval(data: web::Data<AppState>) -> Result<impl Responder> {
-        let mutex = &data.data_source;
-        let mut source = mutex.lock().unwrap();
-        let to_ret = source.interval();
+    async fn time_origin(data: web::Data<SharedAsyncDataSource>) -> Result<impl Responder> {
+        let to_ret = data.time_origin().await;
         Ok(web::Json(to_ret))
     }
 
     async fn fetch_tiles(
         info: web::Json<FetchTilesRequest>,
-        data: web::Data<AppState>,
+        data: web::Data<SharedAsyncDataSource>,
+        entries: web::Data<EntryInfoCache>,
     ) -> Result<impl Responder> {
-        let mutex = &data.data_source;
-        let mut source = mutex.lock().unwrap();
-
         let entry_id = &info.entry_id;
+        entries
+            .check(data.get_ref(), entry_id, "entry", is_any)
+            .await?;
         let request_interval = info.interval;
-        let to_ret = source.request_tiles(entry_id, request_interval);
+        let to_ret = data.request_tiles(entry_id, request_interval).await;
         Ok(web::Json(to_ret))
     }
 
     async fn fetch_slot_meta_tile(
+        req: HttpRequest,
         info: web::Json<FetchRequest>,
-        data: web::Data<AppState>,
+        data: web::Data<SharedAsyncDataSource>,
+        entries: web::Data<EntryInfoCache>,
+        recent: web::Data<RecentSlotTiles>,
     ) -> Result<impl Responder> {
-        let mutex = &data.data_source;
-        let mut source = mutex.lock().unwrap();
-
-        let entry_id = &info.entry_id;
-        let tile_id = info.tile_id;
-        let to_ret = source.fetch_slot_meta_tile(entry_id, tile_id);
-        Ok(web::Json(to_ret))
+        let to_ret = serve_slot_meta_tile(&info, data.get_ref(), &entries, &recent).await?;
+        Self::encode_tile(&req, &to_ret)
     }
 
     async fn fetch_slot_tile(
+        req: HttpRequest,
         info: web::Json<FetchRequest>,
-        data: web::Data<AppState>,
+        data: web::Data<SharedAsyncDataSource>,
+        entries: web::Data<EntryInfoCache>,
+        recent: web::Data<RecentSlotTiles>,
     ) -> Result<impl Responder> {
-        let mutex = &data.data_source;
-        let mut source = mutex.lock().unwrap();
+        let to_ret = serve_slot_tile(&info, data.get_ref(), &entries, &recent).await?;
+        Self::encode_tile(&req, &to_ret)
+    }
 
-        let entry_id = &info.entry_id;
-        let tile_id = info.tile_id;
-        let to_ret = source.fetch_slot_tile(entry_id, tile_id);
-        Ok(web::Json(to_ret))
+    // Tiles are returned in the same order as the requests
+    async fn fetch_slot_tile_batch(
+        req: HttpRequest,
+        info: web::Json<Vec<FetchRequest>>,
+        data: web::Data<SharedAsyncDataSource>,
+        entries: web::Data<EntryInfoCache>,
+        recent: web::Data<RecentSlotTiles>,
+    ) -> Result<impl Responder> {
+        if info.len() > MAX_BATCH_SIZE {
+            return Err(json_error(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "batch of {} tiles exceeds the maximum of {}",
+                    info.len(),
+                    MAX_BATCH_SIZE
+                ),
+            ));
+        }
+
+        let mut to_ret = Vec::with_capacity(info.len());
+        for request in info.iter() {
+            entries
+                .check(data.get_ref(), &request.entry_id, "slot", is_slot)
+                .await?;
+            check_tile(data.get_ref(), &request.entry_id, request.tile_id).await?;
+            let mut tile = data
+                .fetch_slot_tile(&request.entry_id, request.tile_id)
+                .await;
+            if let Some(clip) = request.clip {
+                recent.insert(&request.entry_id, &tile);
+                tile = clip_slot_tile(&tile, clip);
+            }
+            to_ret.push(tile);
+        }
+        Self::encode_tile(&req, &to_ret)
     }
 
     async fn fetch_summary_tile(
+        req: HttpRequest,
         info: web::Json<FetchRequest>,
-        data: web::Data<AppState>,
+        data: web::Data<SharedAsyncDataSource>,
+        entries: web::Data<EntryInfoCache>,
     ) -> Result<impl Responder> {
-        let mutex = &data.data_source;
-        let mut source = mutex.lock().unwrap();
+        let to_ret = serve_summary_tile(&info, data.get_ref(), &entries).await?;
+        Self::encode_tile(&req, &to_ret)
+    }
 
+    // Tiles over a WebSocket (see schema), for clients that would rather
+    // keep one connection open than send a GET per tile
+    async fn websocket(
+        req: HttpRequest,
+        payload: web::Payload,
+        data: web::Data<SharedAsyncDataSource>,
+        entries: web::Data<EntryInfoCache>,
+        recent: web::Data<RecentSlotTiles>,
+    ) -> Result<HttpResponse> {
+        ws::verify_handshake(req.head())?;
+        let key = req.headers().get(header::SEC_WEBSOCKET_KEY).unwrap();
+        let accept = ws::hash_key(key.as_bytes());
+        let connection = WebSocketConnection {
+            payload,
+            buf: BytesMut::new(),
+            codec: ws::Codec::new(),
+            data,
+            entries,
+            recent,
+            closed: false,
+        };
+        // Replies are only produced as the client sends messages, and the
+        // body ends once the client closes the connection
+        let replies = stream::unfold(connection, |mut connection| async move {
+            let reply = connection.next_reply().await?;
+            Some((reply, connection))
+        });
+        Ok(HttpResponse::SwitchingProtocols()
+            .upgrade("websocket")
+            .insert_header((
+                header::SEC_WEBSOCKET_ACCEPT,
+                HeaderValue::from_bytes(&accept).unwrap(),
+            ))
+            .streaming(replies))
+    }
+
+    async fn tile_size(
+        info: web::Json<FetchRequest>,
+        data: web::Data<SharedAsyncDataSource>,
+        entries: web::Data<EntryInfoCache>,
+    ) -> Result<impl Responder> {
         let entry_id = &info.entry_id;
-        let tile_id = info.tile_id;
-        let to_ret = source.fetch_summary_tile(entry_id, tile_id);
+        entries
+            .check(data.get_ref(), entry_id, "entry", is_any)
+            .await?;
+        check_tile(data.get_ref(), entry_id, info.tile_id).await?;
+        let to_ret = data.tile_byte_estimate(entry_id, info.tile_id).await;
+        Ok(web::Json(to_ret))
+    }
+
+    async fn probe(
+        info: web::Json<ProbeRequest>,
+        data: web::Data<SharedAsyncDataSource>,
+        entries: web::Data<EntryInfoCache>,
+    ) -> Result<impl Responder> {
+        entries
+            .check(data.get_ref(), &info.entry_id, "slot", is_slot)
+            .await?;
+        let to_ret = data.probe(&info.entry_id, info.at).await;
         Ok(web::Json(to_ret))
     }
 
     #[actix_web::main]
     pub async fn create_server(self) -> std::io::Result<()> {
-        let state = Data::from(Arc::new(self.state));
-        std::env::set_var("RUST_LOG", "debug");
-        env_logger::init();
-        HttpServer::new(move || {
+        let (server, _handle) = self.create_server_with_handle()?;
+        server.await
+    }
+
+    // Like create_server, but the caller is responsible for running the
+    // returned Server (which must happen inside an actix runtime). The handle
+    // can be used to stop the server, e.g., handle.stop(true) drains
+    // in-flight requests before shutting down.
+    pub fn create_server_with_handle(self) -> std::io::Result<(Server, ServerHandle)> {
+        let source = match self.async_source {
+            Some(source) => source,
+            None => Arc::new(BlockingDataSource(Arc::new(self.state))),
+        };
+        let state = Data::from(source);
+        let entries = Data::new(EntryInfoCache::default());
+        let recent_slot_tiles = Data::new(RecentSlotTiles::default());
+        let expected_auth = self.token.map(|token| format!("Bearer {token}"));
+        let rate_limiter = self.rate_limiter;
+        let allowed_origins = Arc::new(self.allowed_origins);
+        // RUST_LOG (if set) takes precedence over log_level. The logger may
+        // also have been set up already by whoever is embedding the server.
+        let _ = env_logger::Builder::from_env(
+            env_logger::Env::default().default_filter_or(self.log_level.as_str()),
+        )
+        .try_init();
+        let server = HttpServer::new(move || {
             App::new()
+                // Registered first so it is the innermost middleware and
+                // rejected requests still get logged
+                .wrap_fn({
+                    let expected_auth = expected_auth.clone();
+                    move |req, srv| {
+                        // Load balancers probe /health without credentials
+                        let authorized = req.path() == "/health"
+                            || expected_auth.as_ref().map_or(true, |expected| {
+                                req.headers()
+                                    .get(header::AUTHORIZATION)
+                                    .and_then(|value| value.to_str().ok())
+                                    == Some(expected.as_str())
+                            });
+                        let response = if authorized {
+                            Some(srv.call(req))
+                        } else {
+                            None
+                        };
+                        async move {
+                            match response {
+                                Some(response) => response.await,
+                                None => Err(ErrorUnauthorized("invalid or missing bearer token")),
+                            }
+                        }
+                    }
+                })
+                // Outside of authentication, so that clients can't get around
+                // the limit by sending bad tokens
+                .wrap_fn({
+                    let rate_limiter = rate_limiter.clone();
+                    move |req, srv| {
+                        let retry_after = match (&rate_limiter, req.peer_addr()) {
+                            (Some(limiter), Some(addr)) if req.path() != "/health" => {
+                                limiter.check(addr.ip()).err()
+                            }
+                            _ => None,
+                        };
+                        let response = match retry_after {
+                            Some(retry_after) => Err(retry_after),
+                            None => Ok(srv.call(req)),
+                        };
+                        async move {
+                            match response {
+                                Ok(response) => response.await,
+                                Err(retry_after) => {
+                                    // Retry-After is in whole seconds
+                                    let seconds = retry_after.as_secs_f64().ceil() as u64;
+                                    let response = HttpResponse::TooManyRequests()
+                                        .insert_header((header::RETRY_AFTER, seconds.to_string()))
+                                        .finish();
+                                    Err(InternalError::from_response(
+                                        "rate limit exceeded",
+                                        response,
+                                    )
+                                    .into())
+                                }
+                            }
+                        }
+                    }
+                })
+                // Outside of authentication, since preflight requests carry
+                // no credentials
+                .wrap_fn({
+                    let allowed_origins = allowed_origins.clone();
+                    move |req, srv| {
+                        let origin = req
+                            .headers()
+                            .get(header::ORIGIN)
+                            .filter(|origin| {
+                                allowed_origins
+                                    .iter()
+                                    .any(|allowed| allowed == "*" || *origin == allowed.as_str())
+                            })
+                            .cloned();
+                        let preflight = origin.is_some()
+                            && req.method() == Method::OPTIONS
+                            && req
+                                .headers()
+                                .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+                        let response = if preflight {
+                            let mut builder = HttpResponse::NoContent();
+                            builder
+                                .insert_header((header::ACCESS_CONTROL_ALLOW_METHODS, "GET"))
+                                .insert_header((header::ACCESS_CONTROL_MAX_AGE, "3600"));
+                            if let Some(request_headers) =
+                                req.headers().get(header::ACCESS_CONTROL_REQUEST_HEADERS)
+                            {
+                                builder.insert_header((
+                                    header::ACCESS_CONTROL_ALLOW_HEADERS,
+                                    request_headers.clone(),
+                                ));
+                            }
+                            Err(req.into_response(builder.finish()))
+                        } else {
+                            Ok(srv.call(req))
+                        };
+                        let vary = !allowed_origins.is_empty();
+                        async move {
+                            let result = match response {
+                                Ok(response) => response.await.map(|r| r.map_into_boxed_body()),
+                                Err(preflight) => Ok(preflight),
+                            };
+                            // Errors (e.g., a 401) need CORS headers too, or
+                            // the browser hides them from the page
+                            match result {
+                                Ok(mut response) => {
+                                    Self::add_cors_headers(response.headers_mut(), origin, vary);
+                                    Ok(response)
+                                }
+                                Err(e) => {
+                                    let mut response = e.error_response();
+                                    Self::add_cors_headers(response.headers_mut(), origin, vary);
+                                    Err(InternalError::from_response(e, response).into())
+                                }
+                            }
+                        }
+                    }
+                })
                 .wrap(middleware::Logger::default())
                 .wrap(middleware::Compress::default())
+                // web::Json transparently decodes compressed request bodies,
+                // so let clients know they may send them (RFC 7694)
+                .wrap(
+                    middleware::DefaultHeaders::new()
+                        .add((header::ACCEPT_ENCODING, "gzip, deflate, br, zstd")),
+                )
                 .app_data(state.clone())
+                .app_data(entries.clone())
+                .app_data(recent_slot_tiles.clone())
+                .route("/health", web::get().to(Self::health))
+                .route("/version", web::get().to(Self::version))
                 .route("/entry", web::get().to(Self::get_entry_name))
                 .route("/info", web::get().to(Self::fetch_info))
                 .route("/interval", web::get().to(Self::interval))
+                .route("/time_origin", web::get().to(Self::time_origin))
                 .route("/tiles", web::get().to(Self::fetch_tiles))
                 .route("/slot_meta_tile", web::get().to(Self::fetch_slot_meta_tile))
                 .route("/slot_tile", web::get().to(Self::fetch_slot_tile))
+                .route(
+                    "/slot_tile_batch",
+                    web::get().to(Self::fetch_slot_tile_batch),
+                )
                 .route("/summary_tile", web::get().to(Self::fetch_summary_tile))
+                .route("/tile_size", web::get().to(Self::tile_size))
+                .route("/probe", web::get().to(Self::probe))
+                .route("/ws", web::get().to(Self::websocket))
         })
         .bind((self.host.as_str(), self.port))?
-        .run()
-        .await
+        .run();
+        let handle = server.handle();
+        Ok((server, handle))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::file::{snapshot, FileDataSource};
+    use crate::http::client::HTTPDataSource;
+    use crate::http::fetch::{DefaultTransport, Request, Transport};
+    use crate::mock::tests::{interval, sample, slot_id, summary_id};
+    use crate::mock::MockDataSource;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::net::{TcpListener, TcpStream};
+    use std::path::PathBuf;
+    use std::sync::Once;
+
+    // Serves source on a free local port for the rest of the test run, and
+    // returns a client for it
+    pub(crate) fn serve(source: impl DataSource + Sync + Send + 'static) -> HTTPDataSource {
+        start(DataSourceHTTPServer::new(
+            free_port(),
+            "127.0.0.1".to_owned(),
+            Box::new(source),
+        ))
+    }
+
+    fn free_port() -> u16 {
+        TcpListener::bind(("127.0.0.1", 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    // Like serve, but for a server that has already been set up
+    fn start(mut server: DataSourceHTTPServer) -> HTTPDataSource {
+        install_logger();
+        let port = server.port;
+        server.log_level = "warn".to_owned();
+        std::thread::spawn(move || {
+            actix_web::rt::System::new().block_on(async move {
+                let (server, _handle) = server.create_server_with_handle().unwrap();
+                server.await
+            })
+        });
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        HTTPDataSource::new("127.0.0.1".to_owned(), port)
+    }
+
+    thread_local! {
+        static CAPTURED_LOGS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+    }
+
+    // Keeps the messages logged by threads inside capture_logs, and drops
+    // the rest
+    struct CapturingLogger;
+
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, _: &log::Metadata<'_>) -> bool {
+            CAPTURED_LOGS.with(|captured| captured.borrow().is_some())
+        }
+        fn log(&self, record: &log::Record<'_>) {
+            CAPTURED_LOGS.with(|captured| {
+                if let Some(lines) = captured.borrow_mut().as_mut() {
+                    lines.push(record.args().to_string());
+                }
+            });
+        }
+        fn flush(&self) {}
+    }
+
+    // Must happen before any server starts, or the server's env_logger
+    // would take its place
+    fn install_logger() {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            let _ = log::set_logger(&CapturingLogger);
+            log::set_max_level(log::LevelFilter::Debug);
+        });
+    }
+
+    // The messages logged on this thread while f runs
+    pub(crate) fn capture_logs(f: impl FnOnce()) -> Vec<String> {
+        install_logger();
+        CAPTURED_LOGS.with(|captured| *captured.borrow_mut() = Some(Vec::new()));
+        f();
+        CAPTURED_LOGS.with(|captured| captured.borrow_mut().take().unwrap())
+    }
+
+    // A directory under the system's temporary directory that no other test
+    // uses. Removed first in case an earlier run left it behind.
+    pub(crate) fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("prof-viewer-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    // Hands out three tiles for whatever interval is requested, like the
+    // random source in main.rs does
+    struct SplittingDataSource(MockDataSource);
+
+    impl DataSource for SplittingDataSource {
+        fn interval(&mut self) -> Interval {
+            self.0.interval()
+        }
+        fn fetch_info(&mut self) -> EntryInfo {
+            self.0.fetch_info()
+        }
+        fn request_tiles(&mut self, _: &EntryID, request_interval: Interval) -> Vec<TileID> {
+            request_interval
+                .split_evenly(3)
+                .into_iter()
+                .map(TileID)
+                .collect()
+        }
+        fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+            let mut tile = self.0.fetch_summary_tile(entry_id, TileID(interval()));
+            tile.tile_id = tile_id;
+            tile.utilization
+                .retain(|point| tile_id.0.contains(point.time));
+            tile
+        }
+        fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+            let tile = self.0.fetch_slot_tile(entry_id, TileID(interval()));
+            let mut tile = clip_slot_tile(&tile, tile_id.0);
+            tile.tile_id = tile_id;
+            tile
+        }
+        fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+            let tile = self.0.fetch_slot_tile(entry_id, TileID(interval()));
+            let metas = self.0.fetch_slot_meta_tile(entry_id, TileID(interval()));
+            let mut metas = clip_slot_meta_tile(&tile, &metas, tile_id.0);
+            metas.tile_id = tile_id;
+            metas
+        }
+    }
+
+    fn item_uids(tile: &SlotTile) -> Vec<u64> {
+        tile.items[0].iter().map(|item| item.item_uid.0).collect()
+    }
+
+    // Every tile the source hands out must make it through the server intact
+    fn check_round_trip(mut source: impl DataSource + Sync + Send + 'static) {
+        let bounds = source.interval();
+        let tiles = source.request_tiles(&slot_id(), bounds);
+        let expected: Vec<_> = tiles
+            .iter()
+            .map(|&tile_id| item_uids(&source.fetch_slot_tile(&slot_id(), tile_id)))
+            .collect();
+        let mut client = serve(source);
+        assert_eq!(client.interval(), bounds);
+        for (tile_id, expected) in tiles.into_iter().zip(expected) {
+            let tile = client.fetch_slot_tile(&slot_id(), tile_id);
+            assert_eq!(tile.tile_id, tile_id);
+            assert_eq!(item_uids(&tile), expected);
+            let metas = client.fetch_slot_meta_tile(&slot_id(), tile_id);
+            assert_eq!(metas.items[0].len(), expected.len());
+            let summary = client.fetch_summary_tile(&summary_id(), tile_id);
+            assert_eq!(summary.tile_id, tile_id);
+            assert!(!summary.utilization.is_empty());
+        }
+        assert_eq!(client.last_error, None);
+        assert!(client.get_errors().is_empty());
+    }
+
+    #[test]
+    fn mock_tiles_round_trip() {
+        check_round_trip(sample());
+    }
+
+    #[test]
+    fn file_tiles_round_trip() {
+        let dir = temp_dir("file-round-trip");
+        snapshot(&mut sample(), &dir, |_, _| {}).unwrap();
+        check_round_trip(FileDataSource::new(&dir).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // Asking for the same interval again gives different tiles, which must
+    // still be served
+    #[test]
+    fn split_tiles_round_trip() {
+        check_round_trip(SplittingDataSource(sample()));
+        let mut client = serve(SplittingDataSource(sample()));
+        let tile_id = TileID(Interval::new(Timestamp(0), Timestamp(333)));
+        assert_eq!(
+            item_uids(&client.fetch_slot_tile(&slot_id(), tile_id)),
+            vec![0, 1, 2, 3]
+        );
+        assert_eq!(client.last_error, None);
+    }
+
+    #[test]
+    fn empty_tile_is_served() {
+        let mut client = serve(SplittingDataSource(sample()));
+        let tile_id = TileID(Interval::new(Timestamp(500), Timestamp(500)));
+        let tile = client.fetch_slot_tile(&slot_id(), tile_id);
+        assert_eq!(client.last_error, None);
+        assert!(tile.items[0].is_empty());
+    }
+
+    #[test]
+    fn tiles_outside_the_profile_are_rejected() {
+        let mut client = serve(sample());
+        for (start, stop) in [(-1, 100), (0, 1001), (600, 500)] {
+            let tile_id = TileID(Interval::new(Timestamp(start), Timestamp(stop)));
+            client.fetch_slot_tile(&slot_id(), tile_id);
+            let error = client.last_error.take().unwrap();
+            assert!(error.starts_with("400"), "{error}");
+        }
+    }
+
+    // Counts calls to fetch_info, and to fetch_slot_tile
+    struct CountingDataSource(MockDataSource, Arc<AtomicUsize>, Arc<AtomicUsize>);
+
+    impl DataSource for CountingDataSource {
+        fn interval(&mut self) -> Interval {
+            self.0.interval()
+        }
+        fn fetch_info(&mut self) -> EntryInfo {
+            self.1.fetch_add(1, Ordering::Relaxed);
+            self.0.fetch_info()
+        }
+        fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+            self.0.request_tiles(entry_id, request_interval)
+        }
+        fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+            self.0.fetch_summary_tile(entry_id, tile_id)
+        }
+        fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+            self.2.fetch_add(1, Ordering::Relaxed);
+            self.0.fetch_slot_tile(entry_id, tile_id)
+        }
+        fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+            self.0.fetch_slot_meta_tile(entry_id, tile_id)
+        }
+    }
+
+    #[test]
+    fn narrow_clips_return_only_overlapping_items() {
+        let slot_fetches = Arc::new(AtomicUsize::new(0));
+        let source = CountingDataSource(sample(), Default::default(), slot_fetches.clone());
+        let mut client = serve(source);
+        let tile_id = TileID(interval());
+        // Only items 2 (200 to 250 ns) and 3 (300 to 350 ns) overlap
+        let clip = Interval::new(Timestamp(240), Timestamp(310));
+        let tile = client.fetch_clipped_slot_tile(&slot_id(), tile_id, clip);
+        assert_eq!(item_uids(&tile), vec![2, 3]);
+        let metas = client.fetch_clipped_slot_meta_tile(&slot_id(), tile_id, clip);
+        let titles: Vec<_> = metas.items[0]
+            .iter()
+            .map(|meta| meta.title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["Task 2", "Task 3"]);
+        // The metas were clipped without fetching the tile again
+        assert_eq!(slot_fetches.load(Ordering::Relaxed), 1);
+        assert_eq!(client.last_error, None);
+    }
+
+    #[test]
+    fn unknown_entries_refresh_info_at_most_once() {
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let source = CountingDataSource(sample(), fetches.clone(), Default::default());
+        let data = BlockingDataSource(Arc::new(AppState {
+            data_sources: vec![Mutex::new(Box::new(source))],
+            next: AtomicUsize::new(0),
+        }));
+        let entries = EntryInfoCache::default();
+        let unknown = EntryID::root().child(7);
+        actix_web::rt::System::new().block_on(async {
+            assert!(entries
+                .check(&data, &slot_id(), "slot", is_slot)
+                .await
+                .is_ok());
+            for _ in 0..10 {
+                let result = entries.check(&data, &unknown, "slot", is_slot).await;
+                assert_eq!(result.unwrap_err().as_response_error().status_code(), 404);
+            }
+            // A known entry of the wrong kind doesn't refresh either
+            assert!(entries
+                .check(&data, &summary_id(), "slot", is_slot)
+                .await
+                .is_err());
+            assert!(entries
+                .check(&data, &summary_id(), "summary", is_summary)
+                .await
+                .is_ok());
+        });
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn summary_samples_are_bounded() {
+        let mut client = serve(sample());
+        let tile_id = TileID(interval());
+        for samples in [0, MAX_SUMMARY_SAMPLES + 1, usize::MAX] {
+            client.summary_samples = Some(samples);
+            assert!(client
+                .fetch_summary_tile(&summary_id(), tile_id)
+                .utilization
+                .is_empty());
+            let error = client.last_error.take().unwrap();
+            assert!(error.starts_with("400"), "{error}");
+        }
+        client.set_summary_samples(usize::MAX);
+        let tile = client.fetch_summary_tile(&summary_id(), tile_id);
+        assert_eq!(tile.utilization.len(), MAX_SUMMARY_SAMPLES);
+        client.set_summary_samples(100);
+        let tile = client.fetch_summary_tile(&summary_id(), tile_id);
+        assert_eq!(tile.utilization.len(), 100);
+        // Fewer samples than points, which are averaged (see
+        // aggregated_summaries_are_downsampled)
+        client.set_summary_samples(2);
+        let tile = client.fetch_summary_tile(&summary_id(), tile_id);
+        let utils: Vec<_> = tile.utilization.iter().map(|point| point.util).collect();
+        assert_eq!(utils, vec![0.4, 0.5]);
+        assert_eq!(client.last_error, None);
+    }
+
+    #[test]
+    fn requests_need_the_token() {
+        let port = free_port();
+        let server = DataSourceHTTPServer::new(port, "127.0.0.1".to_owned(), Box::new(sample()))
+            .with_token("secret".to_owned());
+        let mut client = start(server);
+        client.interval();
+        assert_eq!(client.last_error().as_deref(), Some("401 Unauthorized"));
+
+        let mut client =
+            HTTPDataSource::new("127.0.0.1".to_owned(), port).with_token("wrong".to_owned());
+        client.interval();
+        assert_eq!(client.last_error().as_deref(), Some("401 Unauthorized"));
+
+        let mut client =
+            HTTPDataSource::new("127.0.0.1".to_owned(), port).with_token("secret".to_owned());
+        assert_eq!(client.interval(), interval());
+        assert_eq!(client.last_error(), None);
+    }
+
+    #[test]
+    fn binary_tiles_round_trip() {
+        let mut source = sample();
+        let tile_id = TileID(interval());
+        let expected =
+            bincode::serialize(&source.fetch_summary_tile(&summary_id(), tile_id)).unwrap();
+        let mut client = serve(source);
+
+        let body = FetchRequest {
+            entry_id: summary_id(),
+            tile_id,
+            samples: None,
+            clip: None,
+        };
+        let request = Request::get(format!("{}summary_tile", client.base_url()))
+            .header("content-type", "application/json")
+            .header("accept", BINARY_CONTENT_TYPE)
+            .body(serde_json::to_vec(&body).unwrap());
+        let response = DefaultTransport::default()
+            .send(&request, Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(response.header("content-type"), Some(BINARY_CONTENT_TYPE));
+        assert_eq!(response.body, expected);
+
+        client.binary = true;
+        let tile = client.fetch_summary_tile(&summary_id(), tile_id);
+        assert_eq!(bincode::serialize(&tile).unwrap(), expected);
+        assert_eq!(client.last_error(), None);
+    }
+
+    #[test]
+    fn health_and_version_are_served() {
+        let port = free_port();
+        let server = DataSourceHTTPServer::new(port, "127.0.0.1".to_owned(), Box::new(sample()))
+            .with_token("secret".to_owned());
+        let client = start(server);
+        let get = |path: &str, token: Option<&str>| {
+            let mut request = Request::get(format!("{}{path}", client.base_url()));
+            if let Some(token) = token {
+                request = request.header("authorization", format!("Bearer {token}"));
+            }
+            DefaultTransport::default()
+                .send(&request, Duration::from_secs(5))
+                .unwrap()
+        };
+
+        // Load balancers don't have the token
+        let response = get("health", None);
+        assert_eq!(response.status, 200);
+        let health: HealthResponse = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(health.status, "ok");
+
+        assert_eq!(get("version", None).status, 401);
+        let response = get("version", Some("secret"));
+        assert_eq!(response.status, 200);
+        let version: VersionResponse = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+        assert!(!version.git_hash.is_empty());
+    }
+
+    #[test]
+    fn log_level_does_not_override_rust_log() {
+        install_logger();
+        std::env::set_var("RUST_LOG", "warn");
+        let server =
+            DataSourceHTTPServer::new(free_port(), "127.0.0.1".to_owned(), Box::new(sample()))
+                .with_log_level("debug".to_owned());
+        actix_web::rt::System::new().block_on(async move {
+            let (server, handle) = server.create_server_with_handle().unwrap();
+            actix_web::rt::spawn(server);
+            handle.stop(false).await;
+        });
+        assert_eq!(std::env::var("RUST_LOG").as_deref(), Ok("warn"));
+    }
+
+    #[test]
+    fn servers_shut_down_gracefully() {
+        install_logger();
+        let port = free_port();
+        let server = DataSourceHTTPServer::new(port, "127.0.0.1".to_owned(), Box::new(sample()))
+            .with_log_level("warn".to_owned());
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let thread = std::thread::spawn(move || {
+            actix_web::rt::System::new().block_on(async move {
+                let (server, handle) = server.create_server_with_handle().unwrap();
+                sender.send(handle).unwrap();
+                server.await
+            })
+        });
+        let handle = receiver.recv().unwrap();
+        let mut client = HTTPDataSource::new("127.0.0.1".to_owned(), port);
+        assert_eq!(client.interval(), interval());
+        // Otherwise the stop waits for the idle keep-alive connection to
+        // time out
+        drop(client);
+
+        actix_web::rt::System::new().block_on(handle.stop(true));
+        thread.join().unwrap().unwrap();
+        assert!(TcpStream::connect(("127.0.0.1", port)).is_err());
+    }
+
+    #[test]
+    fn batches_preserve_order() {
+        let mut client = serve(SplittingDataSource(sample()));
+        // Backwards, so that the server can't just be sorting them
+        let tile_ids: Vec<_> = (0..5)
+            .rev()
+            .map(|i| TileID(Interval::new(Timestamp(i * 200), Timestamp(i * 200 + 200))))
+            .collect();
+        let requests: Vec<_> = tile_ids
+            .iter()
+            .map(|&tile_id| FetchRequest {
+                entry_id: slot_id(),
+                tile_id,
+                samples: None,
+                clip: None,
+            })
+            .collect();
+        let tiles = client.fetch_slot_tile_batch(&requests);
+        assert_eq!(client.last_error(), None);
+        assert_eq!(tiles.len(), 5);
+        for (i, (tile, tile_id)) in tiles.iter().zip(tile_ids).enumerate() {
+            assert_eq!(tile.tile_id, tile_id);
+            // Tile [200k, 200k + 200) holds items 2k and 2k + 1
+            let first = 2 * (4 - i as u64);
+            assert_eq!(item_uids(tile), vec![first, first + 1]);
+        }
+
+        // Too many for one batch, so split up by the client
+        let requests: Vec<_> = (0..MAX_BATCH_SIZE + 1)
+            .map(|_| FetchRequest {
+                entry_id: slot_id(),
+                tile_id: TileID(interval()),
+                samples: None,
+                clip: None,
+            })
+            .collect();
+        let tiles = client.fetch_slot_tile_batch(&requests);
+        assert_eq!(client.last_error(), None);
+        assert_eq!(tiles.len(), MAX_BATCH_SIZE + 1);
+        assert!(tiles.iter().all(|tile| item_uids(tile).len() == 10));
+    }
+
+    #[test]
+    fn polled_slot_tiles_come_in_one_batch() {
+        let mut client = serve(SplittingDataSource(sample()));
+        let clip = Interval::new(Timestamp(100), Timestamp(700));
+        let mut tiles = None;
+        let logs = capture_logs(|| tiles = client.poll_slot_tiles(&slot_id(), clip));
+        let tiles = tiles.unwrap();
+        assert_eq!(client.last_error(), None);
+        let requests: Vec<_> = logs
+            .iter()
+            .filter(|line| line.starts_with("GET /slot_tile") && !line.contains(" bytes in "))
+            .collect();
+        assert_eq!(requests, ["GET /slot_tile_batch 3 tiles"], "{logs:?}");
+
+        // The same as fetching them one at a time
+        assert_eq!(tiles.len(), 3);
+        for tile in &tiles {
+            let single = client.fetch_clipped_slot_tile(&slot_id(), tile.tile_id, clip);
+            assert_eq!(item_uids(tile), item_uids(&single));
+        }
+    }
+
+    #[test]
+    fn bursts_are_rate_limited() {
+        let port = free_port();
+        let server = DataSourceHTTPServer::new(port, "127.0.0.1".to_owned(), Box::new(sample()))
+            .with_rate_limit(2.0, 3);
+        let client = start(server);
+        let transport = DefaultTransport::default();
+        let get = |path: &str| {
+            let request = Request::get(format!("{}{path}", client.base_url()));
+            transport.send(&request, Duration::from_secs(5)).unwrap()
+        };
+
+        for _ in 0..3 {
+            assert_eq!(get("interval").status, 200);
+        }
+        let response = get("interval");
+        assert_eq!(response.status, 429);
+        // A request is let through every 500 ms, rounded up to whole seconds
+        assert_eq!(response.header("retry-after"), Some("1"));
+        // Load balancers are never turned away
+        assert_eq!(get("health").status, 200);
+
+        std::thread::sleep(Duration::from_millis(600));
+        assert_eq!(get("interval").status, 200);
+        assert_eq!(get("interval").status, 429);
+    }
+
+    // Takes info_delay to fetch the info, and summary_delay to fetch each
+    // summary tile
+    struct SleepyDataSource {
+        inner: MockDataSource,
+        info_delay: Duration,
+        summary_delay: Duration,
+    }
+
+    impl DataSource for SleepyDataSource {
+        fn interval(&mut self) -> Interval {
+            self.inner.interval()
+        }
+        fn fetch_info(&mut self) -> EntryInfo {
+            std::thread::sleep(self.info_delay);
+            self.inner.fetch_info()
+        }
+        fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+            self.inner.request_tiles(entry_id, request_interval)
+        }
+        fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+            std::thread::sleep(self.summary_delay);
+            self.inner.fetch_summary_tile(entry_id, tile_id)
+        }
+        fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+            self.inner.fetch_slot_tile(entry_id, tile_id)
+        }
+        fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+            self.inner.fetch_slot_meta_tile(entry_id, tile_id)
+        }
+    }
+
+    // Fetches the summary tile of sample from port on each of clients
+    // threads at once, returning each one's last_error
+    fn fetch_concurrently(port: u16, clients: usize) -> Vec<Option<String>> {
+        let threads: Vec<_> = (0..clients)
+            .map(|_| {
+                std::thread::spawn(move || {
+                    let mut client = HTTPDataSource::new("127.0.0.1".to_owned(), port);
+                    let tile = client.fetch_summary_tile(&summary_id(), TileID(interval()));
+                    assert_eq!(tile.tile_id, TileID(interval()));
+                    client.last_error()
+                })
+            })
+            .collect();
+        threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn first_requests_do_not_race_the_entry_info() {
+        let port = free_port();
+        let source = SleepyDataSource {
+            inner: sample(),
+            info_delay: Duration::from_millis(100),
+            summary_delay: Duration::ZERO,
+        };
+        start(DataSourceHTTPServer::new(
+            port,
+            "127.0.0.1".to_owned(),
+            Box::new(source),
+        ));
+        assert_eq!(fetch_concurrently(port, 4), vec![None; 4]);
+    }
+
+    #[test]
+    fn replicas_serve_requests_in_parallel() {
+        const REPLICAS: usize = 4;
+        const DELAY: Duration = Duration::from_millis(300);
+        let sleepy = || {
+            Box::new(SleepyDataSource {
+                inner: sample(),
+                info_delay: Duration::ZERO,
+                summary_delay: DELAY,
+            })
+        };
+        let port = free_port();
+        let mut server = DataSourceHTTPServer::new(port, "127.0.0.1".to_owned(), sleepy());
+        for _ in 1..REPLICAS {
+            server = server.with_replica(sleepy());
+        }
+        start(server);
+
+        let start = Instant::now();
+        assert_eq!(fetch_concurrently(port, REPLICAS), vec![None; REPLICAS]);
+        // One after another would take REPLICAS times as long
+        let elapsed = start.elapsed();
+        assert!(elapsed < DELAY * 2, "took {elapsed:?}");
+    }
+
+    // Serves a MockDataSource, but waits (without blocking) before answering
+    struct AwaitingDataSource(Mutex<MockDataSource>);
+
+    impl AwaitingDataSource {
+        fn after_wait<'a, T: 'a>(
+            &'a self,
+            f: impl FnOnce(&mut MockDataSource) -> T + 'a,
+        ) -> BoxFuture<'a, T> {
+            Box::pin(async move {
+                actix_web::rt::time::sleep(Duration::from_millis(10)).await;
+                f(&mut self.0.lock().unwrap())
+            })
+        }
+    }
+
+    impl AsyncDataSource for AwaitingDataSource {
+        fn interval(&self) -> BoxFuture<'_, Interval> {
+            self.after_wait(|source| source.interval())
+        }
+        fn fetch_info(&self) -> BoxFuture<'_, EntryInfo> {
+            self.after_wait(|source| source.fetch_info())
+        }
+        fn request_tiles<'a>(
+            &'a self,
+            entry_id: &'a EntryID,
+            request_interval: Interval,
+        ) -> BoxFuture<'a, Vec<TileID>> {
+            self.after_wait(move |source| source.request_tiles(entry_id, request_interval))
+        }
+        fn fetch_summary_tile<'a>(
+            &'a self,
+            entry_id: &'a EntryID,
+            tile_id: TileID,
+        ) -> BoxFuture<'a, SummaryTile> {
+            self.after_wait(move |source| source.fetch_summary_tile(entry_id, tile_id))
+        }
+        fn fetch_slot_tile<'a>(
+            &'a self,
+            entry_id: &'a EntryID,
+            tile_id: TileID,
+        ) -> BoxFuture<'a, SlotTile> {
+            self.after_wait(move |source| source.fetch_slot_tile(entry_id, tile_id))
+        }
+        fn fetch_slot_meta_tile<'a>(
+            &'a self,
+            entry_id: &'a EntryID,
+            tile_id: TileID,
+        ) -> BoxFuture<'a, SlotMetaTile> {
+            self.after_wait(move |source| source.fetch_slot_meta_tile(entry_id, tile_id))
+        }
+        fn probe<'a>(
+            &'a self,
+            entry_id: &'a EntryID,
+            at: Timestamp,
+        ) -> BoxFuture<'a, Option<ItemMeta>> {
+            self.after_wait(move |source| source.probe(entry_id, at))
+        }
+        fn time_origin(&self) -> BoxFuture<'_, TimeOrigin> {
+            self.after_wait(|source| source.time_origin())
+        }
+        fn tile_byte_estimate<'a>(
+            &'a self,
+            entry_id: &'a EntryID,
+            tile_id: TileID,
+        ) -> BoxFuture<'a, Option<usize>> {
+            self.after_wait(move |source| source.tile_byte_estimate(entry_id, tile_id))
+        }
+    }
+
+    #[test]
+    fn async_sources_are_awaited() {
+        let mut source = sample();
+        let tile_id = TileID(interval());
+        let expected = source.fetch_slot_tile(&slot_id(), tile_id);
+        let mut client = start(DataSourceHTTPServer::new_async(
+            free_port(),
+            "127.0.0.1".to_owned(),
+            Arc::new(AwaitingDataSource(Mutex::new(source))),
+        ));
+
+        assert_eq!(client.interval(), interval());
+        let tile = client.fetch_slot_tile(&slot_id(), tile_id);
+        assert_eq!(client.last_error(), None);
+        assert_eq!(
+            bincode::serialize(&tile).unwrap(),
+            bincode::serialize(&expected).unwrap()
+        );
+    }
+
+    #[test]
+    fn only_allowed_origins_get_cors_headers() {
+        let server =
+            DataSourceHTTPServer::new(free_port(), "127.0.0.1".to_owned(), Box::new(sample()))
+                .with_allowed_origins(vec!["https://allowed.example".to_owned()]);
+        let client = start(server);
+        let get = |origin: &str| {
+            let request =
+                Request::get(format!("{}interval", client.base_url())).header("origin", origin);
+            DefaultTransport::default()
+                .send(&request, Duration::from_secs(5))
+                .unwrap()
+        };
+
+        let response = get("https://allowed.example");
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            response.header("access-control-allow-origin"),
+            Some("https://allowed.example")
+        );
+        assert_eq!(
+            response.header("access-control-expose-headers"),
+            Some("etag")
+        );
+
+        // Still served (CORS is enforced by the browser), but without the
+        // headers that would let the page read it
+        let response = get("https://other.example");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("access-control-allow-origin"), None);
+        assert_eq!(response.header("access-control-expose-headers"), None);
+    }
+
+    #[test]
+    fn gzipped_batches_are_decoded() {
+        let client = serve(SplittingDataSource(sample()));
+        let requests: Vec<_> = (0..5)
+            .map(|i| FetchRequest {
+                entry_id: slot_id(),
+                tile_id: TileID(Interval::new(Timestamp(i * 200), Timestamp(i * 200 + 200))),
+                samples: None,
+                clip: None,
+            })
+            .collect();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(&serde_json::to_vec(&requests).unwrap())
+            .unwrap();
+        let request = Request::get(format!("{}slot_tile_batch", client.base_url()))
+            .header("content-type", "application/json")
+            .header("content-encoding", "gzip")
+            .body(encoder.finish().unwrap());
+        let response = DefaultTransport::default()
+            .send(&request, Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(response.status, 200);
+        // Which is how clients know they may compress
+        assert!(response
+            .header_values("accept-encoding")
+            .any(|value| value.contains("gzip")));
+        let tiles: Vec<SlotTile> = serde_json::from_slice(&response.body).unwrap();
+        let uids: Vec<_> = tiles.iter().map(item_uids).collect();
+        assert_eq!(
+            uids,
+            vec![vec![0, 1], vec![2, 3], vec![4, 5], vec![6, 7], vec![8, 9]]
+        );
     }
 }
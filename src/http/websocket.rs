@@ -0,0 +1,408 @@
+use crate::{
+    data::{
+        DataSource, DataSourceError, EntryID, EntryInfo, ItemMeta, SlotMetaTile, SlotTile,
+        SummaryTile, TileID, TimeOrigin,
+    },
+    http::client::HTTPDataSource,
+    http::schema::{ClientMessage, ServerMessage, TileKind},
+    http::server::FetchRequest,
+    timestamp::{Interval, Timestamp},
+};
+
+use actix_codec::{Decoder, Encoder};
+use actix_http::ws;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use bytes::BytesMut;
+use rand::Rng;
+use url::Url;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+// Largest frame accepted from the server. Tiles of busy slots are much bigger
+// than the codec's default of 64 KiB.
+const MAX_FRAME_SIZE: usize = 256 << 20;
+
+/// Fetches tiles over a WebSocket to the /ws endpoint of a
+/// DataSourceHTTPServer, and everything else (e.g., the entry hierarchy)
+/// from the same server's GET endpoints through an HTTPDataSource. Wrap it
+/// in a DeferredDataSource to fetch in the background.
+///
+/// The connection is opened on the first tile fetch, and opened again on the
+/// next one if it breaks.
+pub struct WebSocketDataSource {
+    pub http: HTTPDataSource,
+    socket: Option<Socket>,
+    next_id: u64,
+    // Why the most recent tile fetch failed, or None if it succeeded
+    last_error: Option<String>,
+    // Failed tile fetches not yet drained by get_errors
+    errors: Vec<DataSourceError>,
+}
+
+// The client's end of a /ws connection
+struct Socket {
+    stream: TcpStream,
+    codec: ws::Codec,
+    // Received but not yet decoded
+    buf: BytesMut,
+}
+
+impl Socket {
+    fn connect(http: &HTTPDataSource) -> Result<Self, String> {
+        let describe = |e: std::io::Error| format!("unable to connect to /ws: {e}");
+        let stream = TcpStream::connect((http.host.as_str(), http.port)).map_err(describe)?;
+        stream
+            .set_read_timeout(Some(http.config.timeout))
+            .map_err(describe)?;
+        stream
+            .set_write_timeout(Some(http.config.timeout))
+            .map_err(describe)?;
+        let mut socket = Self {
+            stream,
+            codec: ws::Codec::new().client_mode().max_size(MAX_FRAME_SIZE),
+            buf: BytesMut::new(),
+        };
+        socket.handshake(http).map_err(describe)?;
+        Ok(socket)
+    }
+
+    fn handshake(&mut self, http: &HTTPDataSource) -> std::io::Result<()> {
+        let key = BASE64.encode(rand::thread_rng().gen::<[u8; 16]>());
+        let mut request = format!(
+            "GET /ws HTTP/1.1\r\n\
+             Host: {}:{}\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Key: {key}\r\n\
+             Sec-WebSocket-Version: 13\r\n",
+            http.host, http.port
+        );
+        if let Some(token) = &http.token {
+            request.push_str(&format!("Authorization: Bearer {token}\r\n"));
+        }
+        request.push_str("\r\n");
+        self.stream.write_all(request.as_bytes())?;
+
+        // Anything after the head is already the first frame
+        let end = loop {
+            if let Some(end) = find_head_end(&self.buf) {
+                break end;
+            }
+            self.fill_buf()?;
+        };
+        let head = String::from_utf8_lossy(&self.buf[..end]).into_owned();
+        let _ = self.buf.split_to(end);
+
+        let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::Other, message);
+        let status = head.lines().next().unwrap_or_default();
+        if status.split(' ').nth(1) != Some("101") {
+            return Err(invalid(format!(
+                "expected 101 Switching Protocols, not {status:?}"
+            )));
+        }
+        let expected = ws::hash_key(key.as_bytes());
+        let accepted = head.lines().any(|line| match line.split_once(':') {
+            Some((name, value)) => {
+                name.eq_ignore_ascii_case("sec-websocket-accept")
+                    && value.trim().as_bytes() == expected
+            }
+            None => false,
+        });
+        if !accepted {
+            return Err(invalid("missing or wrong Sec-WebSocket-Accept".to_owned()));
+        }
+        Ok(())
+    }
+
+    fn fill_buf(&mut self) -> std::io::Result<()> {
+        let mut chunk = [0; 64 << 10];
+        let n = self.stream.read(&mut chunk)?;
+        if n == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        self.buf.extend_from_slice(&chunk[..n]);
+        Ok(())
+    }
+
+    fn send(&mut self, message: ws::Message) -> Result<(), String> {
+        let mut out = BytesMut::new();
+        self.codec
+            .encode(message, &mut out)
+            .map_err(|e| e.to_string())?;
+        self.stream.write_all(&out).map_err(|e| e.to_string())
+    }
+
+    fn receive(&mut self) -> Result<ws::Frame, String> {
+        loop {
+            if let Some(frame) = self
+                .codec
+                .decode(&mut self.buf)
+                .map_err(|e| e.to_string())?
+            {
+                return Ok(frame);
+            }
+            self.fill_buf().map_err(|e| e.to_string())?;
+        }
+    }
+
+    // Sends message and waits for the reply to it. Requests are sent one at
+    // a time, so replies with other ids aren't expected, and are skipped.
+    fn request(&mut self, message: &ClientMessage, id: u64) -> Result<ServerMessage, String> {
+        let json = serde_json::to_string(message).expect("unable to serialize message");
+        self.send(ws::Message::Text(json.into()))?;
+        loop {
+            let reply = match self.receive()? {
+                ws::Frame::Text(text) => {
+                    serde_json::from_slice::<ServerMessage>(&text).map_err(|e| e.to_string())?
+                }
+                ws::Frame::Ping(bytes) => {
+                    self.send(ws::Message::Pong(bytes))?;
+                    continue;
+                }
+                ws::Frame::Close(_) => return Err("connection closed by server".to_owned()),
+                _ => continue,
+            };
+            let reply_id = match &reply {
+                ServerMessage::SummaryTile { id, .. }
+                | ServerMessage::SlotTile { id, .. }
+                | ServerMessage::SlotMetaTile { id, .. } => Some(*id),
+                ServerMessage::Error { id, error } => match id {
+                    Some(id) => Some(*id),
+                    None => return Err(error.clone()),
+                },
+            };
+            if reply_id == Some(id) {
+                return Ok(reply);
+            }
+        }
+    }
+}
+
+// The length of the HTTP response head in buf, including the blank line
+fn find_head_end(buf: &[u8]) -> Option<usize> {
+    buf.windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|i| i + 4)
+}
+
+impl WebSocketDataSource {
+    // The URL must have a host, as checked by parse_urls
+    pub fn from_url(url: &Url) -> Self {
+        Self::new(HTTPDataSource::new(
+            url.host_str().unwrap().to_owned(),
+            url.port_or_known_default().unwrap_or(80),
+        ))
+    }
+
+    // http's host, port, token and timeout are used for the WebSocket too
+    pub fn new(http: HTTPDataSource) -> Self {
+        Self {
+            http,
+            socket: None,
+            next_id: 0,
+            last_error: None,
+            errors: Vec::new(),
+        }
+    }
+
+    fn fetch(&mut self, kind: TileKind, request: FetchRequest) -> Option<ServerMessage> {
+        let (entry_id, tile_id) = (request.entry_id.clone(), request.tile_id);
+        let id = self.next_id;
+        self.next_id += 1;
+        let message = ClientMessage::Subscribe { id, kind, request };
+
+        let socket = match self.socket.take() {
+            Some(socket) => Ok(socket),
+            None => Socket::connect(&self.http),
+        };
+        // Whatever breaks, the connection can't be trusted to be in sync
+        // anymore, so it is only kept if a reply arrived
+        let result = socket.and_then(|mut socket| {
+            let reply = socket.request(&message, id)?;
+            self.socket = Some(socket);
+            Ok(reply)
+        });
+        let error = match result {
+            Ok(ServerMessage::Error { error, .. }) | Err(error) => error,
+            Ok(reply) => {
+                self.last_error = None;
+                return Some(reply);
+            }
+        };
+        self.errors.push(DataSourceError {
+            entry_id,
+            tile_id,
+            message: error.clone(),
+        });
+        self.last_error = Some(error);
+        None
+    }
+
+    fn slot_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Option<Interval>,
+    ) -> SlotTile {
+        let request = FetchRequest {
+            entry_id: entry_id.clone(),
+            tile_id,
+            samples: None,
+            clip,
+        };
+        match self.fetch(TileKind::Slot, request) {
+            Some(ServerMessage::SlotTile { tile, .. }) => tile,
+            _ => SlotTile {
+                tile_id,
+                items: Vec::new(),
+            },
+        }
+    }
+
+    fn slot_meta_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Option<Interval>,
+    ) -> SlotMetaTile {
+        let request = FetchRequest {
+            entry_id: entry_id.clone(),
+            tile_id,
+            samples: None,
+            clip,
+        };
+        match self.fetch(TileKind::SlotMeta, request) {
+            Some(ServerMessage::SlotMetaTile { tile, .. }) => tile,
+            _ => SlotMetaTile {
+                tile_id,
+                items: Vec::new(),
+            },
+        }
+    }
+}
+
+impl DataSource for WebSocketDataSource {
+    fn interval(&mut self) -> Interval {
+        self.http.interval()
+    }
+    fn fetch_info(&mut self) -> EntryInfo {
+        self.http.fetch_info()
+    }
+    fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+        self.http.request_tiles(entry_id, request_interval)
+    }
+    fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+        let request = FetchRequest {
+            entry_id: entry_id.clone(),
+            tile_id,
+            samples: self.http.summary_samples,
+            clip: None,
+        };
+        match self.fetch(TileKind::Summary, request) {
+            Some(ServerMessage::SummaryTile { tile, .. }) => tile,
+            _ => SummaryTile {
+                tile_id,
+                utilization: Vec::new(),
+            },
+        }
+    }
+    fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+        self.slot_tile(entry_id, tile_id, None)
+    }
+    fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+        self.slot_meta_tile(entry_id, tile_id, None)
+    }
+    fn fetch_clipped_slot_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Interval,
+    ) -> SlotTile {
+        self.slot_tile(entry_id, tile_id, Some(clip))
+    }
+    fn fetch_clipped_slot_meta_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Interval,
+    ) -> SlotMetaTile {
+        self.slot_meta_tile(entry_id, tile_id, Some(clip))
+    }
+    fn probe(&mut self, entry_id: &EntryID, at: Timestamp) -> Option<ItemMeta> {
+        self.http.probe(entry_id, at)
+    }
+    fn last_error(&self) -> Option<String> {
+        self.last_error.clone().or_else(|| self.http.last_error())
+    }
+    fn get_errors(&mut self) -> Vec<DataSourceError> {
+        let mut errors = std::mem::take(&mut self.errors);
+        errors.extend(self.http.get_errors());
+        errors
+    }
+    fn time_origin(&mut self) -> TimeOrigin {
+        self.http.time_origin()
+    }
+    fn set_summary_samples(&mut self, samples: usize) {
+        self.http.set_summary_samples(samples)
+    }
+    fn tile_byte_estimate(&mut self, entry_id: &EntryID, tile_id: TileID) -> Option<usize> {
+        self.http.tile_byte_estimate(entry_id, tile_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::server::tests::serve;
+    use crate::mock::tests::{interval, item_interval, sample, slot_id, summary_id};
+
+    #[test]
+    fn subscriptions_are_answered_with_tiles() {
+        let mut source = WebSocketDataSource::new(serve(sample()));
+        let tile_id = TileID(interval());
+        let tile = source.fetch_slot_tile(&slot_id(), tile_id);
+        assert_eq!(tile.items[0].len(), 10);
+        let summary = source.fetch_summary_tile(&summary_id(), tile_id);
+        assert_eq!(summary.utilization.len(), 11);
+
+        // Clipped on the server, as over HTTP
+        let clip = item_interval(2);
+        let metas = source.fetch_clipped_slot_meta_tile(&slot_id(), tile_id, clip);
+        let titles: Vec<_> = metas.items[0].iter().map(|meta| &meta.title).collect();
+        assert_eq!(titles, ["Task 2"]);
+        assert_eq!(source.last_error(), None);
+        assert!(source.get_errors().is_empty());
+        // All over the same connection
+        assert!(source.socket.is_some());
+    }
+
+    #[test]
+    fn failed_subscriptions_are_reported() {
+        let mut source = WebSocketDataSource::new(serve(sample()));
+        let bad_tile = TileID(Interval::new(Timestamp(-10), Timestamp(10)));
+        let tile = source.fetch_slot_tile(&slot_id(), bad_tile);
+        assert!(tile.items.is_empty());
+        assert!(source
+            .last_error()
+            .unwrap()
+            .starts_with("no tile from -10 to 10 ns"));
+        let errors = source.get_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].tile_id, bad_tile);
+
+        // The connection is still usable
+        let tile = source.fetch_slot_tile(&slot_id(), TileID(interval()));
+        assert_eq!(tile.items[0].len(), 10);
+        assert_eq!(source.last_error(), None);
+    }
+
+    #[test]
+    fn parse_urls_accepts_websockets() {
+        use crate::http::client::parse_urls;
+
+        let urls = parse_urls("ws://localhost:8080,http://localhost:8081").unwrap();
+        assert_eq!(urls[0].scheme(), "ws");
+        assert!(parse_urls("wss://localhost:8080").is_err());
+    }
+}
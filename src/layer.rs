@@ -0,0 +1,274 @@
+use std::time::Instant;
+
+use crate::data::{
+    DataSource, DataSourceError, EntryID, EntryInfo, ItemMeta, SearchHit, SlotMetaTile, SlotTile,
+    SummaryTile, TileID, TimeOrigin,
+};
+use crate::timestamp::{Interval, Timestamp};
+
+/// Something that wraps a DataSource to add behavior, e.g., caching. Layers
+/// compose with DataSourceStack.
+pub trait DataSourceLayer {
+    fn wrap(self, inner: Box<dyn DataSource>) -> Box<dyn DataSource>;
+}
+
+/// Builds a DataSource out of layers, e.g.,
+/// `DataSourceStack::new(inner).layer(CacheLayer::new(budget)).layer(LogLayer).build()`.
+///
+/// Each layer wraps the ones added before it, so in that example every call
+/// is logged, whether or not the cache answers it.
+pub struct DataSourceStack {
+    data_source: Box<dyn DataSource>,
+}
+
+impl DataSourceStack {
+    pub fn new(inner: Box<dyn DataSource>) -> Self {
+        Self { data_source: inner }
+    }
+
+    pub fn layer(self, layer: impl DataSourceLayer) -> Self {
+        Self {
+            data_source: layer.wrap(self.data_source),
+        }
+    }
+
+    pub fn build(self) -> Box<dyn DataSource> {
+        self.data_source
+    }
+}
+
+/// Logs every call, and how long it took, at debug level.
+pub struct LoggingDataSource {
+    data_source: Box<dyn DataSource>,
+}
+
+impl LoggingDataSource {
+    pub fn new(data_source: Box<dyn DataSource>) -> Self {
+        Self { data_source }
+    }
+
+    // call describes the call, and is only formatted if it gets logged
+    fn logged<T>(
+        &mut self,
+        call: impl FnOnce() -> String,
+        f: impl FnOnce(&mut dyn DataSource) -> T,
+    ) -> T {
+        let start = Instant::now();
+        let result = f(self.data_source.as_mut());
+        log::debug!("{} in {:?}", call(), start.elapsed());
+        result
+    }
+}
+
+impl DataSource for LoggingDataSource {
+    fn interval(&mut self) -> Interval {
+        self.logged(|| "interval".to_owned(), |source| source.interval())
+    }
+
+    fn fetch_info(&mut self) -> EntryInfo {
+        self.logged(|| "fetch_info".to_owned(), |source| source.fetch_info())
+    }
+
+    fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+        let call = || format!("request_tiles {entry_id} {request_interval:?}");
+        self.logged(call, |source| {
+            source.request_tiles(entry_id, request_interval)
+        })
+    }
+
+    fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+        let call = || format!("fetch_summary_tile {entry_id} {:?}", tile_id.0);
+        self.logged(call, |source| source.fetch_summary_tile(entry_id, tile_id))
+    }
+
+    fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+        let call = || format!("fetch_slot_tile {entry_id} {:?}", tile_id.0);
+        self.logged(call, |source| source.fetch_slot_tile(entry_id, tile_id))
+    }
+
+    fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+        let call = || format!("fetch_slot_meta_tile {entry_id} {:?}", tile_id.0);
+        self.logged(call, |source| {
+            source.fetch_slot_meta_tile(entry_id, tile_id)
+        })
+    }
+
+    fn fetch_clipped_slot_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Interval,
+    ) -> SlotTile {
+        let call = || {
+            format!(
+                "fetch_clipped_slot_tile {entry_id} {:?} {clip:?}",
+                tile_id.0
+            )
+        };
+        self.logged(call, |source| {
+            source.fetch_clipped_slot_tile(entry_id, tile_id, clip)
+        })
+    }
+
+    fn fetch_clipped_slot_meta_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Interval,
+    ) -> SlotMetaTile {
+        let call = || {
+            format!(
+                "fetch_clipped_slot_meta_tile {entry_id} {:?} {clip:?}",
+                tile_id.0
+            )
+        };
+        self.logged(call, |source| {
+            source.fetch_clipped_slot_meta_tile(entry_id, tile_id, clip)
+        })
+    }
+
+    fn search(&mut self, query: &str) -> Vec<SearchHit> {
+        let call = || format!("search {query:?}");
+        self.logged(call, |source| source.search(query))
+    }
+
+    fn probe(&mut self, entry_id: &EntryID, at: Timestamp) -> Option<ItemMeta> {
+        let call = || format!("probe {entry_id} {at:?}");
+        self.logged(call, |source| source.probe(entry_id, at))
+    }
+
+    fn next_task_after(&mut self, entry_id: &EntryID, t: Timestamp) -> Option<Interval> {
+        let call = || format!("next_task_after {entry_id} {t:?}");
+        self.logged(call, |source| source.next_task_after(entry_id, t))
+    }
+
+    fn prev_task_before(&mut self, entry_id: &EntryID, t: Timestamp) -> Option<Interval> {
+        let call = || format!("prev_task_before {entry_id} {t:?}");
+        self.logged(call, |source| source.prev_task_before(entry_id, t))
+    }
+
+    fn last_error(&self) -> Option<String> {
+        self.data_source.last_error()
+    }
+
+    fn get_errors(&mut self) -> Vec<DataSourceError> {
+        self.data_source.get_errors()
+    }
+
+    fn time_origin(&mut self) -> TimeOrigin {
+        self.data_source.time_origin()
+    }
+
+    fn tile_byte_estimate(&mut self, entry_id: &EntryID, tile_id: TileID) -> Option<usize> {
+        self.data_source.tile_byte_estimate(entry_id, tile_id)
+    }
+
+    fn set_summary_samples(&mut self, samples: usize) {
+        self.data_source.set_summary_samples(samples)
+    }
+
+    fn idle(&mut self) -> bool {
+        self.data_source.idle()
+    }
+}
+
+pub struct LogLayer;
+
+impl DataSourceLayer for LogLayer {
+    fn wrap(self, inner: Box<dyn DataSource>) -> Box<dyn DataSource> {
+        Box::new(LoggingDataSource::new(inner))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheLayer;
+    use crate::http::server::tests::capture_logs;
+    use crate::mock::tests::{interval, item_interval, sample, slot_id, IndexedDataSource};
+    use crate::mock::MockDataSource;
+    use crate::timestamp::Timestamp;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn lookups_are_passed_through() {
+        let mut source = LoggingDataSource::new(Box::new(IndexedDataSource));
+        let slot = IndexedDataSource::shown_id();
+        assert_eq!(source.search("").len(), 2);
+        assert!(source.probe(&slot, Timestamp(120)).is_some());
+        assert_eq!(
+            source.next_task_after(&slot, Timestamp(0)),
+            Some(item_interval(1))
+        );
+        assert_eq!(
+            source.prev_task_before(&slot, Timestamp(1000)),
+            Some(item_interval(1))
+        );
+    }
+
+    // Counts the slot tile fetches that reach it
+    struct CountingDataSource(MockDataSource, Rc<Cell<usize>>);
+
+    impl DataSource for CountingDataSource {
+        fn interval(&mut self) -> Interval {
+            self.0.interval()
+        }
+        fn fetch_info(&mut self) -> EntryInfo {
+            self.0.fetch_info()
+        }
+        fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+            self.0.request_tiles(entry_id, request_interval)
+        }
+        fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+            self.0.fetch_summary_tile(entry_id, tile_id)
+        }
+        fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+            self.1.set(self.1.get() + 1);
+            self.0.fetch_slot_tile(entry_id, tile_id)
+        }
+        fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+            self.0.fetch_slot_meta_tile(entry_id, tile_id)
+        }
+    }
+
+    #[test]
+    fn stacked_layers_all_take_effect() {
+        let first = TileID(interval());
+        let second = TileID(Interval::new(Timestamp(0), Timestamp(500)));
+        let stacked = |log_outside: bool| {
+            let fetches = Rc::new(Cell::new(0));
+            let inner = Box::new(CountingDataSource(sample(), fetches.clone()));
+            let stack = DataSourceStack::new(inner);
+            let stack = if log_outside {
+                stack.layer(CacheLayer::new(1 << 20)).layer(LogLayer)
+            } else {
+                stack.layer(LogLayer).layer(CacheLayer::new(1 << 20))
+            };
+            let mut source = stack.build();
+            let logs = capture_logs(|| {
+                for tile_id in [first, second, first] {
+                    source.fetch_slot_tile(&slot_id(), tile_id);
+                }
+            });
+            let logged: Vec<_> = logs
+                .iter()
+                .filter_map(|line| line.strip_prefix("fetch_slot_tile "))
+                .map(|line| line.split(" in ").next().unwrap().to_owned())
+                .collect();
+            (fetches.get(), logged)
+        };
+        let call = |tile_id: TileID| format!("{} {:?}", slot_id(), tile_id.0);
+
+        // The repeated fetch is a cache hit, but still logged
+        let (fetches, logged) = stacked(true);
+        assert_eq!(fetches, 2);
+        assert_eq!(logged, [call(first), call(second), call(first)]);
+
+        // With the cache outside, only the fetches that miss it are logged
+        let (fetches, logged) = stacked(false);
+        assert_eq!(fetches, 2);
+        assert_eq!(logged, [call(first), call(second)]);
+    }
+}
@@ -1,7 +1,22 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 pub mod app;
+pub mod cache;
 pub mod data;
-pub mod timestamp;
-pub mod search;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod deferred;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod disk_cache;
+pub mod export;
+pub mod file;
+pub mod filter;
 pub mod http;
+pub mod layer;
+#[cfg(any(test, feature = "testing"))]
+pub mod mock;
+pub mod multiplex;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod screenshot;
+pub mod search;
+pub mod stats;
+pub mod timestamp;
@@ -5,16 +5,55 @@ use egui::{Color32, NumExt};
 use rand::Rng;
 use std::collections::BTreeMap;
 
+use legion_prof_viewer::app::ViewState;
 use legion_prof_viewer::data::{
-    DataSource, EntryID, EntryInfo, Field, Item, ItemMeta, ItemUID, SlotMetaTile, SlotTile,
-    SummaryTile, TileID, UtilPoint,
+    stable_color, DataSource, EntryID, EntryInfo, EntryKind, Field, Item, ItemMeta, ItemUID,
+    SlotMetaTile, SlotTile, SummaryTile, TileID, UtilPoint,
 };
+use legion_prof_viewer::deferred::DeferredDataSource;
+use legion_prof_viewer::file::FileDataSource;
+use legion_prof_viewer::http::client::{connect, parse_urls};
 use legion_prof_viewer::timestamp::{Interval, Timestamp};
 
 fn main() {
+    // Profiles being served can be viewed by passing a view copied with
+    // "Copy Link to View", or just the servers, e.g.,
+    // "urls=http://localhost:8080,http://localhost:8081"
+    if let Some(query) = std::env::args().nth(1).filter(|arg| arg.contains("urls=")) {
+        let view = ViewState::from_query(&query);
+        let urls = parse_urls(view.urls.as_deref().unwrap_or_default()).unwrap_or_else(|e| {
+            eprintln!("error: {e}");
+            std::process::exit(2)
+        });
+        let data_source = DeferredDataSource::new(move || connect(&urls));
+        legion_prof_viewer::app::start(Box::new(data_source), None, view);
+        return;
+    }
+
+    // A saved profile can be viewed by passing the directory it is stored in,
+    // optionally followed by a view copied with "Copy Link to View"
+    if let Some(path) = std::env::args().nth(1) {
+        let data_source = FileDataSource::new(&path)
+            .unwrap_or_else(|e| panic!("unable to open profile {path}: {e}"));
+        let view = std::env::args()
+            .nth(2)
+            .map(|query| ViewState::from_query(&query))
+            .unwrap_or_default();
+        let data_source = DeferredDataSource::new(move || Box::new(data_source));
+        legion_prof_viewer::app::start(Box::new(data_source), None, view);
+        return;
+    }
+
+    // Fetching runs off the UI thread, so the UI stays responsive however
+    // slow the source is
     legion_prof_viewer::app::start(
-        Box::<RandomDataSource>::default(),
-        Some(Box::<RandomDataSource>::default()),
+        Box::new(DeferredDataSource::new(|| {
+            Box::<RandomDataSource>::default()
+        })),
+        Some(Box::new(DeferredDataSource::new(|| {
+            Box::<RandomDataSource>::default()
+        }))),
+        ViewState::default(),
     );
 }
 
@@ -113,16 +152,8 @@ impl RandomDataSource {
                     let start = self.interval().lerp((i as f32 + 0.05) / (N as f32));
                     let stop = self.interval().lerp((i as f32 + 0.95) / (N as f32));
 
-                    let color = match (row * N + i) % 7 {
-                        0 => Color32::BLUE,
-                        1 => Color32::GREEN,
-                        2 => Color32::RED,
-                        3 => Color32::YELLOW,
-                        4 => Color32::KHAKI,
-                        5 => Color32::DARK_GREEN,
-                        6 => Color32::DARK_BLUE,
-                        _ => Color32::WHITE,
-                    };
+                    let title = format!("Test Task {}", (row * N + i) % 7);
+                    let color = stable_color(&title);
 
                     let item_uid = self.item_uid_generator.next();
                     row_items.push(Item {
@@ -132,7 +163,7 @@ impl RandomDataSource {
                     });
                     row_item_metas.push(ItemMeta {
                         item_uid,
-                        title: "Test Item".to_owned(),
+                        title,
                         fields: vec![
                             (
                                 "Interval".to_owned(),
@@ -172,13 +203,13 @@ impl DataSource for RandomDataSource {
         }
 
         let kinds = vec![
-            "CPU".to_string(),
-            "GPU".to_string(),
-            "OMP".to_string(),
-            "Py".to_string(),
-            "Util".to_string(),
-            "Chan".to_string(),
-            "SysMem".to_string(),
+            ("CPU".to_string(), EntryKind::Processor),
+            ("GPU".to_string(), EntryKind::Processor),
+            ("OMP".to_string(), EntryKind::Processor),
+            ("Py".to_string(), EntryKind::Processor),
+            ("Util".to_string(), EntryKind::Processor),
+            ("Chan".to_string(), EntryKind::Channel),
+            ("SysMem".to_string(), EntryKind::Memory),
         ];
 
         const NODES: i32 = 8192;
@@ -187,7 +218,7 @@ impl DataSource for RandomDataSource {
         for node in 0..NODES {
             let mut kind_slots = Vec::new();
             let colors = &[Color32::BLUE, Color32::GREEN, Color32::RED, Color32::YELLOW];
-            for (i, kind) in kinds.iter().enumerate() {
+            for (i, (kind, entry_kind)) in kinds.iter().enumerate() {
                 let color = colors[i % colors.len()];
                 let mut proc_slots = Vec::new();
                 for proc in 0..PROCS {
@@ -200,6 +231,7 @@ impl DataSource for RandomDataSource {
                         ),
                         long_name: format!("Node {node} {kind} {proc}"),
                         max_rows: rows,
+                        kind: *entry_kind,
                     });
                 }
                 kind_slots.push(EntryInfo::Panel {
@@ -226,17 +258,13 @@ impl DataSource for RandomDataSource {
     }
 
     fn request_tiles(&mut self, _entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
-        let duration = request_interval.duration_ns();
-
-        const TILES: i64 = 3;
+        const TILES: usize = 3;
 
-        let mut tiles = Vec::new();
-        for i in 0..TILES {
-            let start = Timestamp(i * duration / TILES + request_interval.start.0);
-            let stop = Timestamp((i + 1) * duration / TILES + request_interval.start.0);
-            tiles.push(TileID(Interval::new(start, stop)));
-        }
-        tiles
+        request_interval
+            .split_evenly(TILES)
+            .into_iter()
+            .map(TileID)
+            .collect()
     }
 
     fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
@@ -293,7 +321,7 @@ impl DataSource for RandomDataSource {
                 // sliced to fit
                 if tile_id.0.overlaps(item.interval) {
                     let mut new_item = item.clone();
-                    new_item.interval = new_item.interval.intersection(tile_id.0);
+                    new_item.interval = new_item.interval.clamp(tile_id.0);
                     slot_row.push(new_item);
                 }
             }
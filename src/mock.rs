@@ -0,0 +1,374 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::data::{
+    tiles_overlapping, DataSource, EntryID, EntryInfo, SlotMetaTile, SlotTile, SummaryTile, TileID,
+    TimeOrigin,
+};
+use crate::timestamp::Interval;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockCall {
+    Interval,
+    FetchInfo,
+    RequestTiles(EntryID, Interval),
+    FetchSummaryTile(EntryID, TileID),
+    FetchSlotTile(EntryID, TileID),
+    FetchSlotMetaTile(EntryID, TileID),
+}
+
+/// An in-memory DataSource for tests. It serves exactly the tiles it was
+/// given and records every call made to it, so that wrappers (e.g.,
+/// CachingDataSource) can be checked for what they actually fetch.
+///
+/// request_tiles returns every tile given for the entry that overlaps the
+/// requested interval. Fetching a tile that was never given returns an
+/// empty one.
+///
+/// ```
+/// use legion_prof_viewer::data::{DataSource, EntryID, EntryInfo, SlotTile, TileID};
+/// use legion_prof_viewer::mock::{MockCall, MockDataSource};
+/// use legion_prof_viewer::timestamp::{Interval, Timestamp};
+///
+/// let info = EntryInfo::Panel {
+///     short_name: "root".to_owned(),
+///     long_name: "root".to_owned(),
+///     summary: None,
+///     slots: Vec::new(),
+/// };
+/// let interval = Interval::new(Timestamp(0), Timestamp(1000));
+/// let slot = EntryID::root().child(0);
+/// let tile_id = TileID(interval);
+/// let mut source = MockDataSource::new(info, interval).with_slot_tile(
+///     slot.clone(),
+///     SlotTile {
+///         tile_id,
+///         items: vec![Vec::new()],
+///     },
+/// );
+///
+/// assert_eq!(source.request_tiles(&slot, interval), vec![tile_id]);
+/// assert_eq!(source.fetch_slot_tile(&slot, tile_id).items.len(), 1);
+/// assert_eq!(
+///     source.calls,
+///     vec![
+///         MockCall::RequestTiles(slot.clone(), interval),
+///         MockCall::FetchSlotTile(slot, tile_id),
+///     ]
+/// );
+/// ```
+pub struct MockDataSource {
+    pub info: EntryInfo,
+    pub interval: Interval,
+    pub summary_tiles: BTreeMap<(EntryID, TileID), SummaryTile>,
+    pub slot_tiles: BTreeMap<(EntryID, TileID), SlotTile>,
+    pub slot_meta_tiles: BTreeMap<(EntryID, TileID), SlotMetaTile>,
+    pub time_origin: TimeOrigin,
+    pub calls: Vec<MockCall>,
+}
+
+impl MockDataSource {
+    pub fn new(info: EntryInfo, interval: Interval) -> Self {
+        Self {
+            info,
+            interval,
+            summary_tiles: BTreeMap::new(),
+            slot_tiles: BTreeMap::new(),
+            slot_meta_tiles: BTreeMap::new(),
+            time_origin: TimeOrigin::default(),
+            calls: Vec::new(),
+        }
+    }
+
+    pub fn with_summary_tile(mut self, entry_id: EntryID, tile: SummaryTile) -> Self {
+        self.summary_tiles.insert((entry_id, tile.tile_id), tile);
+        self
+    }
+
+    pub fn with_slot_tile(mut self, entry_id: EntryID, tile: SlotTile) -> Self {
+        self.slot_tiles.insert((entry_id, tile.tile_id), tile);
+        self
+    }
+
+    pub fn with_slot_meta_tile(mut self, entry_id: EntryID, tile: SlotMetaTile) -> Self {
+        self.slot_meta_tiles.insert((entry_id, tile.tile_id), tile);
+        self
+    }
+
+    pub fn with_time_origin(mut self, time_origin: TimeOrigin) -> Self {
+        self.time_origin = time_origin;
+        self
+    }
+}
+
+impl DataSource for MockDataSource {
+    fn interval(&mut self) -> Interval {
+        self.calls.push(MockCall::Interval);
+        self.interval
+    }
+
+    fn fetch_info(&mut self) -> EntryInfo {
+        self.calls.push(MockCall::FetchInfo);
+        self.info.clone()
+    }
+
+    fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+        self.calls
+            .push(MockCall::RequestTiles(entry_id.clone(), request_interval));
+        let keys = self
+            .summary_tiles
+            .keys()
+            .chain(self.slot_tiles.keys())
+            .chain(self.slot_meta_tiles.keys());
+        let tiles: BTreeSet<_> = keys
+            .filter(|(id, _)| id == entry_id)
+            .map(|(_, tile_id)| *tile_id)
+            .collect();
+        tiles_overlapping(&tiles.into_iter().collect::<Vec<_>>(), request_interval)
+    }
+
+    fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+        self.calls
+            .push(MockCall::FetchSummaryTile(entry_id.clone(), tile_id));
+        self.summary_tiles
+            .get(&(entry_id.clone(), tile_id))
+            .cloned()
+            .unwrap_or_else(|| SummaryTile {
+                tile_id,
+                utilization: Vec::new(),
+            })
+    }
+
+    fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+        self.calls
+            .push(MockCall::FetchSlotTile(entry_id.clone(), tile_id));
+        self.slot_tiles
+            .get(&(entry_id.clone(), tile_id))
+            .cloned()
+            .unwrap_or_else(|| SlotTile {
+                tile_id,
+                items: Vec::new(),
+            })
+    }
+
+    fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+        self.calls
+            .push(MockCall::FetchSlotMetaTile(entry_id.clone(), tile_id));
+        self.slot_meta_tiles
+            .get(&(entry_id.clone(), tile_id))
+            .cloned()
+            .unwrap_or_else(|| SlotMetaTile {
+                tile_id,
+                items: Vec::new(),
+            })
+    }
+
+    fn time_origin(&mut self) -> TimeOrigin {
+        self.time_origin.clone()
+    }
+
+    // The size of the tile as JSON. Not recorded in calls, since nothing is
+    // fetched.
+    fn tile_byte_estimate(&mut self, entry_id: &EntryID, tile_id: TileID) -> Option<usize> {
+        let key = (entry_id.clone(), tile_id);
+        let body = if let Some(tile) = self.summary_tiles.get(&key) {
+            serde_json::to_vec(tile)
+        } else {
+            serde_json::to_vec(self.slot_tiles.get(&key)?)
+        };
+        body.ok().map(|body| body.len())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::data::{Field, Item, ItemMeta, ItemUID, SearchHit, UtilPoint};
+    use crate::timestamp::Timestamp;
+
+    pub(crate) const ITEMS: i64 = 10;
+
+    // root -> node 0 -> kind 0, which has a summary and one slot
+    pub(crate) fn slot_id() -> EntryID {
+        EntryID::root().child(0).child(0).child(0)
+    }
+
+    pub(crate) fn summary_id() -> EntryID {
+        EntryID::root().child(0).child(0).summary()
+    }
+
+    pub(crate) fn interval() -> Interval {
+        Interval::new(Timestamp(0), Timestamp(ITEMS * 100))
+    }
+
+    pub(crate) fn info() -> EntryInfo {
+        let slot = EntryInfo::Slot {
+            short_name: "c0".to_owned(),
+            long_name: "CPU 0".to_owned(),
+            max_rows: 1,
+            kind: Default::default(),
+        };
+        let kind = EntryInfo::Panel {
+            short_name: "cpu".to_owned(),
+            long_name: "CPU".to_owned(),
+            summary: Some(Box::new(EntryInfo::Summary {
+                color: egui::Color32::RED,
+            })),
+            slots: vec![slot],
+        };
+        let node = EntryInfo::Panel {
+            short_name: "n0".to_owned(),
+            long_name: "Node 0".to_owned(),
+            summary: None,
+            slots: vec![kind],
+        };
+        EntryInfo::Panel {
+            short_name: "root".to_owned(),
+            long_name: "root".to_owned(),
+            summary: None,
+            slots: vec![node],
+        }
+    }
+
+    // Item i runs from 100i to 100i + 50 ns and is titled "Task i"
+    pub(crate) fn item_interval(i: i64) -> Interval {
+        Interval::new(Timestamp(i * 100), Timestamp(i * 100 + 50))
+    }
+
+    // A single tile covering the whole profile for each entry
+    pub(crate) fn sample() -> MockDataSource {
+        let tile_id = TileID(interval());
+        let items = (0..ITEMS)
+            .map(|i| Item {
+                item_uid: ItemUID(i as u64),
+                interval: item_interval(i),
+                color: egui::Color32::RED,
+            })
+            .collect();
+        let metas = (0..ITEMS)
+            .map(|i| ItemMeta {
+                item_uid: ItemUID(i as u64),
+                title: format!("Task {i}"),
+                fields: vec![("Interval".to_owned(), Field::Interval(item_interval(i)))],
+            })
+            .collect();
+        let utilization = (0..=ITEMS)
+            .map(|i| UtilPoint {
+                time: Timestamp(i * 100),
+                util: (i % 2) as f32,
+            })
+            .collect();
+        MockDataSource::new(info(), interval())
+            .with_summary_tile(
+                summary_id(),
+                SummaryTile {
+                    tile_id,
+                    utilization,
+                },
+            )
+            .with_slot_tile(
+                slot_id(),
+                SlotTile {
+                    tile_id,
+                    items: vec![items],
+                },
+            )
+            .with_slot_meta_tile(
+                slot_id(),
+                SlotMetaTile {
+                    tile_id,
+                    items: vec![metas],
+                },
+            )
+    }
+
+    // Two slots, c0 and c1, under the usual kind panel. It answers lookups
+    // without any tiles, so that wrappers can be checked for forwarding
+    // them rather than falling back to the defaults. Search finds item i in
+    // slot ci, but otherwise only c1 has a task, item_interval(1).
+    pub(crate) struct IndexedDataSource;
+
+    impl IndexedDataSource {
+        pub(crate) fn shown_id() -> EntryID {
+            EntryID::root().child(0).child(0).child(1)
+        }
+
+        fn task(entry_id: &EntryID) -> Option<Interval> {
+            (*entry_id == Self::shown_id()).then(|| item_interval(1))
+        }
+    }
+
+    impl DataSource for IndexedDataSource {
+        fn interval(&mut self) -> Interval {
+            interval()
+        }
+
+        fn fetch_info(&mut self) -> EntryInfo {
+            let mut info = info();
+            if let EntryInfo::Panel { slots, .. } = &mut info {
+                if let EntryInfo::Panel { slots, .. } = &mut slots[0] {
+                    if let EntryInfo::Panel { slots, .. } = &mut slots[0] {
+                        let mut slot = slots[0].clone();
+                        if let EntryInfo::Slot { short_name, .. } = &mut slot {
+                            *short_name = "c1".to_owned();
+                        }
+                        slots.push(slot);
+                    }
+                }
+            }
+            info
+        }
+
+        fn request_tiles(&mut self, _: &EntryID, _: Interval) -> Vec<TileID> {
+            Vec::new()
+        }
+
+        fn fetch_summary_tile(&mut self, _: &EntryID, tile_id: TileID) -> SummaryTile {
+            SummaryTile {
+                tile_id,
+                utilization: Vec::new(),
+            }
+        }
+
+        fn fetch_slot_tile(&mut self, _: &EntryID, tile_id: TileID) -> SlotTile {
+            SlotTile {
+                tile_id,
+                items: Vec::new(),
+            }
+        }
+
+        fn fetch_slot_meta_tile(&mut self, _: &EntryID, tile_id: TileID) -> SlotMetaTile {
+            SlotMetaTile {
+                tile_id,
+                items: Vec::new(),
+            }
+        }
+
+        fn search(&mut self, _: &str) -> Vec<SearchHit> {
+            let kind = EntryID::root().child(0).child(0);
+            (0..2)
+                .map(|i| SearchHit {
+                    entry_id: kind.child(i),
+                    interval: Some(item_interval(i as i64)),
+                })
+                .collect()
+        }
+
+        // Titled after the slot asked for
+        fn probe(&mut self, entry_id: &EntryID, at: Timestamp) -> Option<ItemMeta> {
+            Self::task(entry_id)?;
+            Some(ItemMeta {
+                item_uid: ItemUID(at.0 as u64),
+                title: entry_id.to_string(),
+                fields: Vec::new(),
+            })
+        }
+
+        fn next_task_after(&mut self, entry_id: &EntryID, _: Timestamp) -> Option<Interval> {
+            Self::task(entry_id)
+        }
+
+        fn prev_task_before(&mut self, entry_id: &EntryID, _: Timestamp) -> Option<Interval> {
+            Self::task(entry_id)
+        }
+    }
+}
@@ -0,0 +1,437 @@
+use crate::data::{
+    DataSource, DataSourceError, EntryID, EntryIndex, EntryInfo, Field, SlotMetaTile, SlotTile,
+    SummaryTile, TileID, TimeOrigin,
+};
+use crate::timestamp::{Interval, Timestamp};
+
+/// Presents several DataSources as one, e.g., to compare two runs side by
+/// side.
+///
+/// The top-level entries (i.e., nodes) of all sources are concatenated under
+/// a single root. This keeps the depth of the entry hierarchy unchanged,
+/// which the app relies on. The first index of an EntryID determines which
+/// source owns it: source k owns the indices starting at the total number of
+/// nodes in sources 0..k.
+///
+/// If every source knows the wall-clock time of its origin, sources are
+/// rebased onto the earliest origin so that their timelines line up.
+pub struct MultiplexDataSource {
+    data_sources: Vec<Box<dyn DataSource>>,
+    // First top-level index owned by each source
+    offsets: Option<Vec<u64>>,
+    // Added to each source's timestamps to get ours
+    shifts: Option<Vec<Timestamp>>,
+    origin: TimeOrigin,
+}
+
+impl MultiplexDataSource {
+    pub fn new(data_sources: Vec<Box<dyn DataSource>>) -> Self {
+        Self {
+            data_sources,
+            offsets: None,
+            shifts: None,
+            origin: TimeOrigin::default(),
+        }
+    }
+
+    fn shifts(&mut self) -> &Vec<Timestamp> {
+        if self.shifts.is_none() {
+            let origins: Vec<_> = self
+                .data_sources
+                .iter_mut()
+                .map(|data_source| data_source.time_origin())
+                .collect();
+            let wall_clocks: Option<Vec<_>> =
+                origins.iter().map(|origin| origin.wall_clock).collect();
+            let common = wall_clocks
+                .as_ref()
+                .and_then(|wall_clocks| wall_clocks.iter().copied().min());
+            let shifts = match (wall_clocks, common) {
+                (Some(wall_clocks), Some(common)) => {
+                    let first = wall_clocks.iter().position(|w| *w == common).unwrap();
+                    self.origin = origins[first].clone();
+                    wall_clocks.into_iter().map(|w| w - common).collect()
+                }
+                // Without wall clocks there's nothing to line up, so leave
+                // every source where it is
+                _ => {
+                    if let Some(origin) = origins.first() {
+                        if origins.iter().all(|o| o == origin) {
+                            self.origin = origin.clone();
+                        }
+                    }
+                    vec![Timestamp(0); self.data_sources.len()]
+                }
+            };
+            self.shifts = Some(shifts);
+        }
+        self.shifts.as_ref().unwrap()
+    }
+
+    fn offsets(&mut self) -> &Vec<u64> {
+        if self.offsets.is_none() {
+            let mut offsets = Vec::with_capacity(self.data_sources.len());
+            let mut total = 0;
+            for data_source in &mut self.data_sources {
+                offsets.push(total);
+                total += data_source.fetch_info().nodes();
+            }
+            self.offsets = Some(offsets);
+        }
+        self.offsets.as_ref().unwrap()
+    }
+
+    // Find the source owning entry_id, and the EntryID as that source knows it
+    fn route(&mut self, entry_id: &EntryID) -> Option<(usize, EntryID)> {
+        let first = entry_id.slot_index(0)?;
+        let offsets = self.offsets();
+        let source = offsets.iter().rposition(|offset| *offset <= first)?;
+        let local_id = rebase(entry_id, first - offsets[source]);
+        Some((source, local_id))
+    }
+
+    // The shift for source, and tile_id as that source knows it
+    fn local_tile(&mut self, source: usize, tile_id: TileID) -> (Timestamp, TileID) {
+        let shift = self.shifts()[source];
+        (shift, TileID(tile_id.0 - shift))
+    }
+}
+
+// Move a tile fetched from a source by shift, under the tile_id it was
+// asked for as
+fn shift_slot_tile(mut tile: SlotTile, tile_id: TileID, shift: Timestamp) -> SlotTile {
+    tile.tile_id = tile_id;
+    for item in tile.items.iter_mut().flatten() {
+        item.interval = item.interval + shift;
+    }
+    tile
+}
+
+fn shift_slot_meta_tile(mut tile: SlotMetaTile, tile_id: TileID, shift: Timestamp) -> SlotMetaTile {
+    tile.tile_id = tile_id;
+    for item in tile.items.iter_mut().flatten() {
+        for (_, field) in &mut item.fields {
+            if let Field::Interval(interval) = field {
+                *interval = *interval + shift;
+            }
+        }
+    }
+    tile
+}
+
+// Replace the first index of entry_id, keeping the rest of the path
+fn rebase(entry_id: &EntryID, first: u64) -> EntryID {
+    let mut result = EntryID::root().child(first);
+    for level in 1..entry_id.level() {
+        result = match entry_id.index(level).unwrap() {
+            EntryIndex::Summary => result.summary(),
+            EntryIndex::Slot(index) => result.child(index),
+        };
+    }
+    result
+}
+
+impl DataSource for MultiplexDataSource {
+    fn interval(&mut self) -> Interval {
+        let shifts = self.shifts().clone();
+        self.data_sources
+            .iter_mut()
+            .zip(shifts)
+            .map(|(data_source, shift)| data_source.interval() + shift)
+            .reduce(Interval::union)
+            .unwrap_or_default()
+    }
+
+    fn fetch_info(&mut self) -> EntryInfo {
+        let mut slots = Vec::new();
+        for (i, data_source) in self.data_sources.iter_mut().enumerate() {
+            if let EntryInfo::Panel { slots: nodes, .. } = data_source.fetch_info() {
+                for mut node in nodes {
+                    if let EntryInfo::Panel { long_name, .. } = &mut node {
+                        *long_name = format!("Source {i} {long_name}");
+                    }
+                    slots.push(node);
+                }
+            }
+        }
+        EntryInfo::Panel {
+            short_name: "root".to_owned(),
+            long_name: "root".to_owned(),
+            summary: None,
+            slots,
+        }
+    }
+
+    fn request_tiles(&mut self, entry_id: &EntryID, request_interval: Interval) -> Vec<TileID> {
+        match self.route(entry_id) {
+            Some((source, local_id)) => {
+                let shift = self.shifts()[source];
+                self.data_sources[source]
+                    .request_tiles(&local_id, request_interval - shift)
+                    .into_iter()
+                    .map(|tile_id| TileID(tile_id.0 + shift))
+                    .collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    fn fetch_summary_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SummaryTile {
+        match self.route(entry_id) {
+            Some((source, local_id)) => {
+                let (shift, local_tile) = self.local_tile(source, tile_id);
+                let mut tile = self.data_sources[source].fetch_summary_tile(&local_id, local_tile);
+                tile.tile_id = tile_id;
+                for point in &mut tile.utilization {
+                    point.time += shift;
+                }
+                tile
+            }
+            None => SummaryTile {
+                tile_id,
+                utilization: Vec::new(),
+            },
+        }
+    }
+
+    fn fetch_slot_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotTile {
+        match self.route(entry_id) {
+            Some((source, local_id)) => {
+                let (shift, local_tile) = self.local_tile(source, tile_id);
+                let tile = self.data_sources[source].fetch_slot_tile(&local_id, local_tile);
+                shift_slot_tile(tile, tile_id, shift)
+            }
+            None => SlotTile {
+                tile_id,
+                items: Vec::new(),
+            },
+        }
+    }
+
+    fn fetch_slot_meta_tile(&mut self, entry_id: &EntryID, tile_id: TileID) -> SlotMetaTile {
+        match self.route(entry_id) {
+            Some((source, local_id)) => {
+                let (shift, local_tile) = self.local_tile(source, tile_id);
+                let tile = self.data_sources[source].fetch_slot_meta_tile(&local_id, local_tile);
+                shift_slot_meta_tile(tile, tile_id, shift)
+            }
+            None => SlotMetaTile {
+                tile_id,
+                items: Vec::new(),
+            },
+        }
+    }
+
+    fn fetch_clipped_slot_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Interval,
+    ) -> SlotTile {
+        match self.route(entry_id) {
+            Some((source, local_id)) => {
+                let (shift, local_tile) = self.local_tile(source, tile_id);
+                let tile = self.data_sources[source].fetch_clipped_slot_tile(
+                    &local_id,
+                    local_tile,
+                    clip - shift,
+                );
+                shift_slot_tile(tile, tile_id, shift)
+            }
+            None => SlotTile {
+                tile_id,
+                items: Vec::new(),
+            },
+        }
+    }
+
+    fn fetch_clipped_slot_meta_tile(
+        &mut self,
+        entry_id: &EntryID,
+        tile_id: TileID,
+        clip: Interval,
+    ) -> SlotMetaTile {
+        match self.route(entry_id) {
+            Some((source, local_id)) => {
+                let (shift, local_tile) = self.local_tile(source, tile_id);
+                let tile = self.data_sources[source].fetch_clipped_slot_meta_tile(
+                    &local_id,
+                    local_tile,
+                    clip - shift,
+                );
+                shift_slot_meta_tile(tile, tile_id, shift)
+            }
+            None => SlotMetaTile {
+                tile_id,
+                items: Vec::new(),
+            },
+        }
+    }
+
+    fn last_error(&self) -> Option<String> {
+        self.data_sources
+            .iter()
+            .find_map(|data_source| data_source.last_error())
+    }
+
+    fn get_errors(&mut self) -> Vec<DataSourceError> {
+        let offsets = self.offsets().clone();
+        let shifts = self.shifts().clone();
+        let mut result = Vec::new();
+        for ((data_source, offset), shift) in self.data_sources.iter_mut().zip(offsets).zip(shifts)
+        {
+            for mut error in data_source.get_errors() {
+                // Translate back to the EntryID and tile the caller asked for
+                if let Some(first) = error.entry_id.slot_index(0) {
+                    error.entry_id = rebase(&error.entry_id, first + offset);
+                }
+                error.tile_id = TileID(error.tile_id.0 + shift);
+                result.push(error);
+            }
+        }
+        result
+    }
+
+    fn time_origin(&mut self) -> TimeOrigin {
+        self.shifts();
+        self.origin.clone()
+    }
+
+    fn tile_byte_estimate(&mut self, entry_id: &EntryID, tile_id: TileID) -> Option<usize> {
+        let (source, local_id) = self.route(entry_id)?;
+        let (_, local_tile) = self.local_tile(source, tile_id);
+        self.data_sources[source].tile_byte_estimate(&local_id, local_tile)
+    }
+
+    fn set_summary_samples(&mut self, samples: usize) {
+        for data_source in &mut self.data_sources {
+            data_source.set_summary_samples(samples);
+        }
+    }
+
+    fn idle(&mut self) -> bool {
+        let mut more = false;
+        for data_source in &mut self.data_sources {
+            more |= data_source.idle();
+        }
+        more
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Item, ItemUID};
+    use crate::mock::tests::{info, interval, sample};
+    use crate::mock::MockDataSource;
+
+    fn with_wall_clock(wall_clock: i64) -> Box<dyn DataSource> {
+        Box::new(sample().with_time_origin(TimeOrigin {
+            epoch: "process start".to_owned(),
+            wall_clock: Some(Timestamp(wall_clock)),
+        }))
+    }
+
+    fn slot_id(node: u64) -> EntryID {
+        EntryID::root().child(node).child(0).child(0)
+    }
+
+    #[test]
+    fn sources_are_rebased_onto_earliest_origin() {
+        let mut source =
+            MultiplexDataSource::new(vec![with_wall_clock(5_300), with_wall_clock(5_000)]);
+        assert_eq!(source.time_origin().wall_clock, Some(Timestamp(5_000)));
+        assert_eq!(
+            source.interval(),
+            Interval::new(Timestamp(0), Timestamp(1_300))
+        );
+        assert_eq!(source.fetch_info().nodes(), 2);
+
+        let shifted = TileID(interval() + Timestamp(300));
+        assert_eq!(source.request_tiles(&slot_id(0), shifted.0), vec![shifted]);
+        assert_eq!(
+            source.request_tiles(&slot_id(1), interval()),
+            vec![TileID(interval())]
+        );
+
+        let tile = source.fetch_slot_tile(&slot_id(0), shifted);
+        assert_eq!(tile.tile_id, shifted);
+        assert_eq!(
+            tile.items[0][0].interval,
+            Interval::new(Timestamp(300), Timestamp(350))
+        );
+        let tile = source.fetch_slot_tile(&slot_id(1), TileID(interval()));
+        assert_eq!(
+            tile.items[0][0].interval,
+            Interval::new(Timestamp(0), Timestamp(50))
+        );
+    }
+
+    #[test]
+    fn meta_intervals_are_rebased() {
+        let mut source =
+            MultiplexDataSource::new(vec![with_wall_clock(5_300), with_wall_clock(5_000)]);
+        let shifted = TileID(interval() + Timestamp(300));
+        let tile = source.fetch_slot_tile(&slot_id(0), shifted);
+        let metas = source.fetch_slot_meta_tile(&slot_id(0), shifted);
+        assert_eq!(metas.tile_id, shifted);
+        for (item, meta) in tile.items[0].iter().zip(&metas.items[0]) {
+            match meta.fields[0].1 {
+                Field::Interval(interval) => assert_eq!(interval, item.interval),
+                _ => panic!("expected an interval"),
+            }
+        }
+    }
+
+    #[test]
+    fn sources_without_wall_clocks_stay_put() {
+        let mut source = MultiplexDataSource::new(vec![Box::new(sample()), with_wall_clock(5_000)]);
+        assert_eq!(source.time_origin().wall_clock, None);
+        assert_eq!(source.interval(), interval());
+    }
+
+    #[test]
+    fn entries_and_tiles_come_from_their_source() {
+        let wide = Interval::new(Timestamp(0), Timestamp(2_000));
+        let tile_id = TileID(wide);
+        let item = Item {
+            item_uid: ItemUID(42),
+            interval: wide,
+            color: egui::Color32::BLUE,
+        };
+        let other = MockDataSource::new(info(), wide).with_slot_tile(
+            slot_id(0),
+            SlotTile {
+                tile_id,
+                items: vec![vec![item]],
+            },
+        );
+        let mut source = MultiplexDataSource::new(vec![Box::new(sample()), Box::new(other)]);
+        assert_eq!(source.interval(), wide);
+
+        let names: Vec<_> = match source.fetch_info() {
+            EntryInfo::Panel { slots, .. } => slots
+                .into_iter()
+                .map(|node| match node {
+                    EntryInfo::Panel { long_name, .. } => long_name,
+                    _ => panic!("expected a node"),
+                })
+                .collect(),
+            _ => panic!("expected a panel"),
+        };
+        assert_eq!(names, vec!["Source 0 Node 0", "Source 1 Node 0"]);
+
+        let item_uids = |tile: SlotTile| -> Vec<u64> {
+            tile.items[0].iter().map(|item| item.item_uid.0).collect()
+        };
+        let tile = source.fetch_slot_tile(&slot_id(0), TileID(interval()));
+        assert_eq!(item_uids(tile), (0..10).collect::<Vec<_>>());
+        let tile = source.fetch_slot_tile(&slot_id(1), tile_id);
+        assert_eq!(item_uids(tile), vec![42]);
+        // Past the last source
+        assert!(source
+            .fetch_slot_tile(&slot_id(2), tile_id)
+            .items
+            .is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+use egui::epaint::{ImageData, Primitive, Vertex};
+use egui::{Color32, Pos2, Rect, TextureId, Vec2};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+struct Texture {
+    size: [usize; 2],
+    pixels: Vec<Color32>,
+}
+
+impl Texture {
+    fn sample(&self, uv: Pos2) -> Color32 {
+        let x = ((uv.x * self.size[0] as f32) as usize).min(self.size[0] - 1);
+        let y = ((uv.y * self.size[1] as f32) as usize).min(self.size[1] - 1);
+        self.pixels[y * self.size[0] + x]
+    }
+}
+
+// Draws run_ui off-screen into a size[0] x size[1] pixel PNG at path. The
+// style and memory (e.g., scroll positions) of ctx are reused, and the UI is
+// scaled so that it is laid out as wide as ctx's screen.
+pub fn export_png(
+    ctx: &egui::Context,
+    path: &Path,
+    size: [u32; 2],
+    mut run_ui: impl FnMut(&egui::Context),
+) -> io::Result<()> {
+    let image = render(ctx, size, &mut run_ui);
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, size[0], size[1]);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::from)?;
+    let data: Vec<u8> = image
+        .iter()
+        .flat_map(|color| color.to_srgba_unmultiplied())
+        .collect();
+    writer.write_image_data(&data).map_err(io::Error::from)?;
+    writer.finish().map_err(io::Error::from)
+}
+
+fn render(
+    ctx: &egui::Context,
+    size: [u32; 2],
+    run_ui: &mut impl FnMut(&egui::Context),
+) -> Vec<Color32> {
+    let pixels_per_point = size[0] as f32 / ctx.screen_rect().width().max(1.0);
+    let screen_rect = Rect::from_min_size(
+        Pos2::ZERO,
+        Vec2::new(size[0] as f32, size[1] as f32) / pixels_per_point,
+    );
+
+    let offscreen = egui::Context::default();
+    offscreen.set_style(ctx.style());
+    offscreen.memory_mut(|m| *m = ctx.memory(|m| m.clone()));
+
+    // Some widgets only size themselves correctly on the second pass, so
+    // run twice, keeping the texture updates from both
+    let mut textures = BTreeMap::new();
+    let mut output = None;
+    for _ in 0..2 {
+        let input = egui::RawInput {
+            screen_rect: Some(screen_rect),
+            pixels_per_point: Some(pixels_per_point),
+            ..Default::default()
+        };
+        let full_output = offscreen.run(input, |ctx| run_ui(ctx));
+        update_textures(&mut textures, full_output.textures_delta.set);
+        output = Some(full_output.shapes);
+    }
+    let primitives = offscreen.tessellate(output.unwrap());
+
+    let width = size[0] as usize;
+    let height = size[1] as usize;
+    let mut image = vec![ctx.style().visuals.panel_fill; width * height];
+    for clipped in primitives {
+        let mesh = match clipped.primitive {
+            Primitive::Mesh(mesh) => mesh,
+            // Paint callbacks need a GPU
+            Primitive::Callback(_) => continue,
+        };
+        let texture = match textures.get(&mesh.texture_id) {
+            Some(texture) => texture,
+            None => continue,
+        };
+        let clip = Rect::from_min_max(
+            (clipped.clip_rect.min.to_vec2() * pixels_per_point).to_pos2(),
+            (clipped.clip_rect.max.to_vec2() * pixels_per_point).to_pos2(),
+        )
+        .intersect(Rect::from_min_size(
+            Pos2::ZERO,
+            Vec2::new(width as f32, height as f32),
+        ));
+        for triangle in mesh.indices.chunks_exact(3) {
+            let vertices = [
+                &mesh.vertices[triangle[0] as usize],
+                &mesh.vertices[triangle[1] as usize],
+                &mesh.vertices[triangle[2] as usize],
+            ];
+            fill_triangle(&mut image, width, clip, pixels_per_point, vertices, texture);
+        }
+    }
+    image
+}
+
+fn update_textures(
+    textures: &mut BTreeMap<TextureId, Texture>,
+    deltas: Vec<(TextureId, egui::epaint::ImageDelta)>,
+) {
+    for (id, delta) in deltas {
+        let (size, pixels) = match &delta.image {
+            ImageData::Color(image) => (image.size, image.pixels.clone()),
+            ImageData::Font(image) => (image.size, image.srgba_pixels(None).collect()),
+        };
+        match delta.pos {
+            None => {
+                textures.insert(id, Texture { size, pixels });
+            }
+            Some([x0, y0]) => {
+                let texture = textures
+                    .get_mut(&id)
+                    .expect("partial update of unknown texture");
+                for y in 0..size[1] {
+                    let start = (y0 + y) * texture.size[0] + x0;
+                    texture.pixels[start..start + size[0]]
+                        .copy_from_slice(&pixels[y * size[0]..(y + 1) * size[0]]);
+                }
+            }
+        }
+    }
+}
+
+// Rasterizes one triangle with premultiplied alpha blending, sampling each
+// pixel at its center
+fn fill_triangle(
+    image: &mut [Color32],
+    width: usize,
+    clip: Rect,
+    pixels_per_point: f32,
+    vertices: [&Vertex; 3],
+    texture: &Texture,
+) {
+    let [a, b, c] = vertices.map(|v| v.pos.to_vec2() * pixels_per_point);
+    let area = (b - a).x * (c - a).y - (b - a).y * (c - a).x;
+    if area == 0.0 {
+        return;
+    }
+    let min = a.min(b).min(c).max(clip.min.to_vec2());
+    let max = a.max(b).max(c).min(clip.max.to_vec2());
+    if min.x >= max.x || min.y >= max.y {
+        return;
+    }
+    for y in min.y.floor() as usize..max.y.ceil() as usize {
+        for x in min.x.floor() as usize..max.x.ceil() as usize {
+            let p = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+            // Barycentric coordinates, which are all non-negative inside
+            let wa = ((b - p).x * (c - p).y - (b - p).y * (c - p).x) / area;
+            let wb = ((c - p).x * (a - p).y - (c - p).y * (a - p).x) / area;
+            let wc = 1.0 - wa - wb;
+            if wa < 0.0 || wb < 0.0 || wc < 0.0 {
+                continue;
+            }
+            let [va, vb, vc] = vertices;
+            let uv = (va.uv.to_vec2() * wa + vb.uv.to_vec2() * wb + vc.uv.to_vec2() * wc).to_pos2();
+            let texel = texture.sample(uv);
+            let mut src = [0.0; 4];
+            for (i, channel) in src.iter_mut().enumerate() {
+                let vertex =
+                    va.color[i] as f32 * wa + vb.color[i] as f32 * wb + vc.color[i] as f32 * wc;
+                *channel = vertex * texel[i] as f32 / 255.0;
+            }
+            let dst = &mut image[y * width + x];
+            let keep = 1.0 - src[3] / 255.0;
+            *dst = Color32::from_rgba_premultiplied(
+                (src[0] + dst[0] as f32 * keep).round() as u8,
+                (src[1] + dst[1] as f32 * keep).round() as u8,
+                (src[2] + dst[2] as f32 * keep).round() as u8,
+                (src[3] + dst[3] as f32 * keep).round() as u8,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::server::tests::temp_dir;
+
+    #[test]
+    fn png_has_the_requested_size() {
+        let dir = temp_dir("screenshot");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("screenshot.png");
+        // A screen half the size of the PNG, as when saving from the app
+        let ctx = egui::Context::default();
+        let screen_rect = Rect::from_min_size(Pos2::ZERO, Vec2::new(160.0, 120.0));
+        let input = egui::RawInput {
+            screen_rect: Some(screen_rect),
+            ..Default::default()
+        };
+        let _ = ctx.run(input, |_| {});
+        export_png(&ctx, &path, [320, 240], |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| ui.label("Profile 0"));
+        })
+        .unwrap();
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        assert_eq!((info.width, info.height), (320, 240));
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
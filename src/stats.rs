@@ -0,0 +1,91 @@
+use crate::data::Item;
+use crate::timestamp::{Interval, Timestamp};
+
+/// Summary statistics of the items overlapping an interval. Durations only
+/// count the part of each item inside the interval.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct IntervalStats {
+    pub count: usize,
+    // Sum of durations. Items that overlap each other (e.g., in different
+    // rows or slots) are all counted, so this can exceed the interval.
+    pub busy: Timestamp,
+    pub mean: Timestamp,
+    pub p50: Timestamp,
+    pub p95: Timestamp,
+    pub max: Timestamp,
+}
+
+pub fn compute_stats(items: &[Item], interval: Interval) -> IntervalStats {
+    let mut durations: Vec<i64> = items
+        .iter()
+        .filter_map(|item| item.interval.intersection(interval))
+        .map(|clipped| clipped.duration_ns())
+        .collect();
+    if durations.is_empty() {
+        return IntervalStats::default();
+    }
+    durations.sort_unstable();
+
+    let count = durations.len();
+    let busy: i64 = durations.iter().sum();
+    // Nearest-rank percentile: the smallest duration that at least p% of
+    // items are no longer than
+    let percentile = |p: usize| Timestamp(durations[((p * count + 99) / 100).max(1) - 1]);
+    IntervalStats {
+        count,
+        busy: Timestamp(busy),
+        mean: Timestamp(busy / count as i64),
+        p50: percentile(50),
+        p95: percentile(95),
+        max: Timestamp(durations[count - 1]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Color32, ItemUID};
+
+    // Item i (from 1 to 20) starts at 100 * i and lasts i ns. The last one
+    // is far past the rest.
+    fn items() -> Vec<Item> {
+        let item = |uid: u64, start: i64, stop: i64| Item {
+            item_uid: ItemUID(uid),
+            interval: Interval::new(Timestamp(start), Timestamp(stop)),
+            color: Color32::RED,
+        };
+        let mut items: Vec<_> = (1..=20).map(|i| item(i as u64, 100 * i, 101 * i)).collect();
+        items.push(item(21, 20_000, 20_500));
+        items
+    }
+
+    #[test]
+    fn percentiles_of_known_durations() {
+        let interval = Interval::new(Timestamp(0), Timestamp(10_000));
+        let stats = compute_stats(&items(), interval);
+        assert_eq!(
+            stats,
+            IntervalStats {
+                count: 20,
+                busy: Timestamp(210),
+                mean: Timestamp(10),
+                p50: Timestamp(10),
+                p95: Timestamp(19),
+                max: Timestamp(20),
+            }
+        );
+    }
+
+    #[test]
+    fn items_are_clipped_to_the_interval() {
+        // Item 10 (1000 to 1010) is cut in half
+        let interval = Interval::new(Timestamp(0), Timestamp(1005));
+        let stats = compute_stats(&items(), interval);
+        assert_eq!(stats.count, 10);
+        assert_eq!(stats.busy, Timestamp(45 + 5));
+        assert_eq!(stats.max, Timestamp(9));
+
+        let empty = Interval::new(Timestamp(10_000), Timestamp(20_000));
+        assert_eq!(compute_stats(&items(), empty), IntervalStats::default());
+    }
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::ops::{Add, AddAssign, Range, Sub, SubAssign};
+use std::time::Duration;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize, Serialize)]
 pub struct Timestamp(pub i64 /* ns */);
@@ -11,10 +13,25 @@ impl fmt::Display for Timestamp {
         let ns_per_us = 1_000;
         let ns_per_ms = 1_000_000;
         let ns_per_s = 1_000_000_000;
+        let ns_per_min = 60 * ns_per_s;
+        let ns_per_h = 60 * ns_per_min;
+        let ns_per_d = 24 * ns_per_h;
         let divisor;
         let remainder_divisor;
         let mut unit_name = "ns";
-        if ns >= ns_per_s {
+        if ns >= ns_per_d {
+            divisor = ns_per_d;
+            remainder_divisor = divisor / 1_000;
+            unit_name = "d";
+        } else if ns >= ns_per_h {
+            divisor = ns_per_h;
+            remainder_divisor = divisor / 1_000;
+            unit_name = "h";
+        } else if ns >= ns_per_min {
+            divisor = ns_per_min;
+            remainder_divisor = divisor / 1_000;
+            unit_name = "min";
+        } else if ns >= ns_per_s {
             divisor = ns_per_s;
             remainder_divisor = divisor / 1_000;
             unit_name = "s";
@@ -35,6 +52,77 @@ impl fmt::Display for Timestamp {
     }
 }
 
+impl Timestamp {
+    // Raw nanoseconds with digits grouped by underscores, e.g. "1_234_567"
+    pub fn format_grouped(&self) -> String {
+        let digits = self.0.unsigned_abs().to_string();
+        let mut result = String::with_capacity(digits.len() + digits.len() / 3 + 1);
+        if self.0 < 0 {
+            result.push('-');
+        }
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                result.push('_');
+            }
+            result.push(c);
+        }
+        result
+    }
+
+    // Like to_string, but honoring config. Timestamps under a microsecond
+    // (including negative ones) are shown in raw nanoseconds.
+    pub fn format(self, config: &DisplayConfig) -> String {
+        let ns_per_us = 1_000;
+        if config.group_digits && self.0 < ns_per_us {
+            return format!("{} ns", self.format_grouped());
+        }
+        self.to_string()
+    }
+
+    // Negative timestamps have no Duration equivalent
+    pub fn to_duration(self) -> Option<Duration> {
+        let ns = u64::try_from(self.0).ok()?;
+        Some(Duration::from_nanos(ns))
+    }
+}
+
+// Saturates at i64::MAX nanoseconds (about 292 years)
+impl From<Duration> for Timestamp {
+    fn from(duration: Duration) -> Self {
+        Timestamp(i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX))
+    }
+}
+
+// Note: Timestamp arithmetic uses plain i64 operations, so it panics on
+// overflow/underflow in debug builds and wraps in release builds.
+impl Add for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, rhs: Timestamp) -> Timestamp {
+        Timestamp(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, rhs: Timestamp) -> Timestamp {
+        Timestamp(self.0 - rhs.0)
+    }
+}
+
+impl AddAssign for Timestamp {
+    fn add_assign(&mut self, rhs: Timestamp) {
+        self.0 += rhs.0;
+    }
+}
+
+impl SubAssign for Timestamp {
+    fn sub_assign(&mut self, rhs: Timestamp) {
+        self.0 -= rhs.0;
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize, Serialize)]
 pub struct Interval {
     pub start: Timestamp,
@@ -49,10 +137,25 @@ impl fmt::Display for Interval {
         let ns_per_us = 1_000;
         let ns_per_ms = 1_000_000;
         let ns_per_s = 1_000_000_000;
+        let ns_per_min = 60 * ns_per_s;
+        let ns_per_h = 60 * ns_per_min;
+        let ns_per_d = 24 * ns_per_h;
         let divisor;
         let remainder_divisor;
         let mut unit_name = "ns";
-        if stop_ns >= ns_per_s {
+        if stop_ns >= ns_per_d {
+            divisor = ns_per_d;
+            remainder_divisor = divisor / 1_000;
+            unit_name = "d";
+        } else if stop_ns >= ns_per_h {
+            divisor = ns_per_h;
+            remainder_divisor = divisor / 1_000;
+            unit_name = "h";
+        } else if stop_ns >= ns_per_min {
+            divisor = ns_per_min;
+            remainder_divisor = divisor / 1_000;
+            unit_name = "min";
+        } else if stop_ns >= ns_per_s {
             divisor = ns_per_s;
             remainder_divisor = divisor / 1_000;
             unit_name = "s";
@@ -91,23 +194,75 @@ impl fmt::Display for Interval {
     }
 }
 
+// Opt-in formatting options for places where intervals are shown to the
+// user. The Display impls are not affected by these.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct DisplayConfig {
+    // Group digits of raw nanosecond values (see Timestamp::format_grouped)
+    pub group_digits: bool,
+}
+
+// Adding or subtracting a Timestamp shifts both endpoints of the interval
+impl Add<Timestamp> for Interval {
+    type Output = Interval;
+
+    fn add(self, rhs: Timestamp) -> Interval {
+        self.shifted(rhs)
+    }
+}
+
+impl Sub<Timestamp> for Interval {
+    type Output = Interval;
+
+    fn sub(self, rhs: Timestamp) -> Interval {
+        Interval::new(self.start - rhs, self.stop - rhs)
+    }
+}
+
 impl Interval {
     pub fn new(start: Timestamp, stop: Timestamp) -> Self {
         Self { start, stop }
     }
+    // Move both endpoints by delta (which may be negative)
+    pub fn shifted(self, delta: Timestamp) -> Self {
+        Self::new(self.start + delta, self.stop + delta)
+    }
     pub fn duration_ns(self) -> i64 {
         self.stop.0 - self.start.0
     }
     pub fn contains(self, point: Timestamp) -> bool {
         point >= self.start && point < self.stop
     }
+    // Since stop is exclusive, intervals that merely touch do not overlap
     pub fn overlaps(self, other: Interval) -> bool {
-        !(other.stop < self.start || other.start >= self.stop)
+        !(other.stop <= self.start || other.start >= self.stop)
     }
-    pub fn intersection(self, other: Interval) -> Self {
-        Self {
+    // Returns None when the intervals do not overlap. Since stop is
+    // exclusive, intervals that merely touch do not overlap either
+    pub fn intersection(self, other: Interval) -> Option<Self> {
+        let result = Self {
             start: Timestamp(self.start.0.max(other.start.0)),
             stop: Timestamp(self.stop.0.min(other.stop.0)),
+        };
+        if result.start < result.stop {
+            Some(result)
+        } else {
+            None
+        }
+    }
+    // Clip to bounds. Unlike intersection, this always produces an
+    // interval: if there is no overlap, the result is a zero-duration
+    // interval at the nearest edge of bounds
+    pub fn clamp(self, bounds: Interval) -> Self {
+        if self.stop <= bounds.start {
+            return Self::new(bounds.start, bounds.start);
+        }
+        if self.start >= bounds.stop {
+            return Self::new(bounds.stop, bounds.stop);
+        }
+        Self {
+            start: self.start.max(bounds.start),
+            stop: self.stop.min(bounds.stop),
         }
     }
     pub fn union(self, other: Interval) -> Self {
@@ -116,32 +271,788 @@ impl Interval {
             stop: Timestamp(self.stop.0.max(other.stop.0)),
         }
     }
-    // Convert a timestamp into [0,1] relative space
+    // Like to_string, but honoring config
+    pub fn format(self, config: &DisplayConfig) -> String {
+        if !config.group_digits {
+            return self.to_string();
+        }
+        let ns_per_us = 1_000;
+        let duration = Timestamp(self.duration_ns()).format(config);
+        if self.stop.0 < ns_per_us {
+            return format!(
+                "from {} to {} ns (duration: {duration})",
+                self.start.format_grouped(),
+                self.stop.format_grouped(),
+            );
+        }
+        // Only the duration can be in raw nanoseconds
+        let display = self.to_string();
+        let prefix = display.rsplit_once(" (duration: ").unwrap().0;
+        format!("{prefix} (duration: {duration})")
+    }
+    // Returns None if either endpoint is negative
+    pub fn to_std_range(self) -> Option<Range<Duration>> {
+        Some(self.start.to_duration()?..self.stop.to_duration()?)
+    }
+    // Split into n contiguous pieces of equal duration. The last piece
+    // absorbs any rounding remainder so that it ends exactly at self.stop
+    pub fn split_evenly(self, n: usize) -> Vec<Interval> {
+        if n == 0 {
+            return Vec::new();
+        }
+        let step = self.duration_ns() / n as i64;
+        let mut result = Vec::with_capacity(n);
+        for i in 0..n as i64 {
+            let start = Timestamp(self.start.0 + i * step);
+            let stop = if i == n as i64 - 1 {
+                self.stop
+            } else {
+                Timestamp(self.start.0 + (i + 1) * step)
+            };
+            result.push(Interval::new(start, stop));
+        }
+        result
+    }
+    // Convert a timestamp into [0,1] relative space. Everything maps to 0
+    // in a zero-duration interval (rather than NaN or infinity)
     pub fn unlerp(self, time: Timestamp) -> f32 {
+        if self.duration_ns() == 0 {
+            return 0.0;
+        }
         (time.0 - self.start.0) as f32 / (self.duration_ns() as f32)
     }
     // Convert [0,1] relative space into a timestamp
     pub fn lerp(self, value: f32) -> Timestamp {
         Timestamp((value * (self.duration_ns() as f32)).round() as i64 + self.start.0)
     }
-    
+    // Shrink the interval by factor (or grow it, if factor < 1) such that
+    // the timestamp at relative position cursor_frac stays at that position,
+    // i.e., under the same pixel
+    pub fn zoom_at(self, cursor_frac: f32, factor: f32) -> Self {
+        let anchor = self.lerp(cursor_frac);
+        let duration = ((self.duration_ns() as f64 / factor as f64) as i64).max(1);
+        let start = anchor.0 - (cursor_frac as f64 * duration as f64).round() as i64;
+        Self::new(Timestamp(start), Timestamp(start + duration))
+    }
+
     // convert a string like "500.0 s" to a timestamp
-    pub fn convert_str_to_timestamp(s: &str) -> Result<Timestamp, String> {
-        let mut parts = s.split_whitespace();
-        let value = parts.next().ok_or("no value")?;
-        let value = value.parse::<f32>().map_err(|_| "invalid value")?;
-        let unit = parts.next().ok_or("no unit")?;
+    pub fn convert_str_to_timestamp(s: &str) -> Result<Timestamp, TimestampParseError> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(TimestampParseError::NoValue);
+        }
+        let (value, unit) = s.split_at(Self::numeric_prefix_len(s));
+        if value.is_empty() {
+            return Err(TimestampParseError::InvalidValue);
+        }
+        let unit = unit.trim();
+        if unit.is_empty() {
+            return Err(TimestampParseError::NoUnit);
+        }
         let unit = unit.to_lowercase();
-        let ns_per_us = 1_000;
-        let ns_per_ms = 1_000_000;
-        let ns_per_s = 1_000_000_000;
-        let ns = match unit.as_str() {
-            "ns" => value as i64,
-            "us" => (value * ns_per_us as f32) as i64,
-            "ms" => (value * ns_per_ms as f32) as i64,
-            "s" => (value * ns_per_s as f32) as i64,
-            _ => return Err(format!("invalid unit: {}", unit)),
+        let ns_per_us: i64 = 1_000;
+        let ns_per_ms: i64 = 1_000_000;
+        let ns_per_s: i64 = 1_000_000_000;
+        let ns_per_min = 60 * ns_per_s;
+        let ns_per_h = 60 * ns_per_min;
+        let ns_per_d = 24 * ns_per_h;
+        let ns_per_unit = match unit.as_str() {
+            "ns" => 1,
+            "us" => ns_per_us,
+            "ms" => ns_per_ms,
+            "s" => ns_per_s,
+            "min" => ns_per_min,
+            "h" => ns_per_h,
+            "d" => ns_per_d,
+            _ => return Err(TimestampParseError::InvalidUnit(unit)),
+        };
+        // Integers are converted exactly, all the way up to i64::MAX ns
+        if let Ok(value) = value.parse::<i64>() {
+            return value
+                .checked_mul(ns_per_unit)
+                .map(Timestamp)
+                .ok_or(TimestampParseError::InvalidValue);
+        }
+        // f64 is needed here: f32 can't represent large values like
+        // "90.5 min" exactly
+        let value = value
+            .parse::<f64>()
+            .map_err(|_| TimestampParseError::InvalidValue)?;
+        let ns = value * ns_per_unit as f64;
+        // Casting would silently saturate. Note i64::MAX as f64 rounds up to
+        // 2^63, which is itself out of range, while i64::MIN is exact.
+        if !ns.is_finite() || ns >= i64::MAX as f64 || ns < i64::MIN as f64 {
+            return Err(TimestampParseError::InvalidValue);
+        }
+        Ok(Timestamp(ns as i64))
+    }
+
+    // convert a string like "100 ms to 200 ms", "100ms-200ms" or
+    // "[100ms, 200ms]" to an interval
+    pub fn parse_interval(s: &str) -> Result<Interval, IntervalParseError> {
+        let mut s = s.trim();
+        if let Some(inner) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            s = inner.trim();
+        }
+        if s.get(..4).map_or(false, |p| p.eq_ignore_ascii_case("from")) {
+            s = s[4..].trim_start();
+        }
+
+        // Note: to_ascii_lowercase preserves byte offsets, so indices into
+        // lower are valid for s
+        let lower = s.to_ascii_lowercase();
+        let (start, stop) = if let Some(i) = lower.find(" to ") {
+            (&s[..i], &s[i + 4..])
+        } else if let Some(i) = s.find(',') {
+            (&s[..i], &s[i + 1..])
+        } else {
+            // Skip the leading number so that a sign or exponent is not
+            // mistaken for the separator
+            let skip = Self::numeric_prefix_len(s);
+            let i = s[skip..].find('-').ok_or(IntervalParseError::NoSeparator)?;
+            (&s[..skip + i], &s[skip + i + 1..])
         };
-        Ok(Timestamp(ns))
+
+        let start =
+            Self::convert_str_to_timestamp(start).map_err(IntervalParseError::InvalidStart)?;
+        let stop = Self::convert_str_to_timestamp(stop).map_err(IntervalParseError::InvalidStop)?;
+        if start > stop {
+            return Err(IntervalParseError::StartAfterStop);
+        }
+        Ok(Interval::new(start, stop))
+    }
+
+    // Check an interval entered by the user against the bounds of the
+    // profile. Parts of the interval may lie outside the bounds, as long as
+    // it overlaps them.
+    pub fn validate_against(self, bounds: Interval) -> Result<(), IntervalParseError> {
+        if self.start > self.stop {
+            return Err(IntervalParseError::StartAfterStop);
+        }
+        if self.start > bounds.stop {
+            return Err(IntervalParseError::StartAfterEnd);
+        }
+        if self.stop < bounds.start {
+            return Err(IntervalParseError::StopBeforeStart);
+        }
+        Ok(())
+    }
+
+    // Length of the leading number (sign, digits, decimal point, exponent)
+    // in s. Everything after that is the unit, e.g. "1e3ms" splits into
+    // "1e3" and "ms".
+    fn numeric_prefix_len(s: &str) -> usize {
+        let bytes = s.as_bytes();
+        let digits = |mut i: usize| {
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            i
+        };
+        let mut i = 0;
+        if i < bytes.len() && (bytes[i] == b'+' || bytes[i] == b'-') {
+            i += 1;
+        }
+        i = digits(i);
+        if i < bytes.len() && bytes[i] == b'.' {
+            i = digits(i + 1);
+        }
+        // Only consume an exponent if it is followed by digits, so that a
+        // unit is never swallowed
+        if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+            let mut j = i + 1;
+            if j < bytes.len() && (bytes[j] == b'+' || bytes[j] == b'-') {
+                j += 1;
+            }
+            let end = digits(j);
+            if end > j {
+                i = end;
+            }
+        }
+        i
+    }
+}
+
+// Picks tick marks for a time axis. Every tick on the axis uses the same
+// unit, and ticks fall on round values (1, 2 or 5 times a power of ten
+// nanoseconds), e.g. "1.2 ms", "1.4 ms", "1.6 ms".
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AxisFormatter {
+    // Minimum distance between ticks, in pixels
+    pub min_spacing: f32,
+}
+
+impl Default for AxisFormatter {
+    fn default() -> Self {
+        Self { min_spacing: 80.0 }
+    }
+}
+
+impl AxisFormatter {
+    const UNITS: [(i64, &'static str); 4] = [
+        (1, "ns"),
+        (1_000, "us"),
+        (1_000_000, "ms"),
+        (1_000_000_000, "s"),
+    ];
+
+    pub fn new(min_spacing: f32) -> Self {
+        Self { min_spacing }
+    }
+
+    // Ticks across an axis width pixels wide showing interval, as the
+    // offset of each tick from the left edge and its label
+    pub fn ticks(&self, interval: Interval, width: f32) -> Vec<(f32, String)> {
+        if interval.duration_ns() <= 0 || width <= 0.0 {
+            return Vec::new();
+        }
+        let step = self.step(interval, width);
+
+        // The largest unit a step is at least a tenth of, so that labels
+        // need at most one decimal place
+        let (unit, unit_name) = Self::UNITS
+            .iter()
+            .rev()
+            .copied()
+            .find(|(unit, _)| step.saturating_mul(10) >= *unit)
+            .unwrap_or(Self::UNITS[0]);
+        let decimals = usize::from(step % unit != 0);
+
+        Self::tick_times(interval, width, step, |time| {
+            let value = time as f64 / unit as f64;
+            format!("{value:.decimals$} {unit_name}")
+        })
+    }
+
+    // Like ticks, but labeled with the UTC time of day, given the wall-clock
+    // time of timestamp 0 (in ns since the Unix epoch). Ticks fall on round
+    // wall-clock times.
+    pub fn wall_clock_ticks(
+        &self,
+        interval: Interval,
+        width: f32,
+        origin: Timestamp,
+    ) -> Vec<(f32, String)> {
+        let absolute = Interval::new(
+            Timestamp(interval.start.0.saturating_add(origin.0)),
+            Timestamp(interval.stop.0.saturating_add(origin.0)),
+        );
+        if absolute.duration_ns() <= 0 || width <= 0.0 {
+            return Vec::new();
+        }
+        // Steps of a second or more fall on round minutes and hours
+        const CLOCK_STEPS: [i64; 18] = [
+            1, 2, 5, 10, 15, 30, 60, 120, 300, 600, 900, 1800, 3600, 7200, 10800, 21600, 43200,
+            86400,
+        ];
+        let min_step = self.min_step(absolute, width);
+        let step = CLOCK_STEPS
+            .iter()
+            .map(|secs| secs * 1_000_000_000)
+            .find(|step| min_step > 1e9 && *step as f64 >= min_step)
+            .unwrap_or_else(|| self.step(absolute, width));
+
+        // Enough digits after the seconds to tell ticks apart
+        let mut digits = 9;
+        let mut scale = 1;
+        while digits > 0 && step % (scale * 10) == 0 {
+            digits -= 1;
+            scale *= 10;
+        }
+
+        Self::tick_times(absolute, width, step, |time| {
+            let secs = time.div_euclid(1_000_000_000).rem_euclid(86_400);
+            let mut label = format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+            if digits > 0 {
+                let fraction = time.rem_euclid(1_000_000_000) / scale;
+                label += &format!(".{fraction:0digits$}");
+            }
+            label
+        })
+    }
+
+    // Every multiple of step in interval, with its offset from the left edge
+    // of an axis width pixels wide
+    fn tick_times(
+        interval: Interval,
+        width: f32,
+        step: i64,
+        mut label: impl FnMut(i64) -> String,
+    ) -> Vec<(f32, String)> {
+        let mut ticks = Vec::new();
+        let mut time = interval.start.0.div_euclid(step) * step;
+        if time < interval.start.0 {
+            time += step;
+        }
+        while time <= interval.stop.0 {
+            let x = interval.unlerp(Timestamp(time)) * width;
+            ticks.push((x, label(time)));
+            time = match time.checked_add(step) {
+                Some(time) => time,
+                None => break,
+            };
+        }
+        ticks
+    }
+
+    fn min_step(&self, interval: Interval, width: f32) -> f64 {
+        let ns_per_pixel = interval.duration_ns() as f64 / width as f64;
+        ns_per_pixel * self.min_spacing.max(1.0) as f64
+    }
+
+    // The smallest round step that keeps ticks min_spacing pixels apart
+    fn step(&self, interval: Interval, width: f32) -> i64 {
+        let min_step = self.min_step(interval, width);
+        let mut power = 1i64;
+        loop {
+            for multiple in [1, 2, 5] {
+                let step = power.saturating_mul(multiple);
+                if step as f64 >= min_step || step == i64::MAX {
+                    return step;
+                }
+            }
+            power = power.saturating_mul(10);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimestampParseError {
+    NoValue,
+    InvalidValue,
+    NoUnit,
+    InvalidUnit(String),
+}
+
+impl fmt::Display for TimestampParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimestampParseError::NoValue => write!(f, "no value"),
+            TimestampParseError::InvalidValue => write!(f, "invalid value"),
+            TimestampParseError::NoUnit => write!(f, "no unit"),
+            TimestampParseError::InvalidUnit(unit) => write!(f, "invalid unit: {unit}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntervalParseError {
+    NoSeparator,
+    InvalidStart(TimestampParseError),
+    InvalidStop(TimestampParseError),
+    StartAfterStop,
+    // The interval lies entirely after/before the bounds it was checked
+    // against, see validate_against
+    StartAfterEnd,
+    StopBeforeStart,
+}
+
+impl fmt::Display for IntervalParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntervalParseError::NoSeparator => write!(f, "expected \"<start> to <stop>\""),
+            IntervalParseError::InvalidStart(e) => write!(f, "start: {e}"),
+            IntervalParseError::InvalidStop(e) => write!(f, "stop: {e}"),
+            IntervalParseError::StartAfterStop => write!(f, "start must be before stop"),
+            IntervalParseError::StartAfterEnd => write!(f, "start must be before end of trace"),
+            IntervalParseError::StopBeforeStart => write!(f, "stop must be after start of trace"),
+        }
+    }
+}
+
+// For use with #[serde(with = "human_readable")] where readability matters
+// more than size, e.g., in manifests. Timestamps are written like "1.500 s",
+// or in nanoseconds if that form would lose precision. Plain integers are
+// still accepted when reading, so this only works with self-describing
+// formats like JSON.
+pub mod human_readable {
+    use serde::de::{self, Visitor};
+    use serde::{Deserializer, Serializer};
+    use std::fmt;
+
+    use super::{Interval, Timestamp};
+
+    pub fn serialize<S: Serializer>(
+        timestamp: &Timestamp,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&to_string(*timestamp))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Timestamp, D::Error> {
+        deserializer.deserialize_any(TimestampVisitor)
+    }
+
+    pub fn to_string(timestamp: Timestamp) -> String {
+        let display = timestamp.to_string();
+        if Interval::convert_str_to_timestamp(&display) == Ok(timestamp) {
+            display
+        } else {
+            format!("{} ns", timestamp.0)
+        }
+    }
+
+    struct TimestampVisitor;
+
+    impl<'de> Visitor<'de> for TimestampVisitor {
+        type Value = Timestamp;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "a timestamp like \"1.500 s\" or an integer in nanoseconds"
+            )
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<Timestamp, E> {
+            Ok(Timestamp(value))
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<Timestamp, E> {
+            i64::try_from(value)
+                .map(Timestamp)
+                .map_err(|_| E::custom("timestamp out of range"))
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Timestamp, E> {
+            Interval::convert_str_to_timestamp(value).map_err(E::custom)
+        }
+    }
+}
+
+// Interval counterpart of human_readable, written as
+// {"start": "1.500 s", "stop": "2.000 s"}
+pub mod human_readable_interval {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{human_readable, Interval, Timestamp};
+
+    #[derive(Deserialize, Serialize)]
+    struct Repr {
+        #[serde(with = "human_readable")]
+        start: Timestamp,
+        #[serde(with = "human_readable")]
+        stop: Timestamp,
+    }
+
+    pub fn serialize<S: Serializer>(interval: &Interval, serializer: S) -> Result<S::Ok, S::Error> {
+        Repr {
+            start: interval.start,
+            stop: interval.stop,
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Interval, D::Error> {
+        let Repr { start, stop } = Repr::deserialize(deserializer)?;
+        Ok(Interval::new(start, stop))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digits_are_grouped_in_threes() {
+        assert_eq!(Timestamp(0).format_grouped(), "0");
+        assert_eq!(Timestamp(999).format_grouped(), "999");
+        assert_eq!(Timestamp(1000).format_grouped(), "1_000");
+        assert_eq!(Timestamp(1234567).format_grouped(), "1_234_567");
+        assert_eq!(Timestamp(-1000).format_grouped(), "-1_000");
+    }
+
+    #[test]
+    fn raw_nanoseconds_are_grouped() {
+        let grouped = DisplayConfig { group_digits: true };
+        let plain = DisplayConfig::default();
+
+        assert_eq!(Timestamp(-1_234_567).format(&grouped), "-1_234_567 ns");
+        assert_eq!(Timestamp(-1_234_567).format(&plain), "-1234567 ns");
+        assert_eq!(Timestamp(1_234_567).format(&grouped), "1.234 ms");
+
+        let negative = Interval::new(Timestamp(-1_234_567), Timestamp(500));
+        assert_eq!(
+            negative.format(&grouped),
+            "from -1_234_567 to 500 ns (duration: 1.235 ms)"
+        );
+        let inverted = Interval::new(Timestamp(2_000_000), Timestamp(1_000));
+        assert_eq!(
+            inverted.format(&grouped),
+            "from 2000.000 to 1.000 us (duration: -1_999_000 ns)"
+        );
+        assert_eq!(inverted.format(&plain), inverted.to_string());
+    }
+
+    #[test]
+    fn minutes_hours_and_days_round_trip() {
+        let ns_per_min = 60 * 1_000_000_000;
+        for (text, ns) in [
+            ("90 min", 90 * ns_per_min),
+            ("90.5 min", 5_430_000_000_000),
+            ("1.5 h", 90 * ns_per_min),
+            ("2 d", 2 * 24 * 60 * ns_per_min),
+        ] {
+            assert_eq!(Interval::convert_str_to_timestamp(text), Ok(Timestamp(ns)));
+        }
+        assert_eq!(Timestamp(61 * 1_000_000_000).to_string(), "1.016 min");
+        assert_eq!(Timestamp(90 * ns_per_min).to_string(), "1.500 h");
+        assert_eq!(Timestamp(2 * 24 * 60 * ns_per_min).to_string(), "2.000 d");
+    }
+
+    #[test]
+    fn arithmetic_shifts_timestamps_and_intervals() {
+        let mut t = Timestamp(100) + Timestamp(50);
+        assert_eq!(t, Timestamp(150));
+        t -= Timestamp(200);
+        assert_eq!(t, Timestamp(-50));
+        t += Timestamp(60);
+        assert_eq!(t - Timestamp(10), Timestamp(0));
+
+        let interval = Interval::new(Timestamp(0), Timestamp(100));
+        assert_eq!(
+            interval + Timestamp(10),
+            Interval::new(Timestamp(10), Timestamp(110))
+        );
+        assert_eq!(
+            interval - Timestamp(10),
+            Interval::new(Timestamp(-10), Timestamp(90))
+        );
+        assert_eq!(interval.shifted(Timestamp(-10)), interval - Timestamp(10));
+    }
+
+    #[test]
+    fn numbers_and_units_split_deterministically() {
+        let parse = Interval::convert_str_to_timestamp;
+        assert_eq!(parse("1e3ms"), Ok(Timestamp(1_000_000_000)));
+        assert_eq!(parse("1e3 ms"), Ok(Timestamp(1_000_000_000)));
+        assert_eq!(parse(".5s"), Ok(Timestamp(500_000_000)));
+        assert_eq!(parse("s500"), Err(TimestampParseError::InvalidValue));
+        assert_eq!(parse("foo ms"), Err(TimestampParseError::InvalidValue));
+        assert_eq!(parse("500.0"), Err(TimestampParseError::NoUnit));
+        assert_eq!(parse("  "), Err(TimestampParseError::NoValue));
+        assert_eq!(
+            parse("5 parsecs"),
+            Err(TimestampParseError::InvalidUnit("parsecs".to_owned()))
+        );
+    }
+
+    #[test]
+    fn split_evenly_is_contiguous() {
+        let interval = Interval::new(Timestamp(0), Timestamp(1000));
+        let pieces = interval.split_evenly(3);
+        let expected: Vec<_> = [(0, 333), (333, 666), (666, 1000)]
+            .into_iter()
+            .map(|(start, stop)| Interval::new(Timestamp(start), Timestamp(stop)))
+            .collect();
+        assert_eq!(pieces, expected);
+        assert_eq!(
+            pieces
+                .iter()
+                .fold(pieces[0], |union, piece| union.union(*piece)),
+            interval
+        );
+        assert_eq!(interval.split_evenly(1), vec![interval]);
+        assert!(interval.split_evenly(0).is_empty());
+    }
+
+    #[test]
+    fn clamp_stays_within_bounds() {
+        let interval = |start, stop| Interval::new(Timestamp(start), Timestamp(stop));
+        let bounds = interval(100, 200);
+        // Fully inside
+        assert_eq!(interval(120, 180).clamp(bounds), interval(120, 180));
+        // Partially overlapping
+        assert_eq!(interval(50, 150).clamp(bounds), interval(100, 150));
+        assert_eq!(interval(150, 250).clamp(bounds), interval(150, 200));
+        assert_eq!(interval(0, 300).clamp(bounds), bounds);
+        // Fully disjoint, at the nearest edge
+        assert_eq!(interval(0, 50).clamp(bounds), interval(100, 100));
+        assert_eq!(interval(250, 300).clamp(bounds), interval(200, 200));
+    }
+
+    #[test]
+    fn disjoint_intervals_have_no_intersection() {
+        let interval = |start, stop| Interval::new(Timestamp(start), Timestamp(stop));
+        assert_eq!(interval(0, 10).intersection(interval(20, 30)), None);
+        assert_eq!(interval(20, 30).intersection(interval(0, 10)), None);
+        // Touching intervals share no time either
+        assert_eq!(interval(0, 10).intersection(interval(10, 20)), None);
+        assert_eq!(
+            interval(0, 10).intersection(interval(9, 20)),
+            Some(interval(9, 10))
+        );
+    }
+
+    #[test]
+    fn durations_round_trip() {
+        let duration = Duration::from_millis(1500);
+        assert_eq!(Timestamp::from(duration), Timestamp(1_500_000_000));
+        assert_eq!(Timestamp(1_500_000_000).to_duration(), Some(duration));
+        assert_eq!(Timestamp(-1).to_duration(), None);
+        assert_eq!(Timestamp::from(Duration::MAX), Timestamp(i64::MAX));
+
+        let interval = Interval::new(Timestamp(0), Timestamp(1_500_000_000));
+        assert_eq!(interval.to_std_range(), Some(Duration::ZERO..duration));
+        assert_eq!((interval - Timestamp(1)).to_std_range(), None);
+    }
+
+    #[test]
+    fn interval_syntaxes_parse() {
+        let expected = Interval::new(Timestamp(100_000_000), Timestamp(200_000_000));
+        for text in [
+            "100 ms to 200 ms",
+            "from 100 ms to 200 ms",
+            "100ms-200ms",
+            "[100ms, 200ms]",
+        ] {
+            assert_eq!(Interval::parse_interval(text), Ok(expected), "{text}");
+        }
+        assert_eq!(
+            Interval::parse_interval("200 ms to 100 ms"),
+            Err(IntervalParseError::StartAfterStop)
+        );
+        assert_eq!(
+            Interval::parse_interval("100 ms"),
+            Err(IntervalParseError::NoSeparator)
+        );
+    }
+
+    #[test]
+    fn touching_intervals_do_not_overlap() {
+        let interval = |start, stop| Interval::new(Timestamp(start), Timestamp(stop));
+        let tile = interval(100, 200);
+        // Touching at start and at end
+        assert!(!tile.overlaps(interval(0, 100)));
+        assert!(!tile.overlaps(interval(200, 300)));
+        assert!(!interval(0, 100).overlaps(tile));
+        assert!(tile.overlaps(interval(0, 101)));
+        assert!(tile.overlaps(interval(199, 300)));
+    }
+
+    #[test]
+    fn human_readable_and_compact_forms_round_trip() {
+        #[derive(Debug, PartialEq, Deserialize, Serialize)]
+        struct Manifest {
+            #[serde(with = "human_readable")]
+            time: Timestamp,
+            #[serde(with = "human_readable_interval")]
+            interval: Interval,
+        }
+
+        let manifest = Manifest {
+            time: Timestamp(1_500_000_000),
+            // Not exactly representable in milliseconds
+            interval: Interval::new(Timestamp(0), Timestamp(1_000_001)),
+        };
+        let json = serde_json::to_string(&manifest).unwrap();
+        assert_eq!(
+            json,
+            r#"{"time":"1.500 s","interval":{"start":"0 ns","stop":"1000001 ns"}}"#
+        );
+        assert_eq!(serde_json::from_str::<Manifest>(&json).unwrap(), manifest);
+        // Plain integers are read too
+        let compact = r#"{"time":1500000000,"interval":{"start":0,"stop":1000001}}"#;
+        assert_eq!(serde_json::from_str::<Manifest>(compact).unwrap(), manifest);
+
+        // The wire format stays compact
+        let json = serde_json::to_string(&manifest.interval).unwrap();
+        assert_eq!(json, r#"{"start":0,"stop":1000001}"#);
+        assert_eq!(
+            serde_json::from_str::<Interval>(&json).unwrap(),
+            manifest.interval
+        );
+    }
+
+    #[test]
+    fn intervals_are_validated_against_bounds() {
+        let interval = |start, stop| Interval::new(Timestamp(start), Timestamp(stop));
+        let bounds = interval(100, 200);
+        assert_eq!(interval(150, 250).validate_against(bounds), Ok(()));
+        assert_eq!(
+            interval(180, 120).validate_against(bounds),
+            Err(IntervalParseError::StartAfterStop)
+        );
+        assert_eq!(
+            interval(250, 300).validate_against(bounds),
+            Err(IntervalParseError::StartAfterEnd)
+        );
+        assert_eq!(
+            interval(0, 50).validate_against(bounds),
+            Err(IntervalParseError::StopBeforeStart)
+        );
+    }
+
+    #[test]
+    fn zero_duration_intervals_map_sanely() {
+        let empty = Interval::new(Timestamp(5), Timestamp(5));
+        assert_eq!(empty.unlerp(Timestamp(5)), 0.0);
+        assert_eq!(empty.unlerp(Timestamp(100)), 0.0);
+        assert_eq!(empty.lerp(0.5), Timestamp(5));
+        assert!(AxisFormatter::default().ticks(empty, 800.0).is_empty());
+    }
+
+    #[test]
+    fn overflowing_timestamps_are_rejected() {
+        let parse = Interval::convert_str_to_timestamp;
+        assert_eq!(parse("1e30 s"), Err(TimestampParseError::InvalidValue));
+        assert_eq!(parse("-1e30 s"), Err(TimestampParseError::InvalidValue));
+        assert_eq!(parse("inf s"), Err(TimestampParseError::InvalidValue));
+        // The largest valid value, and one past it
+        assert_eq!(parse("9223372036854775807 ns"), Ok(Timestamp(i64::MAX)));
+        assert_eq!(
+            parse("9223372036854775808 ns"),
+            Err(TimestampParseError::InvalidValue)
+        );
+        assert_eq!(
+            parse("9223372036854775807 s"),
+            Err(TimestampParseError::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn microsecond_axes_get_microsecond_ticks() {
+        let interval = Interval::new(Timestamp(1_000_000), Timestamp(1_010_000));
+        let ticks = AxisFormatter::new(80.0).ticks(interval, 800.0);
+        let labels: Vec<_> = ticks.iter().map(|(_, label)| label.as_str()).collect();
+        assert_eq!(labels.len(), 11);
+        assert_eq!(labels[0], "1000 us");
+        assert_eq!(labels[10], "1010 us");
+        for pair in ticks.windows(2) {
+            assert!(pair[1].0 - pair[0].0 >= 79.9);
+        }
+
+        // Steps smaller than the unit get a decimal place
+        let ticks = AxisFormatter::new(80.0).ticks(interval, 4000.0);
+        assert_eq!(ticks[1].1, "1000.2 us");
+    }
+
+    #[test]
+    fn wall_clock_ticks_fall_on_round_times() {
+        // 10 minutes, starting 30 s after timestamp 0 (at 03:04:05 UTC)
+        let interval = Interval::new(Timestamp(30_000_000_000), Timestamp(630_000_000_000));
+        let origin = Timestamp(1_672_628_645_000_000_000);
+        let ticks = AxisFormatter::new(80.0).wall_clock_ticks(interval, 800.0, origin);
+        let labels: Vec<_> = ticks.iter().map(|(_, label)| label.as_str()).collect();
+        assert_eq!(labels.first(), Some(&"03:05:00"));
+        assert_eq!(labels.last(), Some(&"03:14:00"));
+        assert_eq!(labels.len(), 10);
+        assert!(ticks[0].0 > 0.0);
+    }
+
+    #[test]
+    fn zooming_keeps_the_cursor_in_place() {
+        let interval = Interval::new(Timestamp(1000), Timestamp(2000));
+        let anchor = interval.lerp(0.25);
+        assert_eq!(anchor, Timestamp(1250));
+
+        let zoomed = interval.zoom_at(0.25, 2.0);
+        assert_eq!(zoomed, Interval::new(Timestamp(1125), Timestamp(1625)));
+        assert_eq!(zoomed.unlerp(anchor), 0.25);
+
+        // And back out again
+        let unzoomed = zoomed.zoom_at(0.25, 0.5);
+        assert_eq!(unzoomed, interval);
+        assert_eq!(unzoomed.unlerp(anchor), 0.25);
     }
 }